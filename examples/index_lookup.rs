@@ -0,0 +1,59 @@
+//! Compares an equality lookup through an index with the same lookup done as a full table scan,
+//! counting page reads and timing both.
+//!
+//!     cargo run --release --example index_lookup -- [db] [table] [column] [value]
+//!
+//! The defaults query `companies.db` from `download_sample_databases.sh`, whose `country`
+//! column is indexed. Writing the column as `+column` keeps the planner from using the index,
+//! as in SQLite, which gives the full-scan side of the comparison.
+
+use anyhow::{Result, bail, ensure};
+use codecrafters_sqlite::Database;
+use std::time::{Duration, Instant};
+
+const RUNS: u32 = 10;
+
+struct Measurement {
+    rows: usize,
+    pages: u64,
+    elapsed: Duration,
+}
+
+fn measure(db: &mut Database, sql: &str) -> Result<Measurement> {
+    let mut elapsed = Duration::ZERO;
+    let mut result = None;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let rows = db.query(sql)?.collect::<Result<Vec<_>, _>>()?.len();
+        elapsed += start.elapsed();
+        result = Some((rows, db.pages_read()));
+    }
+
+    let (rows, pages) = result.expect("RUNS is not zero");
+    Ok(Measurement { rows, pages, elapsed: elapsed / RUNS })
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let arg = |i: usize, default: &'static str| args.get(i).map_or(default, String::as_str);
+    let (db_path, table, column, value) = (arg(0, "companies.db"), arg(1, "companies"), arg(2, "country"), arg(3, "eritrea"));
+
+    let mut db = Database::open(db_path)?;
+    let value = value.replace('\'', "''");
+    let indexed = measure(&mut db, &format!("SELECT * FROM {} WHERE {} = '{}'", table, column, value))?;
+    let scanned = measure(&mut db, &format!("SELECT * FROM {} WHERE +{} = '{}'", table, column, value))?;
+
+    println!("{:<10} {:>6} {:>8} {:>12}", "", "rows", "pages", "time");
+    for (name, m) in [("index", &indexed), ("full scan", &scanned)] {
+        println!("{:<10} {:>6} {:>8} {:>12.3?}", name, m.rows, m.pages, m.elapsed);
+    }
+
+    if indexed.rows != scanned.rows {
+        bail!("the index lookup returned {} rows but the scan {}", indexed.rows, scanned.rows);
+    }
+    // Reading at most half the pages is a loose bound; a selective lookup reads far fewer.
+    ensure!(indexed.pages * 2 <= scanned.pages,
+        "the index lookup read {} pages, not substantially fewer than the scan's {}", indexed.pages, scanned.pages);
+
+    Ok(())
+}
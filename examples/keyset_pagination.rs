@@ -0,0 +1,43 @@
+//! Pages through a table with `Database::query_after_rowid`, checking that the pages together
+//! hold every row exactly once, and compares the page reads of fetching the last page that way
+//! with fetching it by OFFSET.
+//!
+//!     cargo run --release --example keyset_pagination -- [db] [table] [page size]
+//!
+//! The defaults query `companies.db` from `download_sample_databases.sh`.
+
+use anyhow::{Result, ensure};
+use codecrafters_sqlite::{Database, Value};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let arg = |i: usize, default: &'static str| args.get(i).map_or(default, String::as_str);
+    let (db_path, table, page_len) = (arg(0, "companies.db"), arg(1, "companies"), arg(2, "100").parse::<usize>()?);
+
+    let mut db = Database::open(db_path)?;
+    let all = db.query(&format!("SELECT rowid, * FROM {} WHERE rowid > {} ORDER BY rowid", table, i64::MIN))?.collect::<Result<Vec<_>, _>>()?;
+
+    // Page reads are kept for the last non-empty page, the one the OFFSET query fetches below.
+    let (mut paged, mut pages, mut after, mut keyset_pages) = (vec![], 0, i64::MIN, 0);
+    loop {
+        let page = db.query_after_rowid(table, after, page_len)?.collect::<Result<Vec<_>, _>>()?;
+        let Some(Value::Integer(last)) = page.last().map(|row| row[0].clone()) else { break };
+        keyset_pages = db.pages_read();
+        after = last;
+        pages += 1;
+        paged.extend(page);
+    }
+    ensure!(paged == all, "paging returned {} rows, not the table's {} in rowid order", paged.len(), all.len());
+
+    let offset = pages.max(1) * page_len - page_len;
+    let by_offset = db.query(&format!("SELECT rowid, * FROM {} WHERE rowid > {} ORDER BY rowid LIMIT {} OFFSET {}", table, i64::MIN, page_len, offset))?.collect::<Result<Vec<_>, _>>()?;
+    let offset_pages = db.pages_read();
+    ensure!(by_offset == all[offset.min(all.len())..], "OFFSET returned a different last page");
+
+    println!("{} rows in {} pages of up to {}", paged.len(), pages, page_len);
+    println!("{:<10} {:>8}", "", "pages");
+    println!("{:<10} {:>8}", "keyset", keyset_pages);
+    println!("{:<10} {:>8}", "offset", offset_pages);
+
+    Ok(())
+}
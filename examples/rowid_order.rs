@@ -0,0 +1,32 @@
+//! Checks that `ORDER BY rowid DESC LIMIT n` reads the table b-tree from the right instead of
+//! scanning and sorting the whole table, by counting page reads.
+//!
+//!     cargo run --release --example rowid_order -- [db] [table] [limit]
+//!
+//! The defaults query `companies.db` from `download_sample_databases.sh`. Ordering by
+//! `rowid + 0` sorts the same rows without the b-tree's help, which gives the comparison.
+
+use anyhow::{Result, ensure};
+use codecrafters_sqlite::Database;
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let arg = |i: usize, default: &'static str| args.get(i).map_or(default, String::as_str);
+    let (db_path, table, limit) = (arg(0, "companies.db"), arg(1, "companies"), arg(2, "10"));
+
+    let mut db = Database::open(db_path)?;
+    let ordered = db.query(&format!("SELECT rowid FROM {} ORDER BY rowid DESC LIMIT {}", table, limit))?.collect::<Result<Vec<_>, _>>()?;
+    let ordered_pages = db.pages_read();
+    let sorted = db.query(&format!("SELECT rowid FROM {} ORDER BY rowid + 0 DESC LIMIT {}", table, limit))?.collect::<Result<Vec<_>, _>>()?;
+    let sorted_pages = db.pages_read();
+
+    println!("{:<10} {:>6} {:>8}", "", "rows", "pages");
+    println!("{:<10} {:>6} {:>8}", "b-tree", ordered.len(), ordered_pages);
+    println!("{:<10} {:>6} {:>8}", "sort", sorted.len(), sorted_pages);
+
+    ensure!(ordered == sorted, "reading in rowid order returned different rows than sorting");
+    ensure!(ordered_pages * 2 <= sorted_pages,
+        "reading in rowid order read {} pages, not substantially fewer than the sort's {}", ordered_pages, sorted_pages);
+
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use anyhow::Result;
+use regex::Regex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::Value;
+use crate::expr::to_numeric;
+
+const MS_PER_DAY: i64 = 86_400_000;
+/// The Julian day of 1970-01-01 00:00:00, in milliseconds.
+const UNIX_EPOCH_JD_MS: i64 = 210_866_760_000_000;
+/// Julian days SQLite accepts: from 4714-11-24 BC up to the end of the year 9999.
+const MAX_JD_MS: i64 = 464_269_060_799_999;
+
+/// A point in time as SQLite represents it internally: milliseconds since the Julian day epoch
+/// (noon on 4714-11-24 BC), which makes the calendar arithmetic below plain integer math.
+#[derive(Debug, Clone, Copy)]
+struct JulianDay(i64);
+
+struct DateTime {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    /// Seconds including the fractional milliseconds.
+    second: f64,
+}
+
+impl JulianDay {
+    fn from_date_time(dt: &DateTime) -> Self {
+        let (mut year, mut month) = (dt.year, dt.month);
+        if month <= 2 {
+            year -= 1;
+            month += 12;
+        }
+        let a = year / 100;
+        let b = 2 - a + a / 4;
+        let x1 = 36525 * (year + 4716) / 100;
+        let x2 = 306001 * (month + 1) / 10000;
+        let days = ((x1 + x2 + dt.day + b) as f64 - 1524.5) * MS_PER_DAY as f64;
+        JulianDay(days as i64 + dt.hour * 3_600_000 + dt.minute * 60_000 + (dt.second * 1000.0).round() as i64)
+    }
+
+    fn to_date_time(self) -> DateTime {
+        let z = (self.0 + MS_PER_DAY / 2) / MS_PER_DAY;
+        let alpha = ((z as f64 - 1867216.25) / 36524.25) as i64;
+        let a = z + 1 + alpha - alpha / 4;
+        let b = a + 1524;
+        let c = ((b as f64 - 122.1) / 365.25) as i64;
+        let d = 36525 * (c & 32767) / 100;
+        let e = ((b - d) as f64 / 30.6001) as i64;
+        let day = b - d - (30.6001 * e as f64) as i64;
+        let month = if e < 14 { e - 1 } else { e - 13 };
+        let year = if month > 2 { c - 4716 } else { c - 4715 };
+
+        let ms_of_day = (self.0 + MS_PER_DAY / 2) % MS_PER_DAY;
+        DateTime {
+            year,
+            month,
+            day,
+            hour: ms_of_day / 3_600_000,
+            minute: ms_of_day / 60_000 % 60,
+            second: (ms_of_day % 60_000) as f64 / 1000.0,
+        }
+    }
+
+    fn now() -> Self {
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        JulianDay(UNIX_EPOCH_JD_MS + since_epoch.as_millis() as i64)
+    }
+}
+
+/// Reads a time value: `now`, an ISO-8601 `YYYY-MM-DD[ HH:MM[:SS[.SSS]]]` string (or just the
+/// time of day, on 2000-01-01), or a number taken as a Julian day number. A number followed by
+/// the `unixepoch` modifier is seconds since 1970 instead. Anything else, including any other
+/// modifier, gives `None`, which the functions turn into NULL.
+fn parse_time_value(value: &Value, modifiers: &[Value]) -> Option<JulianDay> {
+    let unixepoch = match modifiers {
+        [] => false,
+        [modifier] if modifier.to_string().trim().eq_ignore_ascii_case("unixepoch") => true,
+        _ => return None,
+    };
+
+    let number = match value {
+        Value::Integer(_) | Value::Real(_) => Some(to_numeric(value)),
+        Value::Text(text) if text.trim().parse::<f64>().is_ok() => Some(to_numeric(value)),
+        _ => None,
+    };
+    let jd = match number {
+        Some(Value::Integer(seconds)) if unixepoch => JulianDay(UNIX_EPOCH_JD_MS.checked_add(seconds.checked_mul(1000)?)?),
+        Some(Value::Real(seconds)) if unixepoch => JulianDay(UNIX_EPOCH_JD_MS + (seconds * 1000.0).round() as i64),
+        Some(Value::Integer(days)) => JulianDay(days.checked_mul(MS_PER_DAY)?),
+        Some(Value::Real(days)) => JulianDay((days * MS_PER_DAY as f64 + 0.5) as i64),
+        _ if unixepoch => return None,
+        _ => {
+            let text = value.to_string();
+            if text.trim().eq_ignore_ascii_case("now") {
+                JulianDay::now()
+            } else {
+                parse_time_string(&text)?
+            }
+        }
+    };
+
+    (0..=MAX_JD_MS).contains(&jd.0).then_some(jd)
+}
+
+fn parse_time_string(text: &str) -> Option<JulianDay> {
+    let time_string_re = Regex::new(
+        r"(?i)^(\d{4})-(\d{2})-(\d{2})(?:[ T](\d{2}):(\d{2})(?::(\d{2}(?:\.\d+)?))?\s*Z?)?\s*$"
+    ).ok()?;
+    // A time of day on its own is on 2000-01-01.
+    let text = match text.as_bytes() {
+        [b'0'..=b'9', b'0'..=b'9', b':', ..] => format!("2000-01-01 {}", text),
+        _ => text.to_string(),
+    };
+    let caps = time_string_re.captures(&text)?;
+    let field = |i: usize| caps.get(i).map_or(Some(0), |m| m.as_str().parse().ok());
+
+    let dt = DateTime {
+        year: field(1)?,
+        month: field(2)?,
+        day: field(3)?,
+        hour: field(4)?,
+        minute: field(5)?,
+        second: caps.get(6).map_or(Some(0.0), |m| m.as_str().parse().ok())?,
+    };
+    if !(1..=12).contains(&dt.month) || !(1..=31).contains(&dt.day) || dt.hour > 23 || dt.minute > 59 || dt.second >= 60.0 {
+        return None;
+    }
+    Some(JulianDay::from_date_time(&dt))
+}
+
+/// `strftime(format, time-value, modifier...)`. Supports `%d %f %H %j %m %M %s %S %Y %%`; any
+/// other conversion makes the result NULL, as does a NULL or unreadable time value.
+pub fn strftime(args: &[Value]) -> Result<Value> {
+    if args.iter().take(2).any(|arg| *arg == Value::Null) {
+        return Ok(Value::Null);
+    }
+    let format = args[0].to_string();
+    let jd = match args.get(1) {
+        Some(value) => parse_time_value(value, &args[2..]),
+        None => Some(JulianDay::now()),
+    };
+    let Some(jd) = jd else {
+        return Ok(Value::Null);
+    };
+    let dt = jd.to_date_time();
+
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('d') => out.push_str(&format!("{:02}", dt.day)),
+            Some('f') => out.push_str(&format!("{:06.3}", (dt.second * 1000.0).floor() / 1000.0)),
+            Some('H') => out.push_str(&format!("{:02}", dt.hour)),
+            Some('j') => {
+                let start_of_year = JulianDay::from_date_time(&DateTime { year: dt.year, month: 1, day: 1, hour: 0, minute: 0, second: 0.0 });
+                out.push_str(&format!("{:03}", (jd.0 - start_of_year.0) / MS_PER_DAY + 1));
+            }
+            Some('m') => out.push_str(&format!("{:02}", dt.month)),
+            Some('M') => out.push_str(&format!("{:02}", dt.minute)),
+            Some('s') => out.push_str(&((jd.0 - UNIX_EPOCH_JD_MS).div_euclid(1000)).to_string()),
+            Some('S') => out.push_str(&format!("{:02}", dt.second as i64)),
+            Some('Y') => out.push_str(&format!("{:04}", dt.year)),
+            Some('%') => out.push('%'),
+            _ => return Ok(Value::Null),
+        }
+    }
+
+    Ok(Value::Text(out))
+}
+
+/// `date(time-value, modifier...)`, the same as `strftime('%Y-%m-%d', ...)`.
+pub fn date(args: &[Value]) -> Result<Value> {
+    let mut strftime_args = vec![Value::Text("%Y-%m-%d".to_string())];
+    strftime_args.extend_from_slice(args);
+    strftime(&strftime_args)
+}
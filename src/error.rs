@@ -0,0 +1,67 @@
+use std::io::{self, ErrorKind};
+use thiserror::Error;
+
+/// Errors returned from the public `Database` API.
+#[derive(Debug, Error)]
+pub enum SqliteError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("file is not a database")]
+    NotADatabase,
+    /// Another process holds a PENDING or EXCLUSIVE lock on the database file.
+    #[error("database is locked")]
+    Locked,
+    /// `page` is the page being read when the problem was found, if known.
+    #[error("database disk image is malformed: {detail}")]
+    Corrupt { page: Option<u32>, detail: String },
+    #[error("{0}")]
+    UnsupportedSql(String),
+    #[error("no such table: {0}")]
+    NoSuchTable(String),
+    /// `table` is the table or view the column was looked for in; a subquery has no name.
+    #[error("no such column: {column}{}", table.as_ref().map_or(String::new(), |table| format!(" in table {}", table)))]
+    NoSuchColumn { column: String, table: Option<String> },
+    #[error("{0}")]
+    TypeError(String),
+    #[error("query exceeded the maximum of {limit} scanned rows")]
+    ScanLimitExceeded { limit: u64 },
+    #[error("{0}")]
+    Misuse(String),
+}
+
+impl SqliteError {
+    pub(crate) fn corrupt(page: impl Into<Option<u32>>, detail: impl Into<String>) -> Self {
+        SqliteError::Corrupt { page: page.into(), detail: detail.into() }
+    }
+
+    /// Names `table` in a `NoSuchColumn` error that doesn't name a table yet; other errors
+    /// pass through.
+    pub(crate) fn in_table(error: anyhow::Error, table: &str) -> anyhow::Error {
+        match error.downcast::<SqliteError>() {
+            Ok(SqliteError::NoSuchColumn { column, table: None }) => {
+                SqliteError::NoSuchColumn { column, table: Some(table.to_string()) }.into()
+            }
+            Ok(error) => error.into(),
+            Err(error) => error,
+        }
+    }
+}
+
+/// Internally errors travel as `anyhow::Error` so they can pick up context on the way out.
+/// Typed errors are recovered as-is; a read that ran past the end of the file means a page
+/// or record points outside it, so it is reported as corruption rather than plain I/O.
+impl From<anyhow::Error> for SqliteError {
+    fn from(error: anyhow::Error) -> Self {
+        let detail = format!("{:#}", error);
+        let error = match error.downcast::<SqliteError>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+
+        match error.downcast::<io::Error>() {
+            Ok(error) if error.kind() == ErrorKind::UnexpectedEof => SqliteError::corrupt(None, detail),
+            Ok(error) => SqliteError::Io(error),
+            Err(_) => SqliteError::corrupt(None, detail),
+        }
+    }
+}
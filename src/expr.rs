@@ -0,0 +1,1125 @@
+use anyhow::{Result, bail};
+use std::cmp::Ordering;
+use crate::{Aggregate, AggregateFunc, Collation, Column, RowSet, SqliteError, SqlType, Value, closing_paren, compare_values, find_column, find_qualified_column, ident_eq};
+use crate::functions::ScalarFunction;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Integer(i64),
+    Real(f64),
+    String(String),
+    /// An `X'...'` blob literal.
+    Blob(Vec<u8>),
+    /// A reserved word, spelled in upper case however it was written.
+    Keyword(&'static str),
+    Identifier(String),
+    /// A quoted name; `double_quoted` names fall back to string literals when no column matches.
+    QuotedIdentifier { name: String, double_quoted: bool },
+    Symbol(&'static str),
+    /// The SQL of a parenthesized `(SELECT ...)`, without the parentheses.
+    Subquery(String),
+}
+
+/// The token as it would be written in SQL, for error messages.
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Integer(value) => write!(f, "{}", value),
+            Token::Real(value) => write!(f, "{}", value),
+            Token::String(text) => write!(f, "'{}'", text.replace('\'', "''")),
+            Token::Blob(bytes) => write!(f, "{:?}", Value::Blob(bytes.clone())),
+            Token::Keyword(keyword) => write!(f, "{}", keyword),
+            Token::Identifier(name) => write!(f, "{}", name),
+            Token::QuotedIdentifier { name, .. } => write!(f, "{}", name),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+            Token::Subquery(_) => write!(f, "("),
+        }
+    }
+}
+
+/// Words that cannot name a column unquoted. Others that the parser gives a meaning in context,
+/// such as `LIKE`, `CAST`, `END` or `TRUE`, stay identifiers, since SQLite lets them name columns.
+const KEYWORDS: [&str; 18] = [
+    "ALL", "AND", "AS", "BETWEEN", "CASE", "COLLATE", "DISTINCT", "ELSE", "ESCAPE",
+    "IN", "IS", "ISNULL", "NOT", "NOTNULL", "NULL", "OR", "THEN", "WHEN",
+];
+
+/// Longest spellings first so that e.g. `<=` is not read as `<` followed by `=`.
+const SYMBOLS: [&str; 18] = ["||", "==", "!=", "<>", "<=", ">=", "(", ")", ",", "+", "-", "*", "/", "%", "=", "<", ">", "."];
+
+/// SQLite accepts `==` for `=` and `<>` for `!=`; the tokenizer only ever emits the latter.
+fn canonical_symbol(symbol: &'static str) -> &'static str {
+    match symbol {
+        "==" => "=",
+        "<>" => "!=",
+        symbol => symbol,
+    }
+}
+
+/// Whether the text after an opening parenthesis starts a subquery.
+fn starts_subquery(after_paren: &[char]) -> bool {
+    let word: String = after_paren.iter()
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| **c == '_' || c.is_alphanumeric())
+        .collect();
+    word.eq_ignore_ascii_case("SELECT")
+}
+
+pub fn tokenize(sql: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' && starts_subquery(&chars[i + 1..]) {
+            let rest: String = chars[i..].iter().collect();
+            let Some(close) = closing_paren(&rest, 0) else {
+                bail!(SqliteError::UnsupportedSql("incomplete input".to_string()));
+            };
+            tokens.push(Token::Subquery(rest[1..close].to_string()));
+            i += rest[..=close].chars().count();
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.'
+                || ((chars[i] == '+' || chars[i] == '-') && matches!(chars[i - 1], 'e' | 'E'))) {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            tokens.push(match text.parse::<i64>() {
+                Ok(integer) => Token::Integer(integer),
+                // 2^63 is only an integer once negated; no other literal reads as i64::MIN.
+                Err(_) if text == "9223372036854775808" => Token::Integer(i64::MIN),
+                Err(_) => match text.parse::<f64>() {
+                    Ok(real) => Token::Real(real),
+                    Err(_) => bail!(SqliteError::UnsupportedSql(format!("unrecognized token: \"{}\"", text))),
+                },
+            });
+        } else if matches!(c, 'x' | 'X') && chars.get(i + 1) == Some(&'\'') {
+            let start = i;
+            i += 2;
+            while i < chars.len() && chars[i] != '\'' {
+                i += 1;
+            }
+            let digits: String = chars[start + 2..i].iter().collect();
+            i += 1;
+            if i > chars.len() || !digits.len().is_multiple_of(2) || !digits.chars().all(|d| d.is_ascii_hexdigit()) {
+                bail!(SqliteError::UnsupportedSql(format!("unrecognized token: \"{}\"", chars[start..i.min(chars.len())].iter().collect::<String>())));
+            }
+            let bytes = (0..digits.len()).step_by(2)
+                .map(|j| u8::from_str_radix(&digits[j..j + 2], 16))
+                .collect::<Result<_, _>>()?;
+            tokens.push(Token::Blob(bytes));
+        } else if c == '_' || c.is_alphabetic() {
+            let start = i;
+            while i < chars.len() && (chars[i] == '_' || chars[i].is_alphanumeric()) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            tokens.push(match KEYWORDS.iter().find(|keyword| keyword.eq_ignore_ascii_case(&word)) {
+                Some(keyword) => Token::Keyword(keyword),
+                None => Token::Identifier(word),
+            });
+        } else if matches!(c, '\'' | '"' | '`' | '[') {
+            let close = if c == '[' { ']' } else { c };
+            let start = i;
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => bail!(SqliteError::UnsupportedSql(format!("unrecognized token: \"{}\"", chars[start..].iter().collect::<String>()))),
+                    Some(&ch) if ch == close && close != ']' && chars.get(i + 1) == Some(&close) => {
+                        text.push(close);
+                        i += 2;
+                    }
+                    Some(&ch) if ch == close => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&ch) => {
+                        text.push(ch);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(if c == '\'' {
+                Token::String(text)
+            } else {
+                Token::QuotedIdentifier { name: text, double_quoted: c == '"' }
+            });
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            match SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+                Some(symbol) => {
+                    tokens.push(Token::Symbol(canonical_symbol(symbol)));
+                    i += symbol.len();
+                }
+                None => bail!(SqliteError::UnsupportedSql(format!("unrecognized token: \"{}\"", c))),
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Negate,
+    Plus,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Concat,
+    Multiply,
+    Divide,
+    Remainder,
+    Add,
+    Subtract,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    NotEq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// `IS` and `IS NOT`, which treat NULL as a value equal only to itself.
+    Is,
+    IsNot,
+}
+
+enum Infix {
+    Binary(BinaryOp),
+    Compare(CompareOp),
+    Like,
+    Glob,
+    Between,
+    In,
+    /// The postfix `ISNULL` and `NOTNULL`; `IsNull(false)` is NOTNULL.
+    IsNull(bool),
+    And,
+    Or,
+}
+
+impl Infix {
+    fn from_token(token: &Token) -> Option<Self> {
+        match token {
+            Token::Symbol("||") => Some(Infix::Binary(BinaryOp::Concat)),
+            Token::Symbol("*") => Some(Infix::Binary(BinaryOp::Multiply)),
+            Token::Symbol("/") => Some(Infix::Binary(BinaryOp::Divide)),
+            Token::Symbol("%") => Some(Infix::Binary(BinaryOp::Remainder)),
+            Token::Symbol("+") => Some(Infix::Binary(BinaryOp::Add)),
+            Token::Symbol("-") => Some(Infix::Binary(BinaryOp::Subtract)),
+            Token::Symbol("=") => Some(Infix::Compare(CompareOp::Eq)),
+            Token::Symbol("!=") => Some(Infix::Compare(CompareOp::NotEq)),
+            Token::Symbol("<") => Some(Infix::Compare(CompareOp::Lt)),
+            Token::Symbol("<=") => Some(Infix::Compare(CompareOp::Le)),
+            Token::Symbol(">") => Some(Infix::Compare(CompareOp::Gt)),
+            Token::Symbol(">=") => Some(Infix::Compare(CompareOp::Ge)),
+            Token::Identifier(word) if word.eq_ignore_ascii_case("LIKE") => Some(Infix::Like),
+            Token::Identifier(word) if word.eq_ignore_ascii_case("GLOB") => Some(Infix::Glob),
+            Token::Keyword("BETWEEN") => Some(Infix::Between),
+            Token::Keyword("IN") => Some(Infix::In),
+            Token::Keyword("IS") => Some(Infix::Compare(CompareOp::Is)),
+            Token::Keyword("ISNULL") => Some(Infix::IsNull(true)),
+            Token::Keyword("NOTNULL") => Some(Infix::IsNull(false)),
+            Token::Keyword("AND") => Some(Infix::And),
+            Token::Keyword("OR") => Some(Infix::Or),
+            _ => None,
+        }
+    }
+
+    /// Binding power, following SQLite's operator precedence (higher binds tighter).
+    fn precedence(&self) -> u8 {
+        match self {
+            Infix::Binary(BinaryOp::Concat) => 8,
+            Infix::Binary(BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Remainder) => 7,
+            Infix::Binary(BinaryOp::Add | BinaryOp::Subtract) => 6,
+            Infix::Compare(CompareOp::Lt | CompareOp::Le | CompareOp::Gt | CompareOp::Ge) => 5,
+            Infix::Compare(CompareOp::Eq | CompareOp::NotEq | CompareOp::Is | CompareOp::IsNot)
+            | Infix::Like | Infix::Glob | Infix::Between | Infix::In | Infix::IsNull(_) => 4,
+            Infix::And => 2,
+            Infix::Or => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Column(usize),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Compare(CompareOp, Box<Expr>, Box<Expr>, Collation),
+    /// `CAST(expr AS type)`; the type name only matters for the affinity it implies.
+    Cast(Box<Expr>, SqlType),
+    /// `x IN (SELECT ...)` or `x IN (a, b, ...)` of constants, with the values sorted so
+    /// membership is a binary search.
+    In(Box<Expr>, Vec<Value>, Collation),
+    /// A scalar function call; `x LIKE y ESCAPE z` and `x GLOB y` are the calls `like(y, x, z)`
+    /// and `glob(y, x)`.
+    Function(ScalarFunction, Vec<Expr>),
+    /// Also used for `x BETWEEN a AND b`, which becomes `x >= a AND x <= b`.
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    /// `expr COLLATE name`: the value is unchanged, but comparisons and sorting use the collation.
+    Collate(Box<Expr>, Collation),
+    /// `CASE WHEN cond THEN result ... [ELSE result] END`; the simple form `CASE x WHEN v ...`
+    /// becomes the conditions `x = v`.
+    Case(Vec<(Expr, Expr)>, Option<Box<Expr>>),
+}
+
+/// Precedence of a prefix NOT: looser than comparisons, tighter than AND.
+const NOT_PRECEDENCE: u8 = 3;
+
+/// Runs the SQL of a subquery and returns its result.
+type RunSubquery<'r> = dyn FnMut(&str) -> Result<RowSet> + 'r;
+
+struct Parser<'a, 'r> {
+    tokens: &'a [Token],
+    pos: usize,
+    columns: &'a [Column],
+    /// Without it subqueries are rejected.
+    run_subquery: Option<&'r mut RunSubquery<'r>>,
+    /// Aggregate calls are collected here and read back as the columns after the rowid
+    /// pseudo-column, one per call. Without it aggregates are a misuse.
+    aggregates: Option<&'a mut Vec<Aggregate>>,
+    /// Result columns, whose names can be used where no table column has the name.
+    aliases: &'a [(String, Expr)],
+}
+
+impl Parser<'_, '_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    /// Whether the token at `offset` is `keyword`, given in upper case: a reserved word, or one
+    /// only meaningful in context such as `END`.
+    fn is_keyword_at(&self, offset: usize, keyword: &str) -> bool {
+        match self.tokens.get(self.pos + offset) {
+            Some(Token::Keyword(word)) => *word == keyword,
+            Some(Token::Identifier(word)) => word.eq_ignore_ascii_case(keyword),
+            _ => false,
+        }
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<()> {
+        match self.next() {
+            Some(Token::Symbol(s)) if *s == symbol => Ok(()),
+            Some(token) => bail!(SqliteError::UnsupportedSql(format!("near \"{}\": syntax error", token))),
+            None => bail!(SqliteError::UnsupportedSql("incomplete input".to_string())),
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if self.is_keyword_at(0, keyword) {
+            self.pos += 1;
+            return Ok(());
+        }
+        match self.peek() {
+            Some(token) => bail!(SqliteError::UnsupportedSql(format!("near \"{}\": syntax error", token))),
+            None => bail!(SqliteError::UnsupportedSql("incomplete input".to_string())),
+        }
+    }
+
+    fn parse_expr(&mut self, min_precedence: u8) -> Result<Expr> {
+        let mut lhs = self.parse_unary()?;
+
+        loop {
+            // `x NOT LIKE y`, `x NOT GLOB y`, `x NOT BETWEEN a AND b` and `x NOT IN (...)` negate
+            // the operator after NOT; `x NOT NULL` is `x NOTNULL`.
+            let negated = self.is_keyword_at(0, "NOT")
+                && ["LIKE", "GLOB", "BETWEEN", "IN"].iter().any(|keyword| self.is_keyword_at(1, keyword));
+            let not_null = self.is_keyword_at(0, "NOT") && self.is_keyword_at(1, "NULL");
+            let infix = if not_null {
+                Infix::IsNull(false)
+            } else {
+                match self.tokens.get(self.pos + negated as usize).and_then(Infix::from_token) {
+                    Some(infix) => infix,
+                    None => break,
+                }
+            };
+            let precedence = infix.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+            self.pos += 1 + (negated || not_null) as usize;
+            lhs = match infix {
+                Infix::In => self.parse_in(lhs)?,
+                Infix::IsNull(is_null) => {
+                    let op = if is_null { CompareOp::Is } else { CompareOp::IsNot };
+                    Expr::Compare(op, Box::new(lhs), Box::new(Expr::Literal(Value::Null)), Collation::Binary)
+                }
+                // `x IS NOT y` negates IS.
+                Infix::Compare(CompareOp::Is) if self.is_keyword_at(0, "NOT") => {
+                    self.pos += 1;
+                    self.parse_infix(Infix::Compare(CompareOp::IsNot), lhs)?
+                }
+                infix => self.parse_infix(infix, lhs)?,
+            };
+            if negated {
+                lhs = Expr::Unary(UnaryOp::Not, Box::new(lhs));
+            }
+        }
+
+        Ok(lhs)
+    }
+
+    /// Parses the right-hand side of a binary operator and combines it with `lhs`.
+    fn parse_infix(&mut self, infix: Infix, lhs: Expr) -> Result<Expr> {
+        let precedence = infix.precedence();
+        let rhs = self.parse_expr(precedence + 1)?;
+        Ok(match infix {
+            Infix::Binary(op) => Expr::Binary(op, Box::new(lhs), Box::new(rhs)),
+            Infix::Compare(op) => self.comparison(op, lhs, rhs),
+            Infix::Like => {
+                let mut args = vec![rhs, lhs];
+                if self.is_keyword_at(0, "ESCAPE") {
+                    self.pos += 1;
+                    args.push(self.parse_expr(precedence + 1)?);
+                }
+                Expr::Function(ScalarFunction::Like, args)
+            }
+            Infix::Glob => Expr::Function(ScalarFunction::Glob, vec![rhs, lhs]),
+            Infix::Between => {
+                match self.next() {
+                    Some(Token::Keyword("AND")) => {}
+                    _ => bail!(SqliteError::UnsupportedSql("expected AND in BETWEEN expression".to_string())),
+                }
+                let high = self.parse_expr(precedence + 1)?;
+                Expr::And(
+                    Box::new(self.comparison(CompareOp::Ge, lhs.clone(), rhs)),
+                    Box::new(self.comparison(CompareOp::Le, lhs, high)),
+                )
+            }
+            Infix::In => unreachable!("IN takes a subquery, not an expression"),
+            Infix::IsNull(_) => unreachable!("ISNULL and NOTNULL take no operand"),
+            Infix::And => Expr::And(Box::new(lhs), Box::new(rhs)),
+            Infix::Or => Expr::Or(Box::new(lhs), Box::new(rhs)),
+        })
+    }
+
+    /// Builds a comparison the way SQLite prepares one: an explicit COLLATE, then a column
+    /// operand's collation is used (the left one taking precedence in each case), and a literal
+    /// compared against a column is converted with that column's affinity first.
+    fn comparison(&self, op: CompareOp, lhs: Expr, rhs: Expr) -> Expr {
+        let collation = explicit_collation(&lhs)
+            .or_else(|| explicit_collation(&rhs))
+            .or_else(|| column_collation(&lhs, self.columns))
+            .or_else(|| column_collation(&rhs, self.columns))
+            .unwrap_or(Collation::Binary);
+        let lhs_with_affinity = self.with_column_affinity(lhs.clone(), &rhs);
+        let rhs = self.with_column_affinity(rhs, &lhs);
+
+        Expr::Compare(op, Box::new(lhs_with_affinity), Box::new(rhs), collation)
+    }
+
+    fn with_column_affinity(&self, operand: Expr, other: &Expr) -> Expr {
+        let (Expr::Literal(value), Expr::Column(idx)) = (&operand, other) else {
+            return operand;
+        };
+
+        // The index just past the declared columns refers to the rowid, which has INTEGER
+        // affinity; the ones after it are aggregate results, which have none.
+        if *idx > self.columns.len() {
+            return operand;
+        }
+        let affinity = self.columns.get(*idx).map_or(SqlType::Integer, |col| col.tpe);
+        match (affinity, value) {
+            (SqlType::Integer | SqlType::Real | SqlType::Numeric, Value::Text(text)) => {
+                match to_numeric(value) {
+                    number if text.trim().parse::<f64>().is_ok() => Expr::Literal(number),
+                    _ => operand,
+                }
+            }
+            (SqlType::Text, Value::Integer(_) | Value::Real(_)) => Expr::Literal(Value::Text(value.to_string())),
+            _ => operand,
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        match self.peek() {
+            Some(Token::Symbol("-")) => {
+                self.pos += 1;
+                if self.peek() == Some(&Token::Integer(i64::MIN)) {
+                    self.pos += 1;
+                    return Ok(Expr::Literal(Value::Integer(i64::MIN)));
+                }
+                Ok(Expr::Unary(UnaryOp::Negate, Box::new(self.parse_unary()?)))
+            }
+            Some(Token::Symbol("+")) => {
+                self.pos += 1;
+                Ok(Expr::Unary(UnaryOp::Plus, Box::new(self.parse_unary()?)))
+            }
+            _ if self.is_keyword_at(0, "NOT") => {
+                self.pos += 1;
+                Ok(Expr::Unary(UnaryOp::Not, Box::new(self.parse_expr(NOT_PRECEDENCE)?)))
+            }
+            _ => self.parse_postfix(),
+        }
+    }
+
+    /// Parses a primary expression followed by any number of `COLLATE name`, which binds
+    /// tighter than every other operator.
+    fn parse_postfix(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_primary()?;
+        while self.is_keyword_at(0, "COLLATE") {
+            self.pos += 1;
+            let name = match self.next() {
+                Some(Token::Identifier(name) | Token::QuotedIdentifier { name, .. }) => name.clone(),
+                Some(token) => bail!(SqliteError::UnsupportedSql(format!("near \"{}\": syntax error", token))),
+                None => bail!(SqliteError::UnsupportedSql("incomplete input".to_string())),
+            };
+            let Some(collation) = Collation::from_str(&name) else {
+                bail!(SqliteError::UnsupportedSql(format!("no such collation sequence: {}", name)));
+            };
+            expr = Expr::Collate(Box::new(expr), collation);
+        }
+        Ok(expr)
+    }
+
+    /// Parses a call to a scalar or aggregate function. `min()` and `max()` are aggregates with
+    /// a single argument and scalar functions otherwise. DISTINCT only matters to aggregates.
+    fn parse_function_call(&mut self, name: &str) -> Result<Expr> {
+        let aggregate = AggregateFunc::from_name(name);
+        let function = ScalarFunction::from_name(name);
+        if aggregate.is_none() && function.is_none() {
+            bail!(SqliteError::UnsupportedSql(format!("no such function: {}", name)));
+        }
+        self.expect("(")?;
+
+        let distinct = self.is_keyword_at(0, "DISTINCT");
+        if distinct || self.is_keyword_at(0, "ALL") {
+            self.pos += 1;
+        }
+        let star = self.peek() == Some(&Token::Symbol("*")) && self.tokens.get(self.pos + 1) == Some(&Token::Symbol(")"));
+        if star {
+            self.pos += 2;
+        }
+
+        // Aggregates cannot be nested, so none are collected within the arguments.
+        let aggregates = self.aggregates.take();
+        let args = if star { Ok(vec![]) } else { self.parse_args() };
+        self.aggregates = aggregates;
+        let args = args?;
+
+        match (aggregate, function) {
+            (Some(func), None) => self.aggregate_call(func, args, star, distinct),
+            (Some(func), Some(_)) if args.len() == 1 => self.aggregate_call(func, args, star, distinct),
+            (_, Some(function)) => {
+                if star {
+                    bail!(SqliteError::UnsupportedSql(format!("wrong number of arguments to function {}()", name.to_lowercase())));
+                }
+                function.check_arity(args.len())?;
+                Ok(Expr::Function(function, args))
+            }
+            (None, None) => unreachable!("checked above"),
+        }
+    }
+
+    /// Parses a comma-separated argument list up to and including the closing parenthesis.
+    fn parse_args(&mut self) -> Result<Vec<Expr>> {
+        let mut args = vec![];
+        if self.peek() != Some(&Token::Symbol(")")) {
+            loop {
+                args.push(self.parse_expr(0)?);
+                if self.peek() != Some(&Token::Symbol(",")) {
+                    break;
+                }
+                self.pos += 1;
+            }
+        }
+        self.expect(")")?;
+        Ok(args)
+    }
+
+    /// Records an aggregate call and returns the column its value is read from. `args` is
+    /// empty for `count(*)` and `count()`, which count rows.
+    fn aggregate_call(&mut self, func: AggregateFunc, mut args: Vec<Expr>, star: bool, distinct: bool) -> Result<Expr> {
+        let arg = match args.len() {
+            0 if star && distinct => bail!(SqliteError::UnsupportedSql("near \"*\": syntax error".to_string())),
+            0 if func == AggregateFunc::Count => None,
+            1 => args.pop(),
+            _ => bail!(SqliteError::UnsupportedSql(format!("wrong number of arguments to function {}()", func.name()))),
+        };
+        let Some(aggregates) = self.aggregates.as_mut() else {
+            bail!(SqliteError::UnsupportedSql(format!("misuse of aggregate function {}()", func.name())));
+        };
+
+        aggregates.push(Aggregate { func, arg, distinct });
+        Ok(Expr::Column(self.columns.len() + aggregates.len()))
+    }
+
+    /// Parses the rest of `CAST(expr AS type-name)` after the CAST keyword. The type name may
+    /// be several words and carry a size, as in `VARCHAR(10)`.
+    fn parse_cast(&mut self) -> Result<Expr> {
+        self.expect("(")?;
+        let operand = self.parse_expr(0)?;
+        if !self.is_keyword_at(0, "AS") {
+            bail!(SqliteError::UnsupportedSql("expected AS in CAST expression".to_string()));
+        }
+        self.pos += 1;
+
+        let mut type_words = vec![];
+        while let Some(Token::Identifier(word)) = self.peek() {
+            type_words.push(word.clone());
+            self.pos += 1;
+        }
+        if type_words.is_empty() {
+            bail!(SqliteError::UnsupportedSql("expected a type name in CAST expression".to_string()));
+        }
+        if self.peek() == Some(&Token::Symbol("(")) {
+            while !matches!(self.next(), Some(Token::Symbol(")")) | None) {}
+        }
+        self.expect(")")?;
+
+        Ok(Expr::Cast(Box::new(operand), SqlType::from_str(&type_words.join(" "))))
+    }
+
+    /// Parses the rest of a CASE expression after the CASE keyword.
+    fn parse_case(&mut self) -> Result<Expr> {
+        let operand = if self.is_keyword_at(0, "WHEN") { None } else { Some(self.parse_expr(0)?) };
+
+        let mut whens = vec![];
+        while self.is_keyword_at(0, "WHEN") {
+            self.pos += 1;
+            let value = self.parse_expr(0)?;
+            self.expect_keyword("THEN")?;
+            let result = self.parse_expr(0)?;
+            let condition = match &operand {
+                Some(operand) => self.comparison(CompareOp::Eq, operand.clone(), value),
+                None => value,
+            };
+            whens.push((condition, result));
+        }
+        if whens.is_empty() {
+            self.expect_keyword("WHEN")?;
+        }
+
+        let otherwise = if self.is_keyword_at(0, "ELSE") {
+            self.pos += 1;
+            Some(Box::new(self.parse_expr(0)?))
+        } else {
+            None
+        };
+        self.expect_keyword("END")?;
+
+        Ok(Expr::Case(whens, otherwise))
+    }
+
+    /// Parses the subquery or list after `operand IN`. A subquery runs once, up front. Its
+    /// values, like those of a list of constants, get the operand's affinity the way the
+    /// right-hand side of `=` does. As in SQLite, `operand IN (a)` is `operand = a`, and a list
+    /// that uses columns becomes `operand = a OR operand = b ...`.
+    fn parse_in(&mut self, operand: Expr) -> Result<Expr> {
+        let elements: Vec<Expr> = match self.next().cloned() {
+            Some(Token::Subquery(sql)) => self.run_subquery(&sql)?.rows.into_iter()
+                .map(|mut row| Expr::Literal(row.swap_remove(0)))
+                .collect(),
+            Some(Token::Symbol("(")) => {
+                let mut list = self.parse_args()?;
+                if list.len() == 1 {
+                    return Ok(self.comparison(CompareOp::Eq, operand, list.remove(0)));
+                }
+                list
+            }
+            _ => bail!(SqliteError::UnsupportedSql("IN must be followed by a subquery or a parenthesized list".to_string())),
+        };
+
+        let is_constant = |element: &Expr| {
+            let mut columns = vec![];
+            referenced_columns(element, &mut columns);
+            columns.is_empty()
+        };
+        if !elements.iter().all(is_constant) {
+            return Ok(elements.into_iter()
+                .map(|element| self.comparison(CompareOp::Eq, operand.clone(), element))
+                .reduce(|lhs, rhs| Expr::Or(Box::new(lhs), Box::new(rhs)))
+                .expect("a list that uses a column is not empty"));
+        }
+
+        let collation = column_collation(&operand, self.columns).unwrap_or(Collation::Binary);
+        let mut values = vec![];
+        for element in elements {
+            let value = evaluate(&element, &[])?;
+            match self.with_column_affinity(Expr::Literal(value), &operand) {
+                Expr::Literal(value) => values.push(value),
+                _ => unreachable!("affinity keeps literals literal"),
+            }
+        }
+        values.sort_by(|a, b| compare_values(a, b, collation));
+
+        Ok(Expr::In(Box::new(operand), values, collation))
+    }
+
+    /// Runs a subquery that must return a single column.
+    fn run_subquery(&mut self, sql: &str) -> Result<RowSet> {
+        let Some(run_subquery) = self.run_subquery.as_mut() else {
+            bail!(SqliteError::UnsupportedSql("subqueries are only supported in WHERE".to_string()));
+        };
+        let result = run_subquery(sql)?;
+        if result.column_names.len() != 1 {
+            bail!(SqliteError::UnsupportedSql(format!("sub-select returns {} columns - expected 1", result.column_names.len())));
+        }
+        Ok(result)
+    }
+
+    /// Runs a scalar subquery once, up front: its single value stands in for it as a literal,
+    /// NULL if it returns no rows.
+    fn scalar_subquery(&mut self, sql: &str) -> Result<Value> {
+        let result = self.run_subquery(sql)?;
+        match result.rows.as_slice() {
+            [] => Ok(Value::Null),
+            [row] => Ok(row[0].clone()),
+            _ => bail!(SqliteError::TypeError("scalar subquery returned more than one row".to_string())),
+        }
+    }
+
+    fn column(&self, name: &str) -> Result<Expr> {
+        match find_column(self.columns, name) {
+            Ok(idx) => Ok(Expr::Column(idx)),
+            Err(e) => self.aliases.iter()
+                .find(|(alias, _)| ident_eq(alias, name))
+                .map(|(_, expr)| expr.clone())
+                .ok_or(e),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        match self.next().cloned() {
+            Some(Token::Integer(i64::MIN)) => Ok(Expr::Literal(Value::Real(9223372036854775808.0))),
+            Some(Token::Integer(value)) => Ok(Expr::Literal(Value::Integer(value))),
+            Some(Token::Real(value)) => Ok(Expr::Literal(Value::Real(value))),
+            Some(Token::String(value)) => Ok(Expr::Literal(Value::Text(value))),
+            Some(Token::Blob(value)) => Ok(Expr::Literal(Value::Blob(value))),
+            Some(Token::Keyword("NULL")) => Ok(Expr::Literal(Value::Null)),
+            Some(Token::Identifier(name)) if name.eq_ignore_ascii_case("CAST") && self.peek() == Some(&Token::Symbol("(")) => self.parse_cast(),
+            Some(Token::Keyword("CASE")) => self.parse_case(),
+            Some(Token::Identifier(table) | Token::QuotedIdentifier { name: table, .. }) if self.peek() == Some(&Token::Symbol(".")) => {
+                self.next();
+                match self.next().cloned() {
+                    Some(Token::Identifier(name) | Token::QuotedIdentifier { name, .. }) => Ok(Expr::Column(find_qualified_column(self.columns, &table, &name)?)),
+                    Some(token) => bail!(SqliteError::UnsupportedSql(format!("near \"{}\": syntax error", token))),
+                    None => bail!(SqliteError::UnsupportedSql("incomplete input".to_string())),
+                }
+            }
+            Some(Token::Identifier(name)) if self.peek() == Some(&Token::Symbol("(")) => self.parse_function_call(&name),
+            // As in SQLite, TRUE and FALSE are the integers 1 and 0 unless a column has the name.
+            Some(Token::Identifier(name)) => match self.column(&name) {
+                Err(_) if name.eq_ignore_ascii_case("TRUE") => Ok(Expr::Literal(Value::Integer(1))),
+                Err(_) if name.eq_ignore_ascii_case("FALSE") => Ok(Expr::Literal(Value::Integer(0))),
+                result => result,
+            },
+            Some(Token::QuotedIdentifier { name, double_quoted }) => match self.column(&name) {
+                Err(_) if double_quoted => Ok(Expr::Literal(Value::Text(name))),
+                result => result,
+            },
+            Some(Token::Subquery(sql)) => Ok(Expr::Literal(self.scalar_subquery(&sql)?)),
+            Some(Token::Symbol("(")) => {
+                let expr = self.parse_expr(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(token) => bail!(SqliteError::UnsupportedSql(format!("near \"{}\": syntax error", token))),
+            None => bail!(SqliteError::UnsupportedSql("incomplete input".to_string())),
+        }
+    }
+}
+
+/// Parses a scalar expression; column names are resolved to indices into `columns`.
+pub fn parse_expr(sql: &str, columns: &[Column]) -> Result<Expr> {
+    parse(sql, columns, None, None, &[])
+}
+
+/// Like `parse_expr`, but aggregate calls are allowed and appended to `aggregates`, and names
+/// that are not columns may refer to the result columns in `aliases`. The value of the i-th
+/// aggregate is read from index `columns.len() + 1 + i` of the evaluated row.
+pub fn parse_expr_with_aggregates(sql: &str, columns: &[Column], aliases: &[(String, Expr)], aggregates: &mut Vec<Aggregate>) -> Result<Expr> {
+    parse(sql, columns, None, Some(aggregates), aliases)
+}
+
+/// Like `parse_expr`, but subqueries are allowed and run through `run_subquery` while parsing.
+pub fn parse_expr_with_subqueries<'r>(sql: &str, columns: &[Column], run_subquery: &'r mut RunSubquery<'r>) -> Result<Expr> {
+    parse(sql, columns, Some(run_subquery), None, &[])
+}
+
+fn parse<'r>(sql: &str, columns: &[Column], run_subquery: Option<&'r mut RunSubquery<'r>>, aggregates: Option<&mut Vec<Aggregate>>, aliases: &[(String, Expr)]) -> Result<Expr> {
+    let tokens = tokenize(sql)?;
+    let mut parser = Parser { tokens: &tokens, pos: 0, columns, run_subquery, aggregates, aliases };
+    let expr = parser.parse_expr(0)?;
+
+    if let Some(token) = parser.peek() {
+        bail!(SqliteError::UnsupportedSql(format!("near \"{}\": syntax error", token)));
+    }
+
+    Ok(expr)
+}
+
+/// Numeric value of an arithmetic operand: text and blobs count as the number they start with
+/// (see `numeric_prefix`), so `'3 apples' * 2` is 6, or 0 if there is none.
+pub fn to_numeric(value: &Value) -> Value {
+    match value {
+        Value::Null | Value::Integer(_) | Value::Real(_) => value.clone(),
+        _ => {
+            let text = value.to_string();
+            let (prefix, is_integer) = numeric_prefix(&text);
+            match prefix.parse::<i64>() {
+                Ok(integer) if is_integer => Value::Integer(integer),
+                _ => prefix.parse::<f64>().map_or(Value::Integer(0), Value::Real),
+            }
+        }
+    }
+}
+
+/// The collation given by a COLLATE within `expr`. As in SQLite it carries through unary and
+/// binary operators, the left operand's taking precedence.
+fn explicit_collation(expr: &Expr) -> Option<Collation> {
+    match expr {
+        Expr::Collate(_, collation) => Some(*collation),
+        Expr::Unary(_, operand) | Expr::Cast(operand, _) => explicit_collation(operand),
+        Expr::Binary(_, lhs, rhs) => explicit_collation(lhs).or_else(|| explicit_collation(rhs)),
+        _ => None,
+    }
+}
+
+/// The collation an operand brings to a comparison or sort: an explicit COLLATE, or that of a
+/// column, looking through CAST and unary `+`. Other expressions have none.
+pub fn column_collation(expr: &Expr, columns: &[Column]) -> Option<Collation> {
+    match expr {
+        Expr::Collate(_, collation) => Some(*collation),
+        Expr::Binary(..) => explicit_collation(expr),
+        Expr::Column(idx) => Some(columns.get(*idx).map_or(Collation::Binary, Column::collation)),
+        Expr::Cast(operand, _) | Expr::Unary(UnaryOp::Plus, operand) => column_collation(operand, columns),
+        _ => None,
+    }
+}
+
+/// Longest prefix of `text` (after leading spaces) that reads as a number, which is what CAST
+/// converts: `'12abc'` gives `12` and `'1.5e2x'` gives `1.5e2`. The flag tells whether the
+/// prefix is written as an integer.
+fn numeric_prefix(text: &str) -> (&str, bool) {
+    let text = text.trim_start();
+    let bytes = text.as_bytes();
+    let digits_from = |start: usize| start + bytes[start..].iter().take_while(|b| b.is_ascii_digit()).count();
+
+    let sign_end = if matches!(bytes.first(), Some(b'+' | b'-')) { 1 } else { 0 };
+    let mut end = digits_from(sign_end);
+    let mut is_integer = end > sign_end;
+    if bytes.get(end) == Some(&b'.') {
+        let fraction_end = digits_from(end + 1);
+        if is_integer || fraction_end > end + 1 {
+            end = fraction_end;
+            is_integer = false;
+        }
+    }
+    if end > sign_end && matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let exponent_start = end + 1 + matches!(bytes.get(end + 1), Some(b'+' | b'-')) as usize;
+        let exponent_end = digits_from(exponent_start);
+        if exponent_end > exponent_start {
+            end = exponent_end;
+            is_integer = false;
+        }
+    }
+
+    (&text[..end], is_integer)
+}
+
+/// Integer prefix of `text`, saturating at the bounds of i64.
+fn integer_prefix(text: &str) -> i64 {
+    let text = text.trim_start();
+    let digits_end = text.char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && (c == '+' || c == '-'))))
+        .map_or(text.len(), |(i, _)| i);
+    let digits = &text[..digits_end];
+    digits.parse().unwrap_or_else(|_| match digits.trim_start_matches(['+', '-']) {
+        "" => 0,
+        _ if digits.starts_with('-') => i64::MIN,
+        _ => i64::MAX,
+    })
+}
+
+/// Converts `value` the way `CAST(value AS type)` does for a type with the given affinity.
+fn cast(value: Value, affinity: SqlType) -> Value {
+    match (affinity, value) {
+        (_, Value::Null) => Value::Null,
+        (SqlType::Text, Value::Blob(bytes)) => Value::Text(String::from_utf8_lossy(&bytes).into_owned()),
+        (SqlType::Text, value) => Value::Text(value.to_string()),
+        (SqlType::Blob, Value::Blob(bytes)) => Value::Blob(bytes),
+        (SqlType::Blob, value) => Value::Blob(value.to_string().into_bytes()),
+        (SqlType::Integer, Value::Integer(value)) => Value::Integer(value),
+        (SqlType::Integer, Value::Real(value)) => Value::Integer(value as i64),
+        (SqlType::Integer, value) => Value::Integer(integer_prefix(&value.to_string())),
+        (SqlType::Real, Value::Integer(value)) => Value::Real(value as f64),
+        (SqlType::Real, Value::Real(value)) => Value::Real(value),
+        (SqlType::Real, value) => Value::Real(numeric_prefix(&value.to_string()).0.parse().unwrap_or(0.0)),
+        (SqlType::Numeric, value @ (Value::Integer(_) | Value::Real(_))) => value,
+        // Text becomes an integer if it spells one that fits, or a real with a small enough
+        // integral value; anything else becomes a real.
+        (SqlType::Numeric, value) => {
+            let text = value.to_string();
+            let (prefix, is_integer) = numeric_prefix(&text);
+            match (is_integer.then(|| prefix.parse::<i64>().ok()).flatten(), prefix.parse::<f64>().unwrap_or(0.0)) {
+                (Some(integer), _) => Value::Integer(integer),
+                (None, real) if real.fract() == 0.0 && (-2251799813685248.0..2251799813685248.0).contains(&real) => Value::Integer(real as i64),
+                (None, real) => Value::Real(real),
+            }
+        }
+    }
+}
+
+/// Whether a WHERE condition holds: NULL and zero are false, other values are tested as numbers.
+pub fn is_truthy(value: &Value) -> bool {
+    truth_value(value) == Some(true)
+}
+
+/// Three-valued truth of a value as used by AND, OR and NOT: `None` stands for unknown (NULL).
+fn truth_value(value: &Value) -> Option<bool> {
+    match to_numeric(value) {
+        Value::Null => None,
+        Value::Integer(value) => Some(value != 0),
+        Value::Real(value) => Some(value != 0.0),
+        _ => Some(false),
+    }
+}
+
+pub fn as_f64(value: &Value) -> f64 {
+    match value {
+        Value::Integer(value) => *value as f64,
+        Value::Real(value) => *value,
+        _ => 0.0,
+    }
+}
+
+/// Integer arithmetic stays exact; on overflow SQLite falls back to floating point.
+fn arithmetic(op: BinaryOp, lhs: &Value, rhs: &Value) -> Value {
+    let (lhs, rhs) = (to_numeric(lhs), to_numeric(rhs));
+
+    if let (Value::Integer(a), Value::Integer(b)) = (&lhs, &rhs) {
+        let exact = match op {
+            BinaryOp::Add => a.checked_add(*b),
+            BinaryOp::Subtract => a.checked_sub(*b),
+            BinaryOp::Multiply => a.checked_mul(*b),
+            BinaryOp::Divide if *b == 0 => return Value::Null,
+            BinaryOp::Divide => a.checked_div(*b),
+            BinaryOp::Remainder if *b == 0 => return Value::Null,
+            BinaryOp::Remainder => Some(a.checked_rem(*b).unwrap_or(0)),
+            BinaryOp::Concat => unreachable!("concatenation is not arithmetic"),
+        };
+        if let Some(result) = exact {
+            return Value::Integer(result);
+        }
+    }
+
+    let (a, b) = (as_f64(&lhs), as_f64(&rhs));
+    match op {
+        BinaryOp::Add => Value::Real(a + b),
+        BinaryOp::Subtract => Value::Real(a - b),
+        BinaryOp::Multiply => Value::Real(a * b),
+        BinaryOp::Divide if b == 0.0 => Value::Null,
+        BinaryOp::Divide => Value::Real(a / b),
+        BinaryOp::Remainder => {
+            let (a, b) = (a as i64, b as i64);
+            if b == 0 { Value::Null } else { Value::Real(a.checked_rem(b).unwrap_or(0) as f64) }
+        }
+        BinaryOp::Concat => unreachable!("concatenation is not arithmetic"),
+    }
+}
+
+/// Appends the index of every column `expr` reads to `columns`.
+pub fn referenced_columns(expr: &Expr, columns: &mut Vec<usize>) {
+    match expr {
+        Expr::Literal(_) => {}
+        Expr::Column(idx) => columns.push(*idx),
+        Expr::Unary(_, operand) | Expr::Cast(operand, _) | Expr::In(operand, _, _) | Expr::Collate(operand, _) => referenced_columns(operand, columns),
+        Expr::Binary(_, lhs, rhs) | Expr::Compare(_, lhs, rhs, _) | Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => {
+            referenced_columns(lhs, columns);
+            referenced_columns(rhs, columns);
+        }
+        Expr::Function(_, args) => {
+            for arg in args {
+                referenced_columns(arg, columns);
+            }
+        }
+        Expr::Case(whens, otherwise) => {
+            for (condition, result) in whens {
+                referenced_columns(condition, columns);
+                referenced_columns(result, columns);
+            }
+            if let Some(otherwise) = otherwise {
+                referenced_columns(otherwise, columns);
+            }
+        }
+    }
+}
+
+/// SQLite's NULL rule for binary operators: arithmetic, concatenation and comparisons are NULL
+/// when either operand is. The `null_safe` IS and IS NOT instead compare NULL as a value equal
+/// only to itself, so their result is never NULL.
+fn yields_null(lhs: &Value, rhs: &Value, null_safe: bool) -> bool {
+    !null_safe && (*lhs == Value::Null || *rhs == Value::Null)
+}
+
+/// Evaluates `expr` against one row; see `yields_null` for how binary operators treat NULL.
+pub fn evaluate(expr: &Expr, row: &[Value]) -> Result<Value> {
+    Ok(match expr {
+        Expr::Literal(value) => value.clone(),
+        Expr::Column(idx) => row.get(*idx).cloned().unwrap_or(Value::Null),
+        Expr::Unary(UnaryOp::Plus, operand) => evaluate(operand, row)?,
+        Expr::Unary(UnaryOp::Not, operand) => match truth_value(&evaluate(operand, row)?) {
+            Some(value) => Value::Integer(!value as i64),
+            None => Value::Null,
+        },
+        Expr::Unary(UnaryOp::Negate, operand) => match to_numeric(&evaluate(operand, row)?) {
+            Value::Null => Value::Null,
+            Value::Integer(value) => value.checked_neg()
+                .map_or(Value::Real(-(value as f64)), Value::Integer),
+            value => Value::Real(-as_f64(&value)),
+        },
+        Expr::Binary(op, lhs, rhs) => {
+            let (lhs, rhs) = (evaluate(lhs, row)?, evaluate(rhs, row)?);
+            match op {
+                _ if yields_null(&lhs, &rhs, false) => Value::Null,
+                BinaryOp::Concat => Value::Text(format!("{}{}", lhs, rhs)),
+                _ => arithmetic(*op, &lhs, &rhs),
+            }
+        }
+        Expr::Compare(op, lhs, rhs, collation) => {
+            let (lhs, rhs) = (evaluate(lhs, row)?, evaluate(rhs, row)?);
+            if yields_null(&lhs, &rhs, matches!(op, CompareOp::Is | CompareOp::IsNot)) {
+                return Ok(Value::Null);
+            }
+            let ordering = match (&lhs, &rhs) {
+                (Value::Null, Value::Null) => Ordering::Equal,
+                (Value::Null, _) | (_, Value::Null) => Ordering::Less,
+                _ => compare_values(&lhs, &rhs, *collation),
+            };
+            let holds = match op {
+                CompareOp::Eq | CompareOp::Is => ordering.is_eq(),
+                CompareOp::NotEq | CompareOp::IsNot => ordering.is_ne(),
+                CompareOp::Lt => ordering.is_lt(),
+                CompareOp::Le => ordering.is_le(),
+                CompareOp::Gt => ordering.is_gt(),
+                CompareOp::Ge => ordering.is_ge(),
+            };
+            Value::Integer(holds as i64)
+        }
+        Expr::Cast(operand, affinity) => cast(evaluate(operand, row)?, *affinity),
+        Expr::Collate(operand, _) => evaluate(operand, row)?,
+        // Not finding the value among others that include a NULL makes the result unknown.
+        Expr::In(operand, values, collation) => match evaluate(operand, row)? {
+            _ if values.is_empty() => Value::Integer(0),
+            Value::Null => Value::Null,
+            value if values.binary_search_by(|v| compare_values(v, &value, *collation)).is_ok() => Value::Integer(1),
+            _ if values.contains(&Value::Null) => Value::Null,
+            _ => Value::Integer(0),
+        },
+        Expr::Function(function, args) => {
+            let args = args.iter().map(|arg| evaluate(arg, row)).collect::<Result<Vec<_>>>()?;
+            function.call(&args)?
+        }
+        Expr::And(lhs, rhs) => match (truth_value(&evaluate(lhs, row)?), truth_value(&evaluate(rhs, row)?)) {
+            (Some(false), _) | (_, Some(false)) => Value::Integer(0),
+            (Some(true), Some(true)) => Value::Integer(1),
+            _ => Value::Null,
+        },
+        Expr::Or(lhs, rhs) => match (truth_value(&evaluate(lhs, row)?), truth_value(&evaluate(rhs, row)?)) {
+            (Some(true), _) | (_, Some(true)) => Value::Integer(1),
+            (Some(false), Some(false)) => Value::Integer(0),
+            _ => Value::Null,
+        },
+        // Only the branch taken is evaluated.
+        Expr::Case(whens, otherwise) => {
+            for (condition, result) in whens {
+                if truth_value(&evaluate(condition, row)?) == Some(true) {
+                    return evaluate(result, row);
+                }
+            }
+            match otherwise {
+                Some(otherwise) => evaluate(otherwise, row)?,
+                None => Value::Null,
+            }
+        }
+    })
+}
+
+fn like_match_chars(pattern: &[char], text: &[char], escape: Option<char>) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(&c) if Some(c) == escape => match (pattern.get(1), text.first()) {
+            (Some(&literal), Some(&t)) => literal.eq_ignore_ascii_case(&t) && like_match_chars(&pattern[2..], &text[1..], escape),
+            _ => false,
+        },
+        Some('%') => (0..=text.len()).any(|skip| like_match_chars(&pattern[1..], &text[skip..], escape)),
+        Some('_') => !text.is_empty() && like_match_chars(&pattern[1..], &text[1..], escape),
+        Some(&c) => text.first().is_some_and(|&t| c.eq_ignore_ascii_case(&t)) && like_match_chars(&pattern[1..], &text[1..], escape),
+    }
+}
+
+fn glob_match_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => (0..=text.len()).any(|skip| glob_match_chars(&pattern[1..], &text[skip..])),
+        Some('?') => !text.is_empty() && glob_match_chars(&pattern[1..], &text[1..]),
+        Some('[') => match (text.first(), glob_char_class(&pattern[1..], text.first().copied())) {
+            (Some(_), Some((true, rest))) => glob_match_chars(rest, &text[1..]),
+            _ => false,
+        },
+        Some(&c) => text.first() == Some(&c) && glob_match_chars(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Matches `c` against the body of a `[...]` class (the pattern just after `[`) and returns
+/// the outcome with the rest of the pattern, or `None` if the class is never closed.
+fn glob_char_class(pattern: &[char], c: Option<char>) -> Option<(bool, &[char])> {
+    let negated = pattern.first() == Some(&'^');
+    let mut i = negated as usize;
+    let mut matched = false;
+
+    // A `]` straight after `[` or `[^` is a member rather than the end of the class.
+    if pattern.get(i) == Some(&']') {
+        matched |= c == Some(']');
+        i += 1;
+    }
+    loop {
+        match *pattern.get(i)? {
+            ']' => return Some((matched != negated, &pattern[i + 1..])),
+            low if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|&high| high != ']') => {
+                matched |= c.is_some_and(|c| low <= c && c <= pattern[i + 2]);
+                i += 3;
+            }
+            member => {
+                matched |= c == Some(member);
+                i += 1;
+            }
+        }
+    }
+}
+
+/// SQLite's GLOB: `*` matches any run of characters, `?` exactly one, and `[...]` one character
+/// from a set that may contain ranges and be negated with `^`. Matching is case-sensitive.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_match_chars(&pattern, &text)
+}
+
+/// SQLite's LIKE: `%` matches any run of characters, `_` exactly one, and ASCII letters match
+/// case-insensitively. A character preceded by `escape` (including `%`, `_` and the escape
+/// itself) only matches literally.
+pub fn like_match(pattern: &str, text: &str, escape: Option<char>) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    like_match_chars(&pattern, &text, escape)
+}
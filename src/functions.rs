@@ -0,0 +1,197 @@
+use anyhow::{Result, bail};
+use std::ops::RangeInclusive;
+use crate::{Collation, SqliteError, Value, compare_values};
+use crate::datetime::{date, strftime};
+use crate::expr::{as_f64, glob_match, like_match, to_numeric};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScalarFunction {
+    Like,
+    Glob,
+    Replace,
+    Instr,
+    Hex,
+    Length,
+    Abs,
+    Round,
+    Min,
+    Max,
+    Date,
+    Strftime,
+}
+
+impl ScalarFunction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "like" => Some(ScalarFunction::Like),
+            "glob" => Some(ScalarFunction::Glob),
+            "replace" => Some(ScalarFunction::Replace),
+            "instr" => Some(ScalarFunction::Instr),
+            "hex" => Some(ScalarFunction::Hex),
+            "length" => Some(ScalarFunction::Length),
+            "abs" => Some(ScalarFunction::Abs),
+            "round" => Some(ScalarFunction::Round),
+            "min" => Some(ScalarFunction::Min),
+            "max" => Some(ScalarFunction::Max),
+            "date" => Some(ScalarFunction::Date),
+            "strftime" => Some(ScalarFunction::Strftime),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ScalarFunction::Like => "like",
+            ScalarFunction::Glob => "glob",
+            ScalarFunction::Replace => "replace",
+            ScalarFunction::Instr => "instr",
+            ScalarFunction::Hex => "hex",
+            ScalarFunction::Length => "length",
+            ScalarFunction::Abs => "abs",
+            ScalarFunction::Round => "round",
+            ScalarFunction::Min => "min",
+            ScalarFunction::Max => "max",
+            ScalarFunction::Date => "date",
+            ScalarFunction::Strftime => "strftime",
+        }
+    }
+
+    fn arity(self) -> RangeInclusive<usize> {
+        match self {
+            ScalarFunction::Like => 2..=3,
+            ScalarFunction::Glob | ScalarFunction::Instr => 2..=2,
+            ScalarFunction::Replace => 3..=3,
+            ScalarFunction::Hex | ScalarFunction::Length | ScalarFunction::Abs => 1..=1,
+            ScalarFunction::Round => 1..=2,
+            ScalarFunction::Min | ScalarFunction::Max => 2..=usize::MAX,
+            ScalarFunction::Date => 0..=usize::MAX,
+            ScalarFunction::Strftime => 1..=usize::MAX,
+        }
+    }
+
+    pub fn check_arity(self, n_args: usize) -> Result<()> {
+        if !self.arity().contains(&n_args) {
+            bail!(SqliteError::UnsupportedSql(format!("wrong number of arguments to function {}()", self.name())));
+        }
+        Ok(())
+    }
+
+    /// Applies the function to already evaluated arguments.
+    pub fn call(self, args: &[Value]) -> Result<Value> {
+        match self {
+            // like(X, Y, Z) is `Y LIKE X ESCAPE Z`; NULL in any argument gives NULL.
+            ScalarFunction::Like => {
+                let escape = match args.get(2) {
+                    Some(Value::Null) => return Ok(Value::Null),
+                    Some(escape) => Some(escape_char(escape)?),
+                    None => None,
+                };
+                Ok(match (&args[0], &args[1]) {
+                    (Value::Null, _) | (_, Value::Null) => Value::Null,
+                    (pattern, text) => Value::Integer(like_match(&pattern.to_string(), &text.to_string(), escape) as i64),
+                })
+            }
+            // glob(X, Y) is `Y GLOB X`.
+            ScalarFunction::Glob => Ok(match (&args[0], &args[1]) {
+                (Value::Null, _) | (_, Value::Null) => Value::Null,
+                (pattern, text) => Value::Integer(glob_match(&pattern.to_string(), &text.to_string()) as i64),
+            }),
+            ScalarFunction::Replace => Ok(match (&args[0], &args[1], &args[2]) {
+                (Value::Null, _, _) | (_, Value::Null, _) | (_, _, Value::Null) => Value::Null,
+                (text, find, _) if find.to_string().is_empty() => Value::Text(text.to_string()),
+                (text, find, replacement) => Value::Text(text.to_string().replace(&find.to_string(), &replacement.to_string())),
+            }),
+            // 1-based position of the first occurrence, or 0. Blobs are searched byte-wise,
+            // everything else character-wise.
+            ScalarFunction::Instr => Ok(match (&args[0], &args[1]) {
+                (Value::Null, _) | (_, Value::Null) => Value::Null,
+                (Value::Blob(_), Value::Blob(needle)) if needle.is_empty() => Value::Integer(1),
+                (Value::Blob(haystack), Value::Blob(needle)) => {
+                    Value::Integer(haystack.windows(needle.len()).position(|w| w == needle.as_slice()).map_or(0, |i| i + 1) as i64)
+                }
+                (haystack, needle) => {
+                    let haystack = haystack.to_string();
+                    Value::Integer(haystack.find(&needle.to_string())
+                        .map_or(0, |byte_idx| haystack[..byte_idx].chars().count() + 1) as i64)
+                }
+            }),
+            // Unlike most functions, hex(NULL) is an empty string rather than NULL.
+            ScalarFunction::Hex => {
+                let bytes = match &args[0] {
+                    Value::Null => vec![],
+                    Value::Blob(bytes) => bytes.clone(),
+                    value => value.to_string().into_bytes(),
+                };
+                Ok(Value::Text(bytes.iter().map(|b| format!("{:02X}", b)).collect()))
+            }
+            // Characters for text, bytes for blobs; numbers count the characters of their text form.
+            ScalarFunction::Length => Ok(match &args[0] {
+                Value::Null => Value::Null,
+                Value::Blob(bytes) => Value::Integer(bytes.len() as i64),
+                value => Value::Integer(value.to_string().chars().count() as i64),
+            }),
+            ScalarFunction::Abs => Ok(match &args[0] {
+                Value::Null => Value::Null,
+                Value::Integer(value) => Value::Integer(value.checked_abs()
+                    .ok_or_else(|| SqliteError::TypeError("integer overflow".to_string()))?),
+                value => Value::Real(as_f64(&to_numeric(value)).abs()),
+            }),
+            ScalarFunction::Round => {
+                let digits = match args.get(1) {
+                    Some(Value::Null) => return Ok(Value::Null),
+                    Some(digits) => as_f64(&to_numeric(digits)) as i64,
+                    None => 0,
+                };
+                Ok(match &args[0] {
+                    Value::Null => Value::Null,
+                    value => Value::Real(round(as_f64(&to_numeric(value)), digits.clamp(0, 30) as usize)),
+                })
+            }
+            // Any NULL argument makes the result NULL. Ties go to the first maximum and the last
+            // minimum, as in SQLite.
+            ScalarFunction::Min | ScalarFunction::Max => {
+                if args.contains(&Value::Null) {
+                    return Ok(Value::Null);
+                }
+                let best = args.iter().reduce(|best, arg| {
+                    let ordering = compare_values(best, arg, Collation::Binary);
+                    let replace = if self == ScalarFunction::Min { ordering.is_ge() } else { ordering.is_lt() };
+                    if replace { arg } else { best }
+                });
+                Ok(best.cloned().unwrap_or(Value::Null))
+            }
+            ScalarFunction::Date => date(args),
+            ScalarFunction::Strftime => strftime(args),
+        }
+    }
+}
+
+/// Rounds half away from zero to `digits` decimal places. Values too large to have a
+/// fractional part are returned unchanged.
+fn round(value: f64, digits: usize) -> f64 {
+    if !(-4503599627370496.0..=4503599627370496.0).contains(&value) {
+        return value;
+    }
+    if digits == 0 {
+        return (value + 0.5f64.copysign(value)) as i64 as f64;
+    }
+
+    // Formatting rounds ties to even. `value` lies exactly halfway between two candidates only
+    // if `value * 2^(digits + 1)` is an odd integer; nudge those away from zero first.
+    let scaled = value * 2f64.powi(digits as i32 + 1);
+    let value = match scaled % 2.0 {
+        1.0 => value.next_up(),
+        -1.0 => value.next_down(),
+        _ => value,
+    };
+    format!("{:.*}", digits, value).parse().unwrap_or(value)
+}
+
+fn escape_char(value: &Value) -> Result<char> {
+    let escape = value.to_string();
+    let mut chars = escape.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => bail!(SqliteError::TypeError("ESCAPE expression must be a single character".to_string())),
+    }
+}
@@ -0,0 +1,3974 @@
+mod datetime;
+mod error;
+mod expr;
+mod functions;
+mod lock;
+#[cfg(feature = "serde")]
+mod serialize;
+mod wal;
+
+use anyhow::{Result, bail, Context};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, LazyLock};
+use regex::Regex;
+use expr::{CompareOp, Expr, column_collation, evaluate, is_truthy, parse_expr, parse_expr_with_aggregates, parse_expr_with_subqueries, referenced_columns, to_numeric};
+use lock::LockState;
+use wal::Wal;
+
+pub use error::SqliteError;
+
+#[derive(Debug, Clone)]
+struct Column {
+    name: String,
+    tpe: SqlType,
+    collation: Option<Collation>,
+    rowid_alias: bool,
+    /// The table, or its alias in FROM, that qualifies the column as `table.column`; `None`
+    /// for the columns of a view or subquery.
+    table: Option<String>,
+}
+
+struct Record {
+    serial_types: Vec<u64>,
+    data: Vec<Vec<u8>>,
+    /// Copied from the pager the record was read through; see `extract_string`.
+    strict_text: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PageType {
+    InteriorIndex = 0x2,
+    InteriorTable = 0x5,
+    LeafIndex = 0xa,
+    LeafTable = 0xd
+}
+
+impl PageType {
+    fn from_u8(value: u8, page_num: u32) -> Result<Self> {
+        match value {
+            0x02 => Ok(PageType::InteriorIndex),
+            0x05 => Ok(PageType::InteriorTable),
+            0x0a => Ok(PageType::LeafIndex),
+            0x0d => Ok(PageType::LeafTable),
+            _ => bail!(SqliteError::corrupt(page_num, format!("invalid page type {:#04x}", value))),
+        }
+    }
+}
+
+/// A column's type affinity, derived from its declared type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SqlType {
+    Integer,
+    Text,
+    Real,
+    Blob,
+    Numeric,
+}
+
+impl fmt::Display for SqlType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SqlType::Integer => "INTEGER",
+            SqlType::Text => "TEXT",
+            SqlType::Real => "REAL",
+            SqlType::Blob => "BLOB",
+            SqlType::Numeric => "NUMERIC",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl SqlType {
+    /// Determines a column's affinity from its declared type using SQLite's substring rules.
+    fn from_str(s: &str) -> Self {
+        let s = s.to_uppercase();
+        if s.contains("INT") {
+            SqlType::Integer
+        } else if s.contains("CHAR") || s.contains("CLOB") || s.contains("TEXT") {
+            SqlType::Text
+        } else if s.is_empty() || s.contains("BLOB") {
+            SqlType::Blob
+        } else if s.contains("REAL") || s.contains("FLOA") || s.contains("DOUB") {
+            SqlType::Real
+        } else {
+            SqlType::Numeric
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Collation {
+    #[default]
+    Binary,
+    NoCase,
+}
+
+impl Collation {
+    fn from_str(s: &str) -> Option<Self> {
+        match s.to_uppercase().as_str() {
+            "BINARY" => Some(Collation::Binary),
+            "NOCASE" => Some(Collation::NoCase),
+            _ => None,
+        }
+    }
+
+    /// BINARY compares raw UTF-8 bytes; NOCASE additionally folds ASCII letters, as SQLite does.
+    fn compare_text(self, a: &str, b: &str) -> Ordering {
+        match self {
+            Collation::Binary => a.as_bytes().cmp(b.as_bytes()),
+            Collation::NoCase => a.bytes().map(|c| c.to_ascii_lowercase())
+                .cmp(b.bytes().map(|c| c.to_ascii_lowercase())),
+        }
+    }
+}
+
+impl Column {
+    fn from_strs(name: &str, col_type: &str) -> Self {
+        Column {
+            name: name.to_string(),
+            tpe: SqlType::from_str(col_type),
+            collation: None,
+            rowid_alias: false,
+            table: None,
+        }
+    }
+
+    fn collation(&self) -> Collation {
+        self.collation.unwrap_or_default()
+    }
+}
+
+/// An equality lookup on an indexed column; the value already has the column's affinity applied.
+struct Filter {
+    collation: Collation,
+    value: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AggregateFunc {
+    Count,
+    Min,
+    Max,
+    Sum,
+    Total,
+    Avg,
+}
+
+impl AggregateFunc {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "count" => Some(AggregateFunc::Count),
+            "min" => Some(AggregateFunc::Min),
+            "max" => Some(AggregateFunc::Max),
+            "sum" => Some(AggregateFunc::Sum),
+            "total" => Some(AggregateFunc::Total),
+            "avg" => Some(AggregateFunc::Avg),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            AggregateFunc::Count => "count",
+            AggregateFunc::Min => "min",
+            AggregateFunc::Max => "max",
+            AggregateFunc::Sum => "sum",
+            AggregateFunc::Total => "total",
+            AggregateFunc::Avg => "avg",
+        }
+    }
+}
+
+/// An aggregate call in a result column, HAVING or ORDER BY. `arg` is `None` for the `*` of
+/// `COUNT(*)`; with `distinct` duplicate values of the argument are only counted once.
+struct Aggregate {
+    func: AggregateFunc,
+    arg: Option<Expr>,
+    distinct: bool,
+}
+
+struct OrderTerm {
+    expr: Expr,
+    descending: bool,
+    collation: Collation,
+}
+
+/// A LIMIT clause; `count` is `None` when the limit is negative, i.e. unbounded.
+struct Limit {
+    count: Option<usize>,
+    offset: usize,
+}
+
+impl Limit {
+    fn apply(&self, rows: Vec<Vec<Value>>) -> Vec<Vec<Value>> {
+        rows.into_iter().skip(self.offset).take(self.count.unwrap_or(usize::MAX)).collect()
+    }
+
+    /// How many rows from the front of the ordered result the clause can reach.
+    fn end(&self) -> Option<usize> {
+        self.count.map(|count| count.saturating_add(self.offset))
+    }
+}
+
+#[derive(Debug)]
+struct TableInfo {
+    tpe: String,
+    name: String,
+    tbl_name: String,
+    rootpage: u32,
+    sql: String,
+    columns: Vec<Column>,
+    index_col: Option<String>
+}
+
+/// Summarizes a schema entry for diagnostics, e.g. `table apples (rootpage 2): id INTEGER,
+/// name TEXT`, listing each column with its affinity.
+impl fmt::Display for TableInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} (rootpage {})", self.tpe, self.name, self.rootpage)?;
+        for (i, column) in self.columns.iter().enumerate() {
+            let separator = if i == 0 { ":" } else { "," };
+            write!(f, "{} {} {}", separator, column.name, column.tpe)?;
+        }
+        Ok(())
+    }
+}
+
+impl TableInfo {
+    fn rowid_alias(&self) -> Option<usize> {
+        self.columns.iter().position(|col| col.rowid_alias)
+    }
+
+    /// Index of the value holding the rowid in a full row: the INTEGER PRIMARY KEY column if
+    /// there is one, otherwise a pseudo-column appended after the declared columns.
+    fn rowid_column(&self) -> usize {
+        self.rowid_alias().unwrap_or(self.columns.len())
+    }
+
+    /// For a view, the SELECT it stands for and the column names from an explicit
+    /// `CREATE VIEW name(col, ...)` list, if it has one.
+    fn view_select(&self) -> Result<(String, Option<Vec<String>>)> {
+        let view_re = Regex::new(&format!(
+            r"(?is)^\s*CREATE\s+(?:TEMP\s+|TEMPORARY\s+)?VIEW\s+(?:IF\s+NOT\s+EXISTS\s+)?(?:{0}\s*\.\s*)?{0}\s*(?:\((.*?)\)\s*)?AS\s+(.+?)\s*;?\s*$",
+            IDENTIFIER_PATTERN
+        ))?;
+        let caps = view_re.captures(&self.sql)
+            .ok_or_else(|| SqliteError::UnsupportedSql(format!("unsupported view definition: {}", self.sql)))?;
+        let column_names = caps.get(1).map(|names| split_top_level(names.as_str(), ',').into_iter()
+            .map(|name| unquote_identifier(name.trim()).to_string())
+            .collect());
+        Ok((caps[2].to_string(), column_names))
+    }
+
+    /// Virtual tables are listed with rootpage 0: their rows come from a module rather than a
+    /// b-tree, so there is nothing to scan.
+    fn check_has_btree(&self) -> Result<()> {
+        if self.rootpage == 0 {
+            bail!(SqliteError::UnsupportedSql(format!("cannot scan '{}' directly: it has no b-tree (rootpage 0)", self.name)));
+        }
+        Ok(())
+    }
+
+    /// Indices to decode for a full row, including the rowid pseudo-column when needed.
+    fn full_row_idxs(&self) -> Vec<usize> {
+        (0..self.columns.len()).chain(self.rowid_alias().is_none().then_some(self.columns.len())).collect()
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+/// Writes the value as an SQL literal, so its type shows: `NULL`, `1`, `1.0`, `'text'`, `X'01'`.
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Real(value) => write!(f, "{}", format_real(*value)),
+            Value::Text(value) => write!(f, "'{}'", value.replace('\'', "''")),
+            Value::Blob(value) => write!(f, "X'{}'", value.iter().map(|b| format!("{:02X}", b)).collect::<String>()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Real(value) => write!(f, "{}", format_real(*value)),
+            Value::Text(value) => write!(f, "{}", value),
+            Value::Blob(value) => write!(f, "{}", String::from_utf8_lossy(value)),
+        }
+    }
+}
+
+/// Quotes a CSV field the way the sqlite3 shell does: only when it contains whitespace,
+/// control characters, non-ASCII bytes, quotes or the separator, doubling embedded quotes.
+pub fn csv_field(s: &str, separator: &str) -> String {
+    let needs_quote = s.bytes().any(|b| b <= b' ' || b >= 0x7f || b == b'"') || (!separator.is_empty() && s.contains(separator));
+    if needs_quote {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn trim_fraction(digits: &str) -> String {
+    if !digits.contains('.') {
+        return format!("{}.0", digits);
+    }
+
+    let trimmed = digits.trim_end_matches('0');
+    if trimmed.ends_with('.') {
+        format!("{}0", trimmed)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Formats a REAL the way the sqlite3 shell does (`%!.15g`): at most 15 significant digits,
+/// trailing zeros trimmed but always keeping one fractional digit, e.g. `3.0`, `0.3`,
+/// `1.0e+20` and `1.5e-07`.
+pub fn format_real(value: f64) -> String {
+    if value.is_infinite() {
+        return if value > 0.0 { "Inf" } else { "-Inf" }.to_string();
+    }
+    if value == 0.0 {
+        return "0.0".to_string();
+    }
+
+    let scientific = format!("{:.14e}", value);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+
+    if !(-4..15).contains(&exponent) {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", trim_fraction(mantissa), sign, exponent.abs())
+    } else {
+        trim_fraction(&format!("{:.*}", (14 - exponent) as usize, value))
+    }
+}
+
+/// Auto-vacuum mode from the database header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutoVacuum {
+    None,
+    Full,
+    Incremental,
+}
+
+impl fmt::Display for AutoVacuum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AutoVacuum::None => write!(f, "none"),
+            AutoVacuum::Full => write!(f, "full"),
+            AutoVacuum::Incremental => write!(f, "incremental"),
+        }
+    }
+}
+
+/// The result of a query: its column names, and its rows as an iterator. A query that only
+/// filters a table and cuts it down with LIMIT reads the table's pages as the rows are
+/// iterated, so an error can come out in place of a row. Grouping, DISTINCT and sorting by
+/// anything but the rowid need every row first, so those queries read them all up front.
+pub struct Rows<'a> {
+    column_names: Vec<String>,
+    rows: RowStream<'a>,
+}
+
+impl Rows<'_> {
+    pub fn column_names(&self) -> &[String] {
+        &self.column_names
+    }
+
+    /// Reads the remaining rows, each paired with the column names.
+    pub fn into_rows(self) -> Result<Vec<Row>, SqliteError> {
+        let column_names: Arc<[String]> = self.column_names.clone().into();
+        self.map(|values| Ok(Row { column_names: column_names.clone(), values: values? })).collect()
+    }
+}
+
+/// A row of a query result that carries its column names, so it can be kept or passed on
+/// apart from the `Rows` it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    column_names: Arc<[String]>,
+    values: Vec<Value>,
+}
+
+impl Row {
+    pub fn column_names(&self) -> &[String] {
+        &self.column_names
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// The value of the first column named `name`, compared like an SQL identifier.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.column_names.iter().position(|column| ident_eq(column, name)).map(|i| &self.values[i])
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+}
+
+impl From<RowSet> for Rows<'_> {
+    fn from(RowSet { column_names, rows }: RowSet) -> Self {
+        Rows { column_names, rows: RowStream::Buffered(rows.into_iter()) }
+    }
+}
+
+impl Iterator for Rows<'_> {
+    type Item = Result<Vec<Value>, SqliteError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next().map(|row| row.map_err(SqliteError::from))
+    }
+}
+
+/// Where the rows of a `Rows` come from.
+enum RowStream<'a> {
+    Buffered(std::vec::IntoIter<Vec<Value>>),
+    Scan(Box<TableScan<'a>>),
+}
+
+impl Iterator for RowStream<'_> {
+    type Item = Result<Vec<Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            RowStream::Buffered(rows) => rows.next().map(Ok),
+            RowStream::Scan(scan) => scan.next(),
+        }
+    }
+}
+
+/// A SELECT evaluated row by row as a cursor walks the table: the cursor applies WHERE, then
+/// OFFSET rows are skipped and at most `remaining` more evaluated. An error ends the scan.
+struct TableScan<'a> {
+    cursor: RowCursor<'a>,
+    items: Vec<Expr>,
+    offset: usize,
+    remaining: usize,
+}
+
+impl Iterator for TableScan<'_> {
+    type Item = Result<Vec<Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.remaining > 0 {
+            let row = match self.cursor.next_row()? {
+                Ok(row) => row,
+                Err(e) => {
+                    self.remaining = 0;
+                    return Some(Err(e));
+                }
+            };
+            if self.offset > 0 {
+                self.offset -= 1;
+                continue;
+            }
+            self.remaining -= 1;
+            let values = self.items.iter().map(|expr| evaluate(expr, &row)).collect::<Result<Vec<Value>>>();
+            if values.is_err() {
+                self.remaining = 0;
+            }
+            return Some(values);
+        }
+
+        None
+    }
+}
+
+/// A query result held in memory, as it is passed between the stages of a query, e.g. from a
+/// subquery in FROM to the SELECT around it.
+struct RowSet {
+    column_names: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+/// A statement that has been parsed but not run.
+pub struct Statement {
+    sql: String,
+    column_names: Vec<String>,
+}
+
+impl Statement {
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    pub fn column_count(&self) -> usize {
+        self.column_names.len()
+    }
+
+    pub fn column_names(&self) -> &[String] {
+        &self.column_names
+    }
+}
+
+/// Iterates over the rows of a table in rowid order, or in reverse, each holding the table's
+/// columns as `SELECT *` would. Pages are read as the cursor reaches them, a leaf page's rows
+/// being decoded when the previous leaf's have all been returned. An error ends the iteration.
+pub struct RowCursor<'a> {
+    file: &'a mut Pager,
+    page_size: u32,
+    tinfo: &'a TableInfo,
+    col_idxs: Vec<usize>,
+    predicate: Option<Expr>,
+    /// The rowid range `predicate` allows; subtrees outside it are skipped.
+    min: i64,
+    max: i64,
+    reverse: bool,
+    /// Pages still to visit, the next one last.
+    pending: Vec<u32>,
+    visited: HashSet<u32>,
+    rows: std::vec::IntoIter<Vec<Value>>,
+}
+
+impl<'a> RowCursor<'a> {
+    fn new(file: &'a mut Pager, page_size: u32, tinfo: &'a TableInfo, col_idxs: Vec<usize>, predicate: Option<Expr>, reverse: bool) -> Self {
+        let (min, max) = predicate.as_ref()
+            .and_then(|predicate| rowid_bounds(predicate, tinfo.rowid_column()))
+            .unwrap_or((i64::MIN, i64::MAX));
+        let pending = if min <= max { vec![tinfo.rootpage] } else { vec![] };
+        RowCursor { file, page_size, tinfo, col_idxs, predicate, min, max, reverse, pending, visited: HashSet::new(), rows: vec![].into_iter() }
+    }
+
+    fn visit(&mut self, page_num: u32) -> Result<()> {
+        mark_visited(&mut self.visited, page_num)?;
+
+        match read_page_type(self.file, page_num, self.page_size)? {
+            PageType::InteriorTable => {
+                let children = table_children_in_range(self.file, self.page_size, page_num, self.min, self.max)?;
+                match self.reverse {
+                    true => self.pending.extend(children),
+                    false => self.pending.extend(children.into_iter().rev()),
+                }
+            }
+            PageType::LeafTable => {
+                let mut rows = get_page_data_with_filter(self.file, self.tinfo, &self.col_idxs, self.page_size, page_num, self.predicate.as_ref())?;
+                if self.reverse {
+                    rows.reverse();
+                }
+                self.rows = rows.into_iter();
+            }
+            page_type => bail!(SqliteError::corrupt(page_num, format!("unexpected page type {:?} in table b-tree", page_type))),
+        }
+
+        Ok(())
+    }
+
+    /// The next row matching the predicate, with the rowid pseudo-column still at its end.
+    fn next_row(&mut self) -> Option<Result<Vec<Value>>> {
+        loop {
+            if let Some(row) = self.rows.next() {
+                return Some(Ok(row));
+            }
+            let page_num = self.pending.pop()?;
+            if let Err(e) = self.visit(page_num) {
+                self.pending.clear();
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+impl Iterator for RowCursor<'_> {
+    type Item = Result<Vec<Value>, SqliteError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(match self.next_row()? {
+            Ok(mut row) => {
+                row.truncate(self.tinfo.columns.len());
+                Ok(row)
+            }
+            Err(e) => Err(e.into()),
+        })
+    }
+}
+
+/// A table column as declared in the schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub affinity: SqlType,
+}
+
+pub struct SchemaObject<'a> {
+    pub kind: &'a str,
+    pub name: &'a str,
+    pub sql: &'a str,
+}
+
+/// Where the pager reads the database image from: a file, or an in-memory buffer.
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+struct Pager {
+    source: Box<dyn ReadSeek>,
+    wal: Option<Wal>,
+    page_size: u64,
+    usable_size: u64,
+    auto_vacuum: AutoVacuum,
+    /// The largest root b-tree page (header offset 52); zero unless auto-vacuum is on.
+    largest_root_page: u32,
+    /// The incremental-vacuum flag (header offset 64).
+    incremental_vacuum: bool,
+    pos: u64,
+    rows_scanned: u64,
+    max_rows_scanned: Option<u64>,
+    /// Whether text that is not valid UTF-8 is an error rather than lossily decoded.
+    strict_text: bool,
+    current_page: Option<u32>,
+    pages_read: u64,
+}
+
+impl Pager {
+    /// Opens the file for reading only, so other readers and a writer's RESERVED lock don't
+    /// get in the way. A file another process has locked for writing is refused, since its
+    /// pages may be mid-rewrite.
+    ///
+    /// `-` reads the whole database from stdin into memory, and a `file:` URI stands for the
+    /// file it names (its query parameters are ignored).
+    fn open(path: &str) -> Result<Self> {
+        if path == "-" {
+            let mut bytes = vec![];
+            std::io::stdin().read_to_end(&mut bytes).context("Failed to read the database from stdin")?;
+            return Pager::from_bytes(bytes);
+        }
+
+        let path = uri_path(path);
+        let file = OpenOptions::new().read(true).open(path)?;
+        if lock::lock_state(&file)? == LockState::Exclusive {
+            bail!(SqliteError::Locked);
+        }
+        Pager::new(Box::new(file), Some(path))
+    }
+
+    /// A pager over a database image held in memory; there is no WAL.
+    fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        Pager::new(Box::new(Cursor::new(bytes)), None)
+    }
+
+    /// Reads the database header from `source`; the WAL next to `path`, if any, is read too.
+    fn new(mut source: Box<dyn ReadSeek>, path: Option<&str>) -> Result<Self> {
+        let mut header = [0; 100];
+        if source.read_exact(&mut header).is_err() || &header[0..16] != b"SQLite format 3\0" {
+            bail!(SqliteError::NotADatabase);
+        }
+        // A power of two from 512 to 65536, the largest being stored as 1.
+        let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+            1 => 65536,
+            page_size => page_size as u32,
+        };
+        if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+            bail!(SqliteError::corrupt(None, format!("invalid page size {}", page_size)));
+        }
+        let reserved_bytes = header[20] as u64;
+        if page_size as u64 - reserved_bytes < 480 {
+            bail!(SqliteError::corrupt(None, format!("{} reserved bytes leave too little of a {} byte page", reserved_bytes, page_size)));
+        }
+        let largest_root_page = u32::from_be_bytes([header[52], header[53], header[54], header[55]]);
+        let incremental_vacuum = header[64..68] != [0; 4];
+        // A non-zero largest root page means auto-vacuum is on; offset 64 tells full from incremental.
+        let auto_vacuum = match (largest_root_page != 0, incremental_vacuum) {
+            (false, _) => AutoVacuum::None,
+            (true, false) => AutoVacuum::Full,
+            (true, true) => AutoVacuum::Incremental,
+        };
+        let wal = match path {
+            Some(path) => Wal::open(path, page_size)?,
+            None => None,
+        };
+
+        Ok(Pager {
+            source,
+            wal,
+            page_size: page_size as u64,
+            usable_size: page_size as u64 - reserved_bytes,
+            auto_vacuum,
+            largest_root_page,
+            incremental_vacuum,
+            pos: 0,
+            rows_scanned: 0,
+            max_rows_scanned: None,
+            strict_text: false,
+            current_page: None,
+            pages_read: 0,
+        })
+    }
+
+    /// Number of pages in the database: the size recorded by the WAL's last commit if there is
+    /// one, otherwise the file's length in pages.
+    fn page_count(&mut self) -> Result<u32> {
+        if let Some(db_size) = self.wal.as_ref().and_then(|wal| wal.db_size()) {
+            return Ok(db_size);
+        }
+        let len = self.source.seek(SeekFrom::End(0))?;
+        Ok((len / self.page_size) as u32)
+    }
+
+    fn wal_commit_frame(&self) -> Option<u32> {
+        self.wal.as_ref().and_then(|wal| wal.commit_frame())
+    }
+
+    /// Whether `page_num` is an auto-vacuum pointer-map page. The first one is page 2 and each
+    /// covers the `usable_size / 5` pages that follow it; the lock-byte page is never one.
+    fn is_pointer_map_page(&self, page_num: u32) -> bool {
+        if self.auto_vacuum == AutoVacuum::None || page_num < 2 {
+            return false;
+        }
+        let pages_per_map = self.usable_size / 5 + 1;
+        let mut map_page = (page_num as u64 - 2) / pages_per_map * pages_per_map + 2;
+        if map_page == 1073741824 / self.page_size + 1 {
+            map_page += 1;
+        }
+        map_page == page_num as u64
+    }
+
+    fn reset_counters(&mut self) {
+        self.rows_scanned = 0;
+        self.pages_read = 0;
+        self.current_page = None;
+    }
+
+    fn record_rows_scanned(&mut self, n_rows: u64) -> Result<()> {
+        self.rows_scanned += n_rows;
+        if let Some(limit) = self.max_rows_scanned && self.rows_scanned > limit {
+            bail!(SqliteError::ScanLimitExceeded { limit });
+        }
+        Ok(())
+    }
+}
+
+impl Read for Pager {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let page_num = (self.pos / self.page_size) as u32 + 1;
+        let in_page_offset = self.pos % self.page_size;
+        let len = buf.len().min((self.page_size - in_page_offset) as usize);
+
+        // Counts page loads as if the pager cached the most recently read page.
+        if self.current_page != Some(page_num) {
+            self.current_page = Some(page_num);
+            self.pages_read += 1;
+        }
+
+        let n = match &mut self.wal {
+            Some(wal) if wal.contains(page_num) => wal.read_page_at(page_num, in_page_offset, &mut buf[..len])?,
+            _ => {
+                self.source.seek(SeekFrom::Start(self.pos))?;
+                self.source.read(&mut buf[..len])?
+            }
+        };
+
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for Pager {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.pos = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta)
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid seek"))?,
+            SeekFrom::End(delta) => self.source.seek(SeekFrom::End(0))?.checked_add_signed(delta)
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid seek"))?,
+        };
+        Ok(self.pos)
+    }
+}
+
+/// The file a `file:` URI such as `file:data.db?mode=ro` or `file:///tmp/data.db` names;
+/// anything else is a plain path.
+fn uri_path(path: &str) -> &str {
+    let Some(uri) = path.strip_prefix("file:") else {
+        return path;
+    };
+    let uri = uri.split(['?', '#']).next().unwrap_or(uri);
+    // An authority (`file://host/path`) comes before the path proper.
+    match uri.strip_prefix("//") {
+        Some(rest) => rest.find('/').map_or("", |slash| &rest[slash..]),
+        None => uri,
+    }
+}
+
+/// Decodes a SQLite varint, returning the value and the number of bytes it occupied.
+pub fn read_varint(data: &[u8]) -> (u64, usize) {
+    let mut i = 0;
+    let mut val: u64 = 0;
+
+    while i < 8 && i < data.len() {
+        let current_byte = data[i];
+        i += 1;
+
+        val = (val << 7) | ((current_byte & 0x7F) as u64);
+
+        if current_byte & 0x80 == 0 {
+            return (val, i);
+        }
+    }
+
+    if i < data.len() {
+        let current_byte = data[i];
+        val = (val << 8) | (current_byte as u64);
+        i += 1;
+    }
+
+    (val, i)
+}
+
+/// Encodes `value` as a canonical SQLite varint: 1 to 8 bytes of 7-bit groups (high bit set
+/// on all but the last), or 9 bytes for values above 56 bits, where the last byte carries 8 bits.
+pub fn write_varint(value: u64) -> Vec<u8> {
+    if value > 0x00FF_FFFF_FFFF_FFFF {
+        let high = value >> 8;
+        let mut bytes: Vec<u8> = (0..8).rev()
+            .map(|i| ((high >> (7 * i)) & 0x7F) as u8 | 0x80)
+            .collect();
+        bytes.push(value as u8);
+        return bytes;
+    }
+
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        bytes.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+    }
+    bytes.reverse();
+
+    bytes
+}
+
+/// Byte offset of page `page_num` (pages are numbered from 1). Page 0 does not exist, so a
+/// schema or child pointer naming it can only come from a corrupt file.
+fn page_offset(page_size: u32, page_num: u32) -> Result<u64> {
+    page_num.checked_sub(1)
+        .and_then(|index| (page_size as u64).checked_mul(index as u64))
+        .ok_or_else(|| SqliteError::corrupt(page_num, format!("invalid page number {}", page_num)).into())
+}
+
+fn page_header_offset(page_num: u32, page_offset: u64) -> u64 {
+    if page_num == 1 {
+        page_offset + 100
+    } else {
+        page_offset
+    }
+}
+
+fn mark_visited(visited: &mut HashSet<u32>, page_num: u32) -> Result<()> {
+    if !visited.insert(page_num) {
+        bail!(SqliteError::corrupt(page_num, format!("page cycle detected at page {}", page_num)));
+    }
+    Ok(())
+}
+
+fn read_varint_from(file: &mut Pager) -> Result<(u64, usize)> {
+    let mut buf = [0u8; 9];
+    let mut n = 0;
+
+    while n < buf.len() {
+        file.read_exact(&mut buf[n..n + 1])?;
+        n += 1;
+        if buf[n - 1] & 0x80 == 0 {
+            break;
+        }
+    }
+
+    Ok(read_varint(&buf[..n]))
+}
+
+fn get_serial_type_size(serial_type: u64) -> usize {
+    match serial_type {
+        0 | 8 | 9 => 0,
+        1 => 1,
+        2 => 2,
+        3 => 3,
+        4 => 4,
+        5 => 6,
+        6 | 7 => 8,
+        n if n >= 12 && n % 2 == 0 => ((n - 12) / 2) as usize,
+        n if n >= 13 && n % 2 == 1 => ((n - 13) / 2) as usize,
+        _ => 0,
+    }
+}
+
+/// Byte offset of field `col_idx` from the start of a record whose header is `header_size`
+/// bytes long and lists `serial_types`: the fields are stored back to back after the header.
+/// `None` if the sizes, which come from the file, overflow.
+fn field_offset(serial_types: &[u64], header_size: usize, col_idx: usize) -> Option<usize> {
+    serial_types[..col_idx].iter().try_fold(header_size, |offset, &serial_type| offset.checked_add(get_serial_type_size(serial_type)))
+}
+
+fn extract_integer(buffer: &[u8]) -> Result<i64> {
+    match buffer.len() {
+        0 => Ok(0),
+        1 => Ok(buffer[0] as i8 as i64),
+        2 => {
+            let bytes: [u8; 2] = buffer[0..2].try_into()
+                .context("Failed to read 2 bytes")?;
+            Ok(i16::from_be_bytes(bytes) as i64)
+        }
+        3 => {
+            let b1 = buffer[0] as i32;
+            let b2 = buffer[1] as i32;
+            let b3 = buffer[2] as i32;
+            let mut value = (b1 << 16) | (b2 << 8) | b3;
+            if value & 0x800000 != 0 {
+                value |= 0xFF000000u32 as i32;
+            }
+            Ok(value as i64)
+        }
+        4 => {
+            let bytes: [u8; 4] = buffer[0..4].try_into()
+                .context("Failed to read 4 bytes")?;
+            Ok(i32::from_be_bytes(bytes) as i64)
+        }
+        6 => {
+            let mut bytes = [0u8; 8];
+            bytes[2..8].copy_from_slice(buffer);
+            let mut value = i64::from_be_bytes(bytes);
+            if value & 0x800000000000 != 0 {
+                value |= 0xFFFF000000000000u64 as i64;
+            }
+            Ok(value)
+        }
+        8 => {
+            let bytes: [u8; 8] = buffer[0..8]
+                .try_into()
+                .context("Failed to read 8 bytes")?;
+            Ok(i64::from_be_bytes(bytes))
+        }
+        _ => bail!(SqliteError::corrupt(None, format!("invalid buffer length for integer type: {}", buffer.len()))),
+    }
+}
+
+fn extract_real(buffer: &[u8]) -> Result<f64> {
+    match buffer.len() {
+        8 => {
+            let bytes: [u8; 8] = buffer[0..8]
+                .try_into()
+                .context("Failed to read 8 bytes for float")?;
+            Ok(f64::from_be_bytes(bytes))
+        }
+        _ => bail!(SqliteError::corrupt(None, format!("invalid buffer length for floating type: {}", buffer.len()))),
+    }
+}
+
+/// Text is assumed to be UTF-8. Invalid bytes are replaced with U+FFFD unless `strict`, in
+/// which case they are an error; a UTF-16 database read as UTF-8 is the usual cause.
+fn extract_string(buffer: &[u8], strict: bool) -> Result<String> {
+    if strict {
+        return match std::str::from_utf8(buffer) {
+            Ok(text) => Ok(text.to_string()),
+            Err(e) => bail!(SqliteError::corrupt(None, format!("text value is not valid UTF-8: {}", e))),
+        };
+    }
+
+    Ok(String::from_utf8_lossy(buffer).to_string())
+}
+
+const IDENTIFIER_PATTERN: &str = r#"(?:"[^"]+"|\[[^\]]+\]|`[^`]+`|'[^']+'|\w+)"#;
+
+/// Whether a table is one SQLite maintains itself; user tables can't have the `sqlite_` prefix.
+pub fn is_internal_table(name: &str) -> bool {
+    name.get(..7).is_some_and(|prefix| prefix.eq_ignore_ascii_case("sqlite_"))
+}
+
+/// Identifier comparison: SQLite folds ASCII letters only, so `Apples` names `apples` while
+/// `É` and `é` stay distinct.
+fn ident_eq(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+fn unquote_identifier(ident: &str) -> &str {
+    let bytes = ident.as_bytes();
+    if bytes.len() >= 2 {
+        match (bytes[0], bytes[bytes.len() - 1]) {
+            (b'"', b'"') | (b'[', b']') | (b'`', b'`') | (b'\'', b'\'') => return &ident[1..ident.len() - 1],
+            _ => {}
+        }
+    }
+    ident
+}
+
+/// Splits `s` on `sep`, ignoring separators nested in parentheses or quotes.
+fn split_top_level(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '[') => quote = Some(']'),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, c) if c == sep && depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+
+    parts
+}
+
+/// Replaces each `-- ...` and `/* ... */` comment outside quotes with a space.
+pub fn strip_comments(sql: &str) -> String {
+    let mut stripped = String::with_capacity(sql.len());
+    let mut quote: Option<char> = None;
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '[') => quote = Some(']'),
+            (None, '-') if chars.peek() == Some(&'-') => {
+                chars.find(|&c| c == '\n');
+                stripped.push(' ');
+                continue;
+            }
+            (None, '/') if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                chars.find(|&c| std::mem::replace(&mut prev, c) == '*' && c == '/');
+                stripped.push(' ');
+                continue;
+            }
+            _ => {}
+        }
+        stripped.push(c);
+    }
+
+    stripped
+}
+
+/// Writes a bound value as the SQL literal that stands for it. Negative numbers are wrapped in
+/// parentheses so that following a `-` they cannot start a comment.
+fn param_literal(value: &Value) -> Result<String> {
+    Ok(match value {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(i64::MIN) => "(-9223372036854775807 - 1)".to_string(),
+        Value::Integer(value) if *value < 0 => format!("({})", value),
+        Value::Integer(value) => value.to_string(),
+        Value::Real(value) if value.is_nan() => "NULL".to_string(),
+        Value::Real(value) if value.is_infinite() => if *value < 0.0 { "(-1e999)" } else { "1e999" }.to_string(),
+        Value::Real(value) if *value < 0.0 => format!("({:?})", value),
+        Value::Real(value) => format!("{:?}", value),
+        Value::Text(value) => format!("'{}'", value.replace('\'', "''")),
+        Value::Blob(_) => format!("{:?}", value),
+    })
+}
+
+/// Substitutes `params` for the `?` and `?NNN` placeholders outside quotes, numbering them the
+/// way SQLite does: `?NNN` is parameter NNN and a bare `?` is the one after the largest number
+/// used so far. Comments must already be stripped. Every parameter must be used.
+fn bind_params(sql: &str, params: &[Value]) -> Result<String> {
+    let mut bound = String::with_capacity(sql.len());
+    let mut quote: Option<char> = None;
+    let mut chars = sql.chars().peekable();
+    let mut max_used = 0;
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '[') => quote = Some(']'),
+            (None, '?') => {
+                let mut digits = String::new();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(digit);
+                }
+                let n = match digits.parse::<usize>() {
+                    Ok(n) => n,
+                    Err(_) if digits.is_empty() => max_used + 1,
+                    Err(_) => 0,
+                };
+                let Some(value) = n.checked_sub(1).and_then(|i| params.get(i)) else {
+                    bail!(SqliteError::Misuse(format!("parameter ?{} is out of range: {} given", n, params.len())));
+                };
+                max_used = max_used.max(n);
+                bound.push(' ');
+                bound.push_str(&param_literal(value)?);
+                bound.push(' ');
+                continue;
+            }
+            _ => {}
+        }
+        bound.push(c);
+    }
+
+    if max_used != params.len() {
+        bail!(SqliteError::Misuse(format!("{} parameters given but the statement uses {}", params.len(), max_used)));
+    }
+
+    Ok(bound)
+}
+
+fn parse_columns(sql_str: &str) -> Result<Vec<Column>> {
+    if sql_str.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // The column list ends at the parenthesis matching the one it starts with; types such as
+    // NUMERIC(10,2) and DEFAULT expressions nest their own.
+    let sql = strip_comments(sql_str);
+    let create_re = Regex::new(&format!(r"(?si)CREATE\s+TABLE\s+{}\s*\(", IDENTIFIER_PATTERN))?;
+    let Some(open) = create_re.find(&sql).map(|m| m.end() - 1) else {
+        return Ok(vec![]);
+    };
+    let Some(close) = closing_paren(&sql, open) else {
+        return Ok(vec![]);
+    };
+
+    let constraint_re = Regex::new(r"(?i)^\s*(CONSTRAINT|PRIMARY|UNIQUE|CHECK|FOREIGN)\b")?;
+    let col_re = Regex::new(&format!(r"(?is)^\s*({})\s*(\w+)?", IDENTIFIER_PATTERN))?;
+    let keyword_re = Regex::new(r"(?i)^(CONSTRAINT|PRIMARY|NOT|NULL|UNIQUE|CHECK|DEFAULT|COLLATE|REFERENCES|GENERATED|AS)$")?;
+    let collate_re = Regex::new(r"(?i)\bCOLLATE\s+(\w+)")?;
+    let primary_key_re = Regex::new(r"(?i)\bPRIMARY\s+KEY(\s+DESC)?\b")?;
+
+    let mut columns = vec![];
+    for def in split_top_level(&sql[open + 1..close], ',') {
+        if constraint_re.is_match(def) {
+            continue;
+        }
+        let Some(col_caps) = col_re.captures(def) else {
+            continue;
+        };
+
+        let col_type = col_caps.get(2)
+            .map(|m| m.as_str())
+            .filter(|t| !keyword_re.is_match(t))
+            .unwrap_or("");
+        let mut column = Column::from_strs(unquote_identifier(&col_caps[1]), col_type);
+        column.collation = collate_re.captures(def).and_then(|c| Collation::from_str(&c[1]));
+        // Only a column declared exactly `INTEGER PRIMARY KEY` aliases the rowid; with DESC
+        // (or any other type spelling such as INT) it is an ordinary column.
+        let pk_desc = primary_key_re.captures(def).map(|c| c.get(1).is_some());
+        column.rowid_alias = col_type.eq_ignore_ascii_case("integer") && pk_desc == Some(false);
+        columns.push(column);
+    }
+
+    Ok(columns)
+}
+
+/// Number of payload bytes stored in the cell itself; the rest spills onto overflow pages.
+fn local_payload_size(usable_size: u64, payload_size: u64, index_cell: bool) -> u64 {
+    let max_local = if index_cell {
+        (usable_size - 12) * 64 / 255 - 23
+    } else {
+        usable_size - 35
+    };
+    if payload_size <= max_local {
+        return payload_size;
+    }
+
+    let min_local = (usable_size - 12) * 32 / 255 - 23;
+    let local = min_local + (payload_size - min_local) % (usable_size - 4);
+    if local <= max_local { local } else { min_local }
+}
+
+/// A cell's record payload: the bytes stored in the cell itself, followed by a chain of
+/// overflow pages that is only walked as far as the bytes actually requested. Each overflow
+/// page starts with the next page number (0 on the last page) followed by payload bytes.
+struct Payload {
+    /// The page holding the cell, for errors.
+    page: u32,
+    local: Vec<u8>,
+    size: usize,
+    first_overflow_page: u32,
+    /// The overflow page the cursor is on and the payload offset of its first byte.
+    overflow_page: u32,
+    overflow_start: usize,
+    visited: HashSet<u32>,
+}
+
+impl Payload {
+    fn new(page: u32, local: Vec<u8>, size: usize, first_overflow_page: u32) -> Self {
+        let overflow_start = local.len();
+        Payload { page, local, size, first_overflow_page, overflow_page: first_overflow_page, overflow_start, visited: HashSet::new() }
+    }
+
+    /// Returns payload bytes `start..start + len`. Overflow pages before the range are only
+    /// read for their next-page pointer, and pages after it are not touched.
+    fn read(&mut self, file: &mut Pager, start: usize, len: usize) -> Result<Vec<u8>> {
+        let Some(end) = start.checked_add(len).filter(|&end| end <= self.size) else {
+            bail!(SqliteError::corrupt(self.page, format!("record field of {} bytes at {} exceeds the {} byte payload", len, start, self.size)));
+        };
+
+        let mut out = Vec::with_capacity(len);
+        if start < self.local.len() {
+            out.extend_from_slice(&self.local[start..end.min(self.local.len())]);
+        }
+
+        let chunk = (file.usable_size - 4) as usize;
+        while out.len() < len {
+            let pos = start + out.len();
+            if pos < self.overflow_start {
+                self.overflow_page = self.first_overflow_page;
+                self.overflow_start = self.local.len();
+                self.visited.clear();
+            }
+            while pos >= self.overflow_start + chunk {
+                self.advance(file)?;
+                self.overflow_start += chunk;
+            }
+            if self.overflow_page == 0 {
+                bail!(SqliteError::corrupt(self.page, format!("overflow chain ended {} bytes early", self.size - pos)));
+            }
+            let n = (len - out.len()).min(self.overflow_start + chunk - pos);
+            let page_start = (self.overflow_page - 1) as u64 * file.page_size;
+            file.seek(SeekFrom::Start(page_start + 4 + (pos - self.overflow_start) as u64))?;
+            let filled = out.len();
+            out.resize(filled + n, 0);
+            file.read_exact(&mut out[filled..])
+                .context(format!("Failed to read overflow page {}", self.overflow_page))?;
+        }
+
+        Ok(out)
+    }
+
+    fn advance(&mut self, file: &mut Pager) -> Result<()> {
+        if self.overflow_page == 0 {
+            bail!(SqliteError::corrupt(self.page, "overflow chain ended early"));
+        }
+        self.visited.insert(self.overflow_page);
+
+        file.seek(SeekFrom::Start((self.overflow_page - 1) as u64 * file.page_size))?;
+        let mut next_page = [0u8; 4];
+        file.read_exact(&mut next_page)
+            .context(format!("Failed to read overflow page {}", self.overflow_page))?;
+        self.overflow_page = u32::from_be_bytes(next_page);
+        if self.visited.contains(&self.overflow_page) {
+            bail!(SqliteError::corrupt(self.overflow_page, format!("page cycle detected at page {}", self.overflow_page)));
+        }
+
+        Ok(())
+    }
+}
+
+fn get_cell_data(file: &mut Pager, page_offset: u64, cell_offset: u16, index_cell: bool) -> Result<(Record, i64)> {
+    get_cell_fields(file, page_offset, cell_offset, index_cell, None)
+}
+
+/// Reads a cell's record, materializing only the fields listed in `wanted` (all of them when
+/// `None`). Skipped fields are not copied and read back as NULL (serial type 0), and overflow
+/// pages past the last wanted field are never loaded. Table cells also give their rowid, which
+/// is stored as a varint holding the full 64-bit two's complement value.
+fn get_cell_fields(file: &mut Pager, page_offset: u64, cell_offset: u16, index_cell: bool, wanted: Option<&[usize]>) -> Result<(Record, i64)> {
+    let absolute_offset = page_offset + cell_offset as u64;
+
+    file.seek(SeekFrom::Start(absolute_offset))?;
+    let (payload_size, payload_size_bytes) = read_varint_from(file)?;
+
+    let mut rowid = 0;
+    let mut total_header_bytes = payload_size_bytes;
+
+    if !index_cell {
+        let (rowid_val, rowid_bytes) = read_varint_from(file)?;
+        rowid = rowid_val as i64;
+        total_header_bytes += rowid_bytes;
+    }
+
+    file.seek(SeekFrom::Start(absolute_offset + total_header_bytes as u64))?;
+
+    let local_size = local_payload_size(file.usable_size, payload_size, index_cell);
+    let mut local = vec![0u8; local_size as usize];
+    file.read_exact(&mut local)
+        .context(format!("Failed to read record ({} bytes) at cell offset {}", local_size, cell_offset))?;
+    let mut first_overflow_page = [0u8; 4];
+    if local_size < payload_size {
+        file.read_exact(&mut first_overflow_page)?;
+    }
+    let page = (page_offset / file.page_size) as u32 + 1;
+    let mut payload = Payload::new(page, local, payload_size as usize, u32::from_be_bytes(first_overflow_page));
+
+    let header_size_bytes = payload.read(file, 0, payload.size.min(9))?;
+    let (header_size, mut header_pos) = read_varint(&header_size_bytes);
+    let header = payload.read(file, 0, header_size as usize)?;
+
+    let mut all_serial_types: Vec<u64> = vec![];
+    while header_pos < header.len() {
+        let (serial_type, bytes) = read_varint(&header[header_pos..]);
+        all_serial_types.push(serial_type);
+        header_pos += bytes;
+    }
+
+    let mut serial_types = Vec::with_capacity(all_serial_types.len());
+    let mut data = Vec::with_capacity(all_serial_types.len());
+    for (col_idx, &serial_type) in all_serial_types.iter().enumerate() {
+        if wanted.is_none_or(|wanted| wanted.contains(&col_idx)) {
+            let Some(offset) = field_offset(&all_serial_types, header_size as usize, col_idx) else {
+                bail!(SqliteError::corrupt(page, format!("record field {} lies past the end of any payload", col_idx)));
+            };
+            serial_types.push(serial_type);
+            data.push(payload.read(file, offset, get_serial_type_size(serial_type))?);
+        } else {
+            serial_types.push(0);
+            data.push(vec![]);
+        }
+    }
+
+    Ok((Record { serial_types, data, strict_text: file.strict_text }, rowid))
+}
+
+fn read_tbl_info(file: &mut Pager, page_num: u32, page_offset: u64, cell_offset: u16) -> Result<TableInfo> {
+    let (record, _) = get_cell_data(file, page_offset, cell_offset, false)?;
+
+    if record.data.len() < 5 {
+        bail!(SqliteError::corrupt(page_num, format!("expected at least 5 columns in sqlite_schema, found {}", record.data.len())));
+    }
+
+    let type_str = extract_string(&record.data[0], record.strict_text)?;
+    let name_str = extract_string(&record.data[1], record.strict_text)?;
+    let tbl_name_str = extract_string(&record.data[2], record.strict_text)?;
+    let rootpage_int = extract_integer(&record.data[3])? as u32;
+    let sql_str = extract_string(&record.data[4], record.strict_text)?;
+
+    let index_col = if type_str == "index" {
+        let index_re = Regex::new(&format!(r"(?i)CREATE\s+INDEX\s+{0}\s+on\s+{0}\s*\(\s*(\w+)\s*\)", IDENTIFIER_PATTERN))?;
+        index_re.captures(&sql_str)
+            .map(|caps| caps[1].to_string())
+    } else {
+        None
+    };
+
+    Ok(TableInfo {
+        tpe: type_str,
+        name: name_str,
+        tbl_name: tbl_name_str,
+        rootpage: rootpage_int,
+        columns: parse_columns(&sql_str)?,
+        sql: sql_str,
+        index_col
+    })
+}
+
+fn schema_table_info() -> TableInfo {
+    TableInfo {
+        tpe: "table".to_string(),
+        name: "sqlite_schema".to_string(),
+        tbl_name: "sqlite_schema".to_string(),
+        rootpage: 1,
+        sql: "CREATE TABLE sqlite_schema(type text, name text, tbl_name text, rootpage integer, sql text)".to_string(),
+        columns: vec![
+            Column::from_strs("type", "text"),
+            Column::from_strs("name", "text"),
+            Column::from_strs("tbl_name", "text"),
+            Column::from_strs("rootpage", "integer"),
+            Column::from_strs("sql", "text"),
+        ],
+        index_col: None
+    }
+}
+
+static SCHEMA_TABLE: LazyLock<TableInfo> = LazyLock::new(schema_table_info);
+
+/// Finds a table by its `name`, or failing that its `tbl_name`, both compared as identifiers.
+/// SQLite keeps the two equal for tables, but a schema written by other tools may not.
+fn find_table<'a>(tables_info: &'a [TableInfo], table_name: &str) -> Option<&'a TableInfo> {
+    if ident_eq(table_name, "sqlite_schema") || ident_eq(table_name, "sqlite_master") {
+        return Some(&SCHEMA_TABLE);
+    }
+
+    let mut tables = tables_info.iter().filter(|t| t.tpe == "table");
+    tables.clone().find(|t| ident_eq(&t.name, table_name))
+        .or_else(|| tables.find(|t| ident_eq(&t.tbl_name, table_name)))
+}
+
+fn find_view<'a>(tables_info: &'a [TableInfo], view_name: &str) -> Option<&'a TableInfo> {
+    tables_info.iter().find(|t| t.tpe == "view" && ident_eq(&t.name, view_name))
+}
+
+/// Reads every row of `sqlite_schema`, the table b-tree rooted at page 1. With many tables
+/// and indexes it grows interior pages like any other table; a page 1 that is not a table
+/// b-tree page is corruption.
+fn get_tables_info(file: &mut Pager, page_size: u32) -> Result<Vec<TableInfo>> {
+    let mut result = vec![];
+    walk_table_btree(file, page_size, 1, |file, page_num| {
+        let page_offset = page_offset(page_size, page_num)?;
+        file.seek(SeekFrom::Start(page_header_offset(page_num, page_offset)))?;
+        let mut page_header = [0; 8];
+        file.read_exact(&mut page_header)
+            .context(format!("Failed to read page header at schema page {}", page_num))?;
+        let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]) as usize;
+
+        let mut cell_array_contents = vec![0u8; n_cells * 2];
+        file.read_exact(&mut cell_array_contents)
+            .context(format!("Failed to read cell array ({} bytes) at schema page {}", n_cells * 2, page_num))?;
+
+        for pointer in cell_array_contents.chunks_exact(2) {
+            let cell_offset = u16::from_be_bytes([pointer[0], pointer[1]]);
+            result.push(read_tbl_info(file, page_num, page_offset, cell_offset)?);
+        }
+        Ok(())
+    })?;
+
+    Ok(result)
+}
+
+fn leaf_cell_count(file: &mut Pager, current_page: u32, page_size: u32) -> Result<u64> {
+    let page_offset = page_offset(page_size, current_page)?;
+
+    file.seek(SeekFrom::Start(page_header_offset(current_page, page_offset)))?;
+    let mut page_header = [0; 8];
+    file.read_exact(&mut page_header)?;
+    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]) as u64;
+
+    Ok(n_cells)
+}
+
+/// Counts the rows of a table b-tree by summing leaf cell counts; every leaf cell is
+/// exactly one row, so no payload needs to be decoded.
+fn count_rows(file: &mut Pager, page_size: u32, root_page: u32) -> Result<u64> {
+    let mut total_count = 0u64;
+    walk_table_btree(file, page_size, root_page, |file, page_num| {
+        total_count += leaf_cell_count(file, page_num, page_size)?;
+        Ok(())
+    })?;
+
+    Ok(total_count)
+}
+
+/// Reads the rowid of every cell on a table leaf page without touching the record payloads.
+fn leaf_rowids(file: &mut Pager, page_num: u32, page_size: u32) -> Result<Vec<i64>> {
+    let page_offset = page_offset(page_size, page_num)?;
+
+    file.seek(SeekFrom::Start(page_header_offset(page_num, page_offset)))?;
+    let mut page_header = [0; 8];
+    file.read_exact(&mut page_header)
+        .context(format!("Failed to read page header at leaf page {}", page_num))?;
+    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]) as usize;
+
+    let mut cell_array_contents = vec![0u8; n_cells * 2];
+    file.read_exact(&mut cell_array_contents)
+        .context(format!("Failed to read cell array ({} bytes) at leaf page {}", n_cells * 2, page_num))?;
+    file.record_rows_scanned(n_cells as u64)?;
+
+    let mut rowids = Vec::with_capacity(n_cells);
+    for pointer in cell_array_contents.chunks_exact(2) {
+        let cell_offset = u16::from_be_bytes([pointer[0], pointer[1]]);
+        file.seek(SeekFrom::Start(page_offset + cell_offset as u64))?;
+        read_varint_from(file)?;
+        rowids.push(read_varint_from(file)?.0 as i64);
+    }
+
+    Ok(rowids)
+}
+
+/// Collects the rowids of a table b-tree in ascending order (the b-tree's key order).
+fn collect_rowids(file: &mut Pager, page_size: u32, root_page: u32) -> Result<Vec<i64>> {
+    let mut rowids = vec![];
+    walk_table_btree(file, page_size, root_page, |file, page_num| {
+        rowids.extend(leaf_rowids(file, page_num, page_size)?);
+        Ok(())
+    })?;
+
+    Ok(rowids)
+}
+
+/// Decodes a record field according to its serial type, which reflects the storage class
+/// actually used for this value regardless of the column's declared type.
+fn decode_value(serial_type: u64, data: &[u8], strict_text: bool) -> Result<Value> {
+    Ok(match serial_type {
+        0 => Value::Null,
+        1..=6 => Value::Integer(extract_integer(data)?),
+        7 => Value::Real(extract_real(data)?),
+        8 => Value::Integer(0),
+        9 => Value::Integer(1),
+        n if n >= 12 && n % 2 == 0 => Value::Blob(data.to_vec()),
+        n if n >= 13 => Value::Text(extract_string(data, strict_text)?),
+        n => bail!(SqliteError::corrupt(None, format!("invalid serial type: {}", n))),
+    })
+}
+
+/// `rowid_alias` is the index of the table's INTEGER PRIMARY KEY column, if any; SQLite
+/// stores NULL in its record slot and the value lives in the cell's rowid instead.
+fn decode_column(record: &Record, rowid: i64, col_idx: usize, rowid_alias: Option<usize>) -> Result<Value> {
+    if rowid_alias == Some(col_idx) {
+        return Ok(Value::Integer(rowid));
+    }
+    if col_idx >= record.data.len() {
+        return Ok(Value::Null);
+    }
+
+    decode_value(record.serial_types[col_idx], &record.data[col_idx], record.strict_text)
+}
+
+fn decode_row(record: &Record, rowid: i64, col_idxs: &[usize], rowid_alias: Option<usize>) -> Result<Vec<Value>> {
+    col_idxs.iter()
+        .map(|col_idx| decode_column(record, rowid, *col_idx, rowid_alias))
+        .collect()
+}
+
+/// SQLite writes integral REAL values as integers on disk and converts them back when read.
+fn apply_real_affinity(rows: &mut [Vec<Value>], col_idxs: &[usize], columns: &[Column]) {
+    for row in rows.iter_mut() {
+        for (value, col_idx) in row.iter_mut().zip(col_idxs) {
+            if let Value::Integer(integer) = value
+                && columns.get(*col_idx).is_some_and(|col| col.tpe == SqlType::Real) {
+                *value = Value::Real(*integer as f64);
+            }
+        }
+    }
+}
+
+fn value_as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(value) => Some(*value as f64),
+        Value::Real(value) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Values of different storage classes sort as NULL < INTEGER/REAL < TEXT < BLOB.
+fn storage_class_rank(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Integer(_) | Value::Real(_) => 1,
+        Value::Text(_) => 2,
+        Value::Blob(_) => 3,
+    }
+}
+
+/// Compares two values the way SQLite does, ordering text under the given collation.
+/// Integers compare exactly among themselves and are promoted to f64 against reals.
+/// The RTRIM collation is not supported yet.
+fn compare_values(a: &Value, b: &Value, collation: Collation) -> Ordering {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        (Value::Integer(_) | Value::Real(_), Value::Integer(_) | Value::Real(_)) => {
+            let (a, b) = (value_as_f64(a).unwrap_or(0.0), value_as_f64(b).unwrap_or(0.0));
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        }
+        (Value::Text(a), Value::Text(b)) => collation.compare_text(a, b),
+        (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+        _ => storage_class_rank(a).cmp(&storage_class_rank(b)),
+    }
+}
+
+/// Orders values as SQLite does under the BINARY collation, so sorting a column that mixes
+/// storage classes is deterministic. Integers and reals compare numerically, which means
+/// `Integer(1)` and `Real(1.0)` are unequal under `==` but compare as `Ordering::Equal`.
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(compare_values(self, other, Collation::Binary))
+    }
+}
+
+impl Filter {
+    fn matches(&self, value: &Value) -> bool {
+        *value != Value::Null && compare_values(value, &self.value, self.collation).is_eq()
+    }
+
+    /// Finds the `column = literal` terms (in either order) among the predicate's AND-ed
+    /// conditions, any of which can drive an index lookup.
+    fn from_predicate(predicate: &Expr) -> Vec<(usize, Filter)> {
+        match predicate {
+            Expr::And(lhs, rhs) => {
+                let mut filters = Filter::from_predicate(lhs);
+                filters.extend(Filter::from_predicate(rhs));
+                filters
+            }
+            Expr::Compare(CompareOp::Eq, lhs, rhs, collation) => match (lhs.as_ref(), rhs.as_ref()) {
+                (Expr::Column(idx), Expr::Literal(value)) | (Expr::Literal(value), Expr::Column(idx)) => {
+                    vec![(*idx, Filter { collation: *collation, value: value.clone() })]
+                }
+                _ => vec![],
+            },
+            _ => vec![],
+        }
+    }
+}
+
+/// Evaluates a WHERE predicate against a full row; NULL and false results both reject the row.
+fn row_matches(predicate: &Expr, row: &[Value]) -> Result<bool> {
+    Ok(is_truthy(&evaluate(predicate, row)?))
+}
+
+fn filter_rows(rows: Vec<Vec<Value>>, predicate: &Expr) -> Result<Vec<Vec<Value>>> {
+    let mut matching = Vec::with_capacity(rows.len());
+    for row in rows {
+        if row_matches(predicate, &row)? {
+            matching.push(row);
+        }
+    }
+
+    Ok(matching)
+}
+
+fn get_page_data_with_filter(file: &mut Pager, tinfo: &TableInfo, col_idxs: &[usize], page_size: u32, page_num: u32, predicate: Option<&Expr>) -> Result<Vec<Vec<Value>>> {
+    let page_offset = page_offset(page_size, page_num)?;
+
+    file.seek(SeekFrom::Start(page_header_offset(page_num, page_offset)))?;
+    let mut page_header = [0; 8];
+    file.read_exact(&mut page_header)
+        .context(format!("Failed to read page header at leaf page {}", page_num))?;
+    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]) as u64;
+
+    let n_bytes = (n_cells * 2) as usize;
+    let mut cell_array_contents = vec![0u8; n_bytes];
+    file.read_exact(&mut cell_array_contents)
+        .context(format!("Failed to read cell array ({} bytes) at leaf page {}", n_bytes, page_num))?;
+
+    let all_col_idxs = tinfo.full_row_idxs();
+    let mut results: Vec<Vec<Value>> = vec![];
+    for i in (0..n_bytes).step_by(2) {
+        let cell_offset = u16::from_be_bytes([cell_array_contents[i], cell_array_contents[i + 1]]);
+        let (record, rowid) = get_cell_fields(file, page_offset, cell_offset, false, Some(col_idxs))?;
+        file.record_rows_scanned(1)?;
+
+        let mut row = decode_row(&record, rowid, &all_col_idxs, Some(tinfo.rowid_column()))?;
+        apply_real_affinity(std::slice::from_mut(&mut row), &all_col_idxs, &tinfo.columns);
+        if let Some(predicate) = predicate && !row_matches(predicate, &row)? {
+            continue;
+        }
+
+        results.push(row);
+    }
+
+    Ok(results)
+}
+
+fn extract_interior_cell_data(file: &mut Pager, page_offset: u64, cell_offset: u16, index_cell: bool) -> Result<(u32, u64)> {
+    let absolute_offset = page_offset + cell_offset as u64;
+    file.seek(SeekFrom::Start(absolute_offset))?;
+
+    let mut left_page_buf = [0; 4];
+    file.read_exact(&mut left_page_buf)
+        .context(format!("Failed to read left page pointer at cell offset {} (absolute: {})", cell_offset, absolute_offset))?;
+
+    if !index_cell {
+        return Ok((u32::from_be_bytes(left_page_buf), read_varint_from(file)?.0));
+    }
+
+    Ok((u32::from_be_bytes(left_page_buf), 0u64))
+}
+
+fn read_page_type(file: &mut Pager, page_num: u32, page_size: u32) -> Result<PageType> {
+    if file.is_pointer_map_page(page_num) {
+        bail!(SqliteError::corrupt(page_num, format!("page {} is a pointer-map page, not a b-tree page", page_num)));
+    }
+    let page_offset = page_offset(page_size, page_num)?;
+    file.seek(SeekFrom::Start(page_header_offset(page_num, page_offset)))?;
+
+    let mut page_type_buf = [0; 1];
+    file.read_exact(&mut page_type_buf)
+        .context(format!("Failed to read page type at page {}", page_num))?;
+    PageType::from_u8(page_type_buf[0], page_num)
+}
+
+fn get_child_pages(file: &mut Pager, page_num: u32, page_size: u32, index_page: bool) -> Result<Vec<u32>> {
+    let page_offset = page_offset(page_size, page_num)?;
+    file.seek(SeekFrom::Start(page_header_offset(page_num, page_offset)))?;
+
+    let mut page_header = [0; 12];
+    file.read_exact(&mut page_header)
+        .context(format!("Failed to read page header at interior page {}", page_num))?;
+    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]);
+
+    let n_bytes = n_cells as usize * 2;
+    let mut cell_array_contents = vec![0u8; n_bytes];
+    file.read_exact(&mut cell_array_contents)
+        .context(format!("Failed to read cell array ({} bytes) at interior page {}", n_bytes, page_num))?;
+
+    let mut children = Vec::with_capacity(n_cells as usize + 1);
+    for i in (0..n_bytes).step_by(2) {
+        let cell_offset = u16::from_be_bytes([cell_array_contents[i], cell_array_contents[i + 1]]);
+        let (left_page, _) = extract_interior_cell_data(file, page_offset, cell_offset, index_page)?;
+        children.push(left_page);
+    }
+    children.push(u32::from_be_bytes([page_header[8], page_header[9], page_header[10], page_header[11]]));
+
+    Ok(children)
+}
+
+/// Visits every page of the table or index b-tree at `page_num`, each before its children,
+/// failing on page cycles and on pages of the other kind of b-tree.
+fn walk_btree<F>(file: &mut Pager, page_size: u32, page_num: u32, index_tree: bool, visited: &mut HashSet<u32>, visit: &mut F) -> Result<()>
+where
+    F: FnMut(&mut Pager, u32, PageType) -> Result<()>,
+{
+    mark_visited(visited, page_num)?;
+
+    let page_type = read_page_type(file, page_num, page_size)?;
+    match (page_type, index_tree) {
+        (PageType::InteriorTable, false) | (PageType::InteriorIndex, true) => {
+            visit(file, page_num, page_type)?;
+            for child_page in get_child_pages(file, page_num, page_size, index_tree)? {
+                walk_btree(file, page_size, child_page, index_tree, visited, visit)?;
+            }
+            Ok(())
+        }
+        (PageType::LeafTable, false) | (PageType::LeafIndex, true) => visit(file, page_num, page_type),
+        _ => bail!(SqliteError::corrupt(page_num, format!("page {}: unexpected page type {:?} in {} b-tree",
+            page_num, page_type, if index_tree { "an index" } else { "a table" }))),
+    }
+}
+
+/// Visits every leaf page of the table b-tree rooted at `root_page`, failing on page cycles.
+fn walk_table_btree<F>(file: &mut Pager, page_size: u32, root_page: u32, mut visit_leaf: F) -> Result<()>
+where
+    F: FnMut(&mut Pager, u32) -> Result<()>,
+{
+    walk_btree(file, page_size, root_page, false, &mut HashSet::new(), &mut |file, page_num, page_type| {
+        match page_type {
+            PageType::LeafTable => visit_leaf(file, page_num),
+            _ => Ok(()),
+        }
+    })
+}
+
+/// Like `walk_table_btree`, but only descends into subtrees that can hold rowids in `min..=max`.
+fn walk_table_btree_range<F>(file: &mut Pager, page_size: u32, page_num: u32, min: i64, max: i64, visited: &mut HashSet<u32>, visit_leaf: &mut F) -> Result<()>
+where
+    F: FnMut(&mut Pager, u32) -> Result<()>,
+{
+    mark_visited(visited, page_num)?;
+
+    match read_page_type(file, page_num, page_size)? {
+        PageType::LeafTable => visit_leaf(file, page_num),
+        PageType::InteriorTable => {
+            for child_page in table_children_in_range(file, page_size, page_num, min, max)? {
+                walk_table_btree_range(file, page_size, child_page, min, max, visited, visit_leaf)?;
+            }
+            Ok(())
+        }
+        page_type => bail!(SqliteError::corrupt(page_num, format!("unexpected page type {:?}", page_type))),
+    }
+}
+
+/// The children of an interior table page that can hold rowids in `min..=max`, in rowid order.
+/// Each interior cell's key is the largest rowid in its left child, so a child covers the rowids
+/// above the previous cell's key up to its own key.
+fn table_children_in_range(file: &mut Pager, page_size: u32, page_num: u32, min: i64, max: i64) -> Result<Vec<u32>> {
+    let page_offset = page_offset(page_size, page_num)?;
+    file.seek(SeekFrom::Start(page_header_offset(page_num, page_offset)))?;
+    let mut page_header = [0; 12];
+    file.read_exact(&mut page_header)
+        .context(format!("Failed to read page header at interior page {}", page_num))?;
+    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]) as usize;
+    let right_most = u32::from_be_bytes([page_header[8], page_header[9], page_header[10], page_header[11]]);
+
+    let mut cell_array_contents = vec![0u8; n_cells * 2];
+    file.read_exact(&mut cell_array_contents)
+        .context(format!("Failed to read cell array ({} bytes) at interior page {}", n_cells * 2, page_num))?;
+
+    let mut cells = Vec::with_capacity(n_cells);
+    for pointer in cell_array_contents.chunks_exact(2) {
+        let cell_offset = u16::from_be_bytes([pointer[0], pointer[1]]);
+        cells.push(extract_interior_cell_data(file, page_offset, cell_offset, false)?);
+    }
+
+    let mut children = vec![];
+    let mut lower_key: Option<i64> = None;
+    for (child_page, key) in cells {
+        let key = key as i64;
+        if key >= min && lower_key.is_none_or(|lower| lower < max) {
+            children.push(child_page);
+        }
+        if key >= max {
+            return Ok(children);
+        }
+        lower_key = Some(key);
+    }
+    children.push(right_most);
+
+    Ok(children)
+}
+
+/// Checks that every cell pointer on a b-tree page lands between the end of the cell pointer
+/// array (and the start of the cell content area) and the end of the usable page.
+fn check_cell_pointers(file: &mut Pager, page_num: u32, page_size: u32, page_type: PageType) -> Result<()> {
+    let page_offset = page_offset(page_size, page_num)?;
+    let header_offset = page_header_offset(page_num, page_offset);
+    let header_size = match page_type {
+        PageType::InteriorTable | PageType::InteriorIndex => 12,
+        PageType::LeafTable | PageType::LeafIndex => 8,
+    };
+
+    file.seek(SeekFrom::Start(header_offset))?;
+    let mut page_header = vec![0; header_size];
+    file.read_exact(&mut page_header)
+        .context(format!("Failed to read page header at page {}", page_num))?;
+    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]) as u64;
+    // A content area start of 0 stands for 65536.
+    let content_start = match u16::from_be_bytes([page_header[5], page_header[6]]) {
+        0 => 65536,
+        start => start as u64,
+    };
+
+    let pointers_end = header_offset - page_offset + header_size as u64 + n_cells * 2;
+    if pointers_end > file.usable_size {
+        bail!(SqliteError::corrupt(page_num, format!("page {}: {} cells do not fit in the page", page_num, n_cells)));
+    }
+    let mut cell_array_contents = vec![0u8; n_cells as usize * 2];
+    file.read_exact(&mut cell_array_contents)
+        .context(format!("Failed to read cell array ({} bytes) at page {}", n_cells * 2, page_num))?;
+
+    for (i, pointer) in cell_array_contents.chunks_exact(2).enumerate() {
+        let cell_offset = u16::from_be_bytes([pointer[0], pointer[1]]) as u64;
+        if cell_offset < pointers_end.max(content_start) || cell_offset >= file.usable_size {
+            bail!(SqliteError::corrupt(page_num, format!(
+                "page {}: cell {} at offset {} is outside the cell content area {}..{}",
+                page_num, i, cell_offset, pointers_end.max(content_start), file.usable_size)));
+        }
+    }
+
+    Ok(())
+}
+
+/// State carried through `check_btree`: every page reached so far, across all b-trees, and
+/// the last rowid seen in the table b-tree being checked.
+struct IntegrityCheck {
+    visited: HashSet<u32>,
+    last_rowid: Option<i64>,
+}
+
+/// Walks one b-tree checking its page types and cell pointers, that it has no cycles and
+/// shares no page with another b-tree and, for table b-trees, that the rowids strictly
+/// increase from leaf to leaf.
+fn check_btree(file: &mut Pager, page_size: u32, root_page: u32, index_tree: bool, check: &mut IntegrityCheck) -> Result<()> {
+    walk_btree(file, page_size, root_page, index_tree, &mut HashSet::new(), &mut |file, page_num, page_type| {
+        if !check.visited.insert(page_num) {
+            bail!(SqliteError::corrupt(page_num, format!("page {} is referenced more than once", page_num)));
+        }
+        check_cell_pointers(file, page_num, page_size, page_type)?;
+        if page_type == PageType::LeafTable {
+            for rowid in leaf_rowids(file, page_num, page_size)? {
+                if let Some(last_rowid) = check.last_rowid.filter(|&last_rowid| last_rowid >= rowid) {
+                    bail!(SqliteError::corrupt(page_num, format!("page {}: rowid {} out of order (follows {})", page_num, rowid, last_rowid)));
+                }
+                check.last_rowid = Some(rowid);
+            }
+        }
+        Ok(())
+    })
+}
+
+fn check_integrity(file: &mut Pager, page_size: u32, tables_info: &[TableInfo]) -> Result<()> {
+    let without_rowid_re = Regex::new(r"(?i)\bWITHOUT\s+ROWID\b")?;
+    let trees = std::iter::once((1, false))
+        .chain(tables_info.iter()
+            .filter(|t| t.rootpage != 0)
+            .map(|t| (t.rootpage, t.tpe == "index" || without_rowid_re.is_match(&t.sql))));
+
+    let mut check = IntegrityCheck { visited: HashSet::new(), last_rowid: None };
+    for (root_page, index_tree) in trees {
+        check.last_rowid = None;
+        check_btree(file, page_size, root_page, index_tree, &mut check)?;
+    }
+
+    Ok(())
+}
+
+/// Inclusive rowid bounds implied by a single comparison `rowid <op> value`.
+fn rowid_bounds_for(op: CompareOp, value: &Value) -> Option<(i64, i64)> {
+    let (floor, ceil) = match value {
+        Value::Integer(value) => (*value, *value),
+        Value::Real(value) => (value.floor() as i64, value.ceil() as i64),
+        _ => return None,
+    };
+
+    match op {
+        CompareOp::Eq if floor == ceil => Some((floor, floor)),
+        CompareOp::Eq => Some((1, 0)),
+        CompareOp::Gt => Some((floor.saturating_add(1), i64::MAX)),
+        CompareOp::Ge => Some((ceil, i64::MAX)),
+        CompareOp::Lt => Some((i64::MIN, ceil.saturating_sub(1))),
+        CompareOp::Le => Some((i64::MIN, floor)),
+        CompareOp::NotEq | CompareOp::IsNot => None,
+        CompareOp::Is => rowid_bounds_for(CompareOp::Eq, value),
+    }
+}
+
+/// Rowid bounds a row must satisfy for `predicate` to hold, used to prune the table b-tree.
+/// The predicate is still evaluated on every row that is visited.
+fn rowid_bounds(predicate: &Expr, rowid_col: usize) -> Option<(i64, i64)> {
+    match predicate {
+        Expr::Compare(op, lhs, rhs, _) => match (lhs.as_ref(), rhs.as_ref()) {
+            (Expr::Column(idx), Expr::Literal(value)) if *idx == rowid_col => rowid_bounds_for(*op, value),
+            (Expr::Literal(value), Expr::Column(idx)) if *idx == rowid_col => {
+                let op = match op {
+                    CompareOp::Gt => CompareOp::Lt,
+                    CompareOp::Ge => CompareOp::Le,
+                    CompareOp::Lt => CompareOp::Gt,
+                    CompareOp::Le => CompareOp::Ge,
+                    op => *op,
+                };
+                rowid_bounds_for(op, value)
+            }
+            _ => None,
+        },
+        Expr::And(lhs, rhs) => match (rowid_bounds(lhs, rowid_col), rowid_bounds(rhs, rowid_col)) {
+            (Some((lhs_min, lhs_max)), Some((rhs_min, rhs_max))) => Some((lhs_min.max(rhs_min), lhs_max.min(rhs_max))),
+            (bounds, None) | (None, bounds) => bounds,
+        },
+        Expr::Or(lhs, rhs) => {
+            let (lhs_min, lhs_max) = rowid_bounds(lhs, rowid_col)?;
+            let (rhs_min, rhs_max) = rowid_bounds(rhs, rowid_col)?;
+            Some((lhs_min.min(rhs_min), lhs_max.max(rhs_max)))
+        }
+        _ => None,
+    }
+}
+
+/// Scans the table for rows matching `predicate`. Only the record fields in `col_idxs` are
+/// materialized; rows still span every column (see `TableInfo::full_row_idxs`), with NULL in
+/// the positions that were skipped.
+fn get_cols_data_with_filter(file: &mut Pager, page_size: u32, tinfo: &TableInfo, col_idxs: &[usize], predicate: Option<&Expr>) -> Result<Vec<Vec<Value>>> {
+    let (min, max) = predicate
+        .and_then(|predicate| rowid_bounds(predicate, tinfo.rowid_column()))
+        .unwrap_or((i64::MIN, i64::MAX));
+    if min > max {
+        return Ok(vec![]);
+    }
+
+    let mut rows = vec![];
+    walk_table_btree_range(file, page_size, tinfo.rootpage, min, max, &mut HashSet::new(), &mut |file, page_num| {
+        rows.extend(get_page_data_with_filter(file, tinfo, col_idxs, page_size, page_num, predicate)?);
+        Ok(())
+    })?;
+
+    Ok(rows)
+}
+
+fn find_child_page_for_rowid(file: &mut Pager, page_offset: u64, header_offset: u64, rowid: i64) -> Result<u32> {
+    file.seek(SeekFrom::Start(header_offset))?;
+    let mut page_header = [0; 12];
+    file.read_exact(&mut page_header)?;
+    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]) as usize;
+
+    let n_bytes = n_cells * 2;
+    let mut cell_array_contents = vec![0u8; n_bytes];
+    file.read_exact(&mut cell_array_contents)?;
+
+    let mut i = 0;
+    while i < n_bytes {
+        let cell_offset = u16::from_be_bytes([cell_array_contents[i], cell_array_contents[i + 1]]);
+        let (left_child, cell_key) = extract_interior_cell_data(file, page_offset, cell_offset, false)?;
+        // Rowids are signed; as varints negative ones would otherwise sort after the rest.
+        if rowid <= cell_key as i64 {
+            return Ok(left_child);
+        }
+        i += 2
+    }
+
+    Ok(u32::from_be_bytes([page_header[8], page_header[9], page_header[10], page_header[11]]))
+}
+
+fn find_row_in_leaf(file: &mut Pager, page_offset: u64, header_offset: u64, tinfo: &TableInfo, col_idxs: &[usize], rowid: i64) -> Result<Option<Vec<Value>>> {
+    file.seek(SeekFrom::Start(header_offset))?;
+    let mut page_header = [0; 8];
+    file.read_exact(&mut page_header)?;
+    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]) as u64;
+
+    let n_bytes = (n_cells * 2) as usize;
+    let mut cell_array_contents = vec![0u8; n_bytes];
+    file.read_exact(&mut cell_array_contents)?;
+
+    for i in (0..n_bytes).step_by(2) {
+        let cell_offset = u16::from_be_bytes([cell_array_contents[i], cell_array_contents[i + 1]]);
+        file.seek(SeekFrom::Start(page_offset + cell_offset as u64))?;
+        read_varint_from(file)?;
+        let (cell_rowid, _) = read_varint_from(file)?;
+        file.record_rows_scanned(1)?;
+
+        if cell_rowid as i64 == rowid {
+            let (record, _) = get_cell_fields(file, page_offset, cell_offset, false, Some(col_idxs))?;
+            return Ok(Some(decode_row(&record, rowid, &tinfo.full_row_idxs(), Some(tinfo.rowid_column()))?));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Looks `rowid` up in the table b-tree at `curr_page`, descending into a single child per level.
+fn find_row_by_rowid(file: &mut Pager, curr_page: u32, page_size: u32, tinfo: &TableInfo, col_idxs: &[usize], rowid: i64, visited: &mut HashSet<u32>) -> Result<Option<Vec<Value>>> {
+    mark_visited(visited, curr_page)?;
+    let page_offset = page_offset(page_size, curr_page)?;
+    let header_offset = page_header_offset(curr_page, page_offset);
+
+    match read_page_type(file, curr_page, page_size)? {
+        PageType::InteriorTable => {
+            let child_page = find_child_page_for_rowid(file, page_offset, header_offset, rowid)?;
+            find_row_by_rowid(file, child_page, page_size, tinfo, col_idxs, rowid, visited)
+        }
+        PageType::LeafTable => {
+            find_row_in_leaf(file, page_offset, header_offset, tinfo, col_idxs, rowid)
+        }
+        page_type => bail!(SqliteError::corrupt(curr_page, format!("unexpected page type {:?} in table b-tree", page_type))),
+    }
+}
+
+/// The row of a table with the given rowid, as `SELECT *` gives it. Only the b-tree pages on
+/// the path to the row are read.
+fn find_full_row(file: &mut Pager, page_size: u32, tinfo: &TableInfo, rowid: i64) -> Result<Option<Vec<Value>>> {
+    let col_idxs = tinfo.full_row_idxs();
+    let Some(mut row) = find_row_by_rowid(file, tinfo.rootpage, page_size, tinfo, &col_idxs, rowid, &mut HashSet::new())? else {
+        return Ok(None);
+    };
+    row.truncate(tinfo.columns.len());
+    apply_real_affinity(std::slice::from_mut(&mut row), &col_idxs, &tinfo.columns);
+
+    Ok(Some(row))
+}
+
+fn get_rows_by_rowids(file: &mut Pager, page_size: u32, col_idxs: &[usize], rowids: &[i64], tinfo: &TableInfo) -> Result<Vec<Vec<Value>>> {
+    let mut results = Vec::new();
+
+    for &rowid in rowids {
+        let row = find_row_by_rowid(file, tinfo.rootpage, page_size, tinfo, col_idxs, rowid, &mut HashSet::new())?
+            .ok_or_else(|| SqliteError::corrupt(tinfo.rootpage, format!("index entry for missing rowid {}", rowid)))?;
+        results.push(row);
+    }
+
+    Ok(results)
+}
+
+/// Reads the key and rowid of every cell on an index page, in key order.
+fn read_index_entries(file: &mut Pager, index_curr_page: u32, page_size: u32, page_type: PageType) -> Result<Vec<(Value, i64)>> {
+    let page_offset = page_offset(page_size, index_curr_page)?;
+    let (header_size, cell_skip) = match page_type {
+        PageType::InteriorIndex => (12, 4),
+        _ => (8, 0),
+    };
+
+    file.seek(SeekFrom::Start(page_offset))?;
+    let mut page_header = vec![0; header_size];
+    file.read_exact(&mut page_header)
+        .context(format!("Failed to read page header at index page {}", index_curr_page))?;
+    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]) as u64;
+
+    let n_bytes = (n_cells * 2) as usize;
+    let mut cell_array_contents = vec![0u8; n_bytes];
+    file.read_exact(&mut cell_array_contents)
+        .context(format!("Failed to read cell array ({} bytes) at index page {}", n_bytes, index_curr_page))?;
+
+    let mut entries = Vec::with_capacity(n_cells as usize);
+    for i in (0..n_bytes).step_by(2) {
+        let cell_offset = u16::from_be_bytes([cell_array_contents[i], cell_array_contents[i + 1]]);
+        let Some(cell_offset) = cell_offset.checked_add(cell_skip) else {
+            bail!(SqliteError::corrupt(index_curr_page, format!("cell offset {} is past the end of the page", cell_offset)));
+        };
+        let (record, _) = get_cell_data(file, page_offset, cell_offset, true)?;
+        file.record_rows_scanned(1)?;
+        // An index entry holds the indexed value followed by the rowid.
+        if record.data.len() < 2 {
+            bail!(SqliteError::corrupt(index_curr_page, format!("index entry with {} fields", record.data.len())));
+        }
+        let value = decode_value(record.serial_types[0], &record.data[0], record.strict_text)?;
+        // The rowid is the record's last field; like any integer it may use serial type 8 or 9.
+        let rowid = match record.serial_types.last().zip(record.data.last()) {
+            Some((&serial_type, data)) => decode_value(serial_type, data, record.strict_text)?,
+            None => Value::Null,
+        };
+        let Value::Integer(rowid) = rowid else {
+            bail!(SqliteError::corrupt(index_curr_page, format!("index entry without an integer rowid: {:?}", rowid)));
+        };
+        entries.push((value, rowid));
+    }
+
+    Ok(entries)
+}
+
+/// Visits the entries of the index b-tree rooted at `root_page` in key order, failing on page
+/// cycles. `descend` is asked whether to enter the child before an interior entry, given that
+/// entry's key, or the right-most child, given `None`; `visit` gets each entry's key and rowid
+/// and returns whether to go on.
+fn walk_index_btree<D, V>(file: &mut Pager, page_size: u32, root_page: u32, mut descend: D, mut visit: V) -> Result<()>
+where
+    D: FnMut(Option<&Value>) -> bool,
+    V: FnMut(&Value, i64) -> bool,
+{
+    walk_index_entries(file, page_size, root_page, &mut HashSet::new(), &mut descend, &mut visit)?;
+    Ok(())
+}
+
+/// The recursion behind `walk_index_btree`; returns `false` once `visit` has stopped the walk.
+fn walk_index_entries<D, V>(file: &mut Pager, page_size: u32, page_num: u32, visited: &mut HashSet<u32>, descend: &mut D, visit: &mut V) -> Result<bool>
+where
+    D: FnMut(Option<&Value>) -> bool,
+    V: FnMut(&Value, i64) -> bool,
+{
+    mark_visited(visited, page_num)?;
+
+    let page_type = read_page_type(file, page_num, page_size)?;
+    let entries = read_index_entries(file, page_num, page_size, page_type)?;
+    let children = match page_type {
+        PageType::InteriorIndex => get_child_pages(file, page_num, page_size, true)?,
+        PageType::LeafIndex => vec![],
+        _ => bail!(SqliteError::corrupt(page_num, format!("page {}: unexpected page type {:?} in an index b-tree", page_num, page_type))),
+    };
+
+    for (i, (key, rowid)) in entries.iter().enumerate() {
+        if let Some(&child) = children.get(i) && descend(Some(key)) && !walk_index_entries(file, page_size, child, visited, descend, visit)? {
+            return Ok(false);
+        }
+        if !visit(key, *rowid) {
+            return Ok(false);
+        }
+    }
+    match children.last() {
+        Some(&right_child) if descend(None) => walk_index_entries(file, page_size, right_child, visited, descend, visit),
+        _ => Ok(true),
+    }
+}
+
+/// Collects, in index order, the rowids of the entries in the index b-tree at `index_rootpage`
+/// whose key matches `filter`. The child before an interior cell only holds keys up to the
+/// cell's key and the right-most child keys from the last cell's key on, so only the subtrees
+/// whose range takes in the filter value are searched.
+fn get_rowids_index(file: &mut Pager, index_rootpage: u32, page_size: u32, filter: &Filter) -> Result<Vec<i64>> {
+    let mut rowids: Vec<i64> = vec![];
+    walk_index_btree(file, page_size, index_rootpage,
+        |key| key.is_none_or(|key| compare_values(&filter.value, key, filter.collation).is_le()),
+        |key, rowid| {
+            if filter.matches(key) {
+                rowids.push(rowid);
+            }
+            compare_values(&filter.value, key, filter.collation).is_ge()
+        })?;
+
+    Ok(rowids)
+}
+
+fn get_cols_data_with_index(file: &mut Pager, tinfo: &TableInfo, page_size: u32, col_idxs: &[usize], index_rootpage: u32, filter: &Filter) -> Result<Vec<Vec<Value>>> {
+    let rowids = get_rowids_index(file, index_rootpage, page_size, filter)?;
+
+    get_rows_by_rowids(file, page_size, col_idxs, &rowids, tinfo)
+}
+
+struct DatabaseFile {
+    file: Pager,
+    page_size: u32,
+    tables_info: Vec<TableInfo>,
+}
+
+impl DatabaseFile {
+    fn open(path: &str) -> Result<Self> {
+        DatabaseFile::new(Pager::open(path)?)
+    }
+
+    fn new(mut file: Pager) -> Result<Self> {
+        let page_size = file.page_size as u32;
+        let tables_info = get_tables_info(&mut file, page_size)?;
+
+        Ok(DatabaseFile { file, page_size, tables_info })
+    }
+}
+
+pub struct Database {
+    main: DatabaseFile,
+    attached: Vec<(String, DatabaseFile)>,
+}
+
+impl Database {
+    pub fn open(path: &str) -> Result<Self, SqliteError> {
+        Ok(Database { main: DatabaseFile::open(path)?, attached: vec![] })
+    }
+
+    /// Opens a database image held in memory, such as the bytes of a `.db` file.
+    pub fn open_bytes(bytes: Vec<u8>) -> Result<Self, SqliteError> {
+        Ok(Database { main: DatabaseFile::new(Pager::from_bytes(bytes)?)?, attached: vec![] })
+    }
+
+    /// Opens another database file whose tables can then be queried as `schema_name.table`.
+    pub fn attach(&mut self, schema_name: &str, path: &str) -> Result<(), SqliteError> {
+        if ident_eq(schema_name, "main") || self.attached.iter().any(|(name, _)| ident_eq(name, schema_name)) {
+            return Err(SqliteError::Misuse(format!("database {} is already in use", schema_name)));
+        }
+
+        let mut db_file = DatabaseFile::open(path)?;
+        db_file.file.max_rows_scanned = self.main.file.max_rows_scanned;
+        db_file.file.strict_text = self.main.file.strict_text;
+        self.attached.push((schema_name.to_string(), db_file));
+
+        Ok(())
+    }
+
+    pub fn page_size(&self) -> u32 {
+        self.main.page_size
+    }
+
+    pub fn auto_vacuum(&self) -> AutoVacuum {
+        self.main.file.auto_vacuum
+    }
+
+    /// The largest root b-tree page from the header, which auto-vacuum keeps so pages past it
+    /// can be relocated; zero when auto-vacuum is off.
+    pub fn largest_root_page(&self) -> u32 {
+        self.main.file.largest_root_page
+    }
+
+    pub fn incremental_vacuum(&self) -> bool {
+        self.main.file.incremental_vacuum
+    }
+
+    pub fn schema_entry_count(&self) -> usize {
+        self.main.tables_info.len()
+    }
+
+    /// Names of the user tables and views. SQLite's own tables, such as `sqlite_sequence` and the
+    /// `sqlite_stat1` written by ANALYZE, are left out as in the shell's `.tables`.
+    pub fn table_names(&self) -> Vec<&str> {
+        self.main.tables_info.iter()
+            .filter(|t| (t.tpe == "table" || t.tpe == "view") && !is_internal_table(&t.tbl_name))
+            .map(|t| t.tbl_name.as_str())
+            .collect()
+    }
+
+    /// Columns of a table in the main database, in declaration order.
+    pub fn columns(&self, table_name: &str) -> Result<Vec<ColumnInfo>, SqliteError> {
+        let tinfo = find_table(&self.main.tables_info, table_name)
+            .ok_or_else(|| SqliteError::NoSuchTable(table_name.to_string()))?;
+
+        Ok(tinfo.columns.iter()
+            .map(|col| ColumnInfo { name: col.name.clone(), affinity: col.tpe })
+            .collect())
+    }
+
+    pub fn schema_objects(&self) -> Vec<SchemaObject<'_>> {
+        self.main.tables_info.iter()
+            .map(|t| SchemaObject { kind: &t.tpe, name: &t.name, sql: &t.sql })
+            .collect()
+    }
+
+    /// Returns the raw bytes of a page (1-based), as seen through the WAL if there is one.
+    /// Page 1 includes the 100-byte database header. A page number past the end of the
+    /// database is a `Misuse` error.
+    pub fn read_page(&mut self, page_num: u32) -> Result<Vec<u8>, SqliteError> {
+        if page_num == 0 {
+            return Err(SqliteError::Misuse("page numbers start at 1".to_string()));
+        }
+        let file = &mut self.main.file;
+        let page_count = file.page_count()?;
+        if page_num > page_count {
+            return Err(SqliteError::Misuse(format!("page {} is past the end of the database, which has {} pages", page_num, page_count)));
+        }
+
+        let mut page = vec![0u8; file.page_size as usize];
+        file.seek(SeekFrom::Start((page_num - 1) as u64 * file.page_size))?;
+        file.read_exact(&mut page)
+            .context(format!("Failed to read page {}", page_num))?;
+
+        Ok(page)
+    }
+
+    pub fn wal_commit_frame(&self) -> Option<u32> {
+        self.main.file.wal_commit_frame()
+    }
+
+    /// Caps the number of rows a single query may visit; exceeding it fails the query.
+    /// `SELECT COUNT(*)` without a WHERE clause only reads leaf cell counts, visiting no rows,
+    /// so it is not limited.
+    pub fn set_max_rows_scanned(&mut self, limit: Option<u64>) {
+        self.main.file.max_rows_scanned = limit;
+        for (_, db_file) in &mut self.attached {
+            db_file.file.max_rows_scanned = limit;
+        }
+    }
+
+    /// Makes text values that are not valid UTF-8 fail the query with `Corrupt` instead of
+    /// having the invalid bytes replaced with U+FFFD, the default.
+    pub fn set_strict_text(&mut self, strict: bool) {
+        self.main.file.strict_text = strict;
+        for (_, db_file) in &mut self.attached {
+            db_file.file.strict_text = strict;
+        }
+    }
+
+    /// Runs `sql`. The rows borrow the database until they are dropped; see `Rows` for which
+    /// queries read them as they are iterated.
+    pub fn query(&mut self, sql: &str) -> Result<Rows<'_>, SqliteError> {
+        self.main.file.reset_counters();
+        for (_, db_file) in &mut self.attached {
+            db_file.file.reset_counters();
+        }
+
+        Ok(query_rows(self, &strip_comments(sql))?)
+    }
+
+    /// Runs `sql` with `params` bound to its `?` placeholders, in order (`?NNN` names a
+    /// parameter by number). A bound value is used as a value wherever it appears, so text bound
+    /// to the right of `LIKE` or `GLOB` is a pattern with its wildcards active.
+    pub fn query_params(&mut self, sql: &str, params: &[Value]) -> Result<Rows<'_>, SqliteError> {
+        let sql = bind_params(&strip_comments(sql), params)?;
+        self.query(&sql)
+    }
+
+    /// Runs `sql` and returns each row as a map from result column name (alias if given) to
+    /// value. When several columns share a name, the rightmost one wins.
+    pub fn query_maps(&mut self, sql: &str) -> Result<Vec<HashMap<String, Value>>, SqliteError> {
+        let rows = self.query(sql)?;
+        let column_names = rows.column_names().to_vec();
+        rows.map(|row| Ok(column_names.iter().cloned().zip(row?).collect()))
+            .collect()
+    }
+
+    /// Runs `sql` and writes the result to `out` as CSV, one line per row, preceded by a line of
+    /// column names if `headers` is set. NULL is written as an empty field. Each row is written
+    /// as the query produces it; see `Rows` for which queries must read every row first. An
+    /// error partway through leaves the lines written so far in `out`.
+    pub fn query_to_csv(&mut self, sql: &str, out: &mut dyn Write, headers: bool) -> Result<(), SqliteError> {
+        let rows = self.query(sql)?;
+        if headers {
+            let header: Vec<String> = rows.column_names().iter().map(|name| csv_field(name, ",")).collect();
+            writeln!(out, "{}", header.join(","))?;
+        }
+        for row in rows {
+            let fields: Vec<String> = row?.iter().map(|value| csv_field(&value.to_string(), ",")).collect();
+            writeln!(out, "{}", fields.join(","))?;
+        }
+        out.flush()?;
+
+        Ok(())
+    }
+
+    /// Parses `sql` without running it, e.g. to find out which columns it will return.
+    pub fn prepare(&mut self, sql: &str) -> Result<Statement, SqliteError> {
+        let column_names = result_column_names(self, &strip_comments(sql))?;
+        Ok(Statement { sql: sql.to_string(), column_names })
+    }
+
+    /// Lists the rowids of a table in the main database in ascending order. Only the cell
+    /// headers are read, which makes this much cheaper than selecting every row.
+    pub fn rowids(&mut self, table_name: &str) -> Result<Vec<i64>, SqliteError> {
+        let DatabaseFile { file, page_size, tables_info } = &mut self.main;
+        file.reset_counters();
+        let tinfo = find_table(tables_info, table_name)
+            .ok_or_else(|| SqliteError::NoSuchTable(table_name.to_string()))?;
+        tinfo.check_has_btree()?;
+
+        Ok(collect_rowids(file, *page_size, tinfo.rootpage)?)
+    }
+
+    /// Opens a cursor over the rows of a table in the main database, for walking them in Rust
+    /// without going through SQL.
+    ///
+    /// ```
+    /// # use codecrafters_sqlite::{Database, Value};
+    /// let mut db = Database::open("sample.db")?;
+    /// let mut ids = 0;
+    /// for row in db.table_cursor("apples")? {
+    ///     if let Value::Integer(id) = row?[0] {
+    ///         ids += id;
+    ///     }
+    /// }
+    /// assert_eq!(ids, 1 + 2 + 3 + 4);
+    /// # Ok::<(), codecrafters_sqlite::SqliteError>(())
+    /// ```
+    pub fn table_cursor(&mut self, table_name: &str) -> Result<RowCursor<'_>, SqliteError> {
+        self.open_table_cursor(table_name, false)
+    }
+
+    /// Like `table_cursor`, but returns the rows from the highest rowid down.
+    pub fn table_cursor_desc(&mut self, table_name: &str) -> Result<RowCursor<'_>, SqliteError> {
+        self.open_table_cursor(table_name, true)
+    }
+
+    fn open_table_cursor(&mut self, table_name: &str, reverse: bool) -> Result<RowCursor<'_>, SqliteError> {
+        let DatabaseFile { file, page_size, tables_info } = &mut self.main;
+        file.reset_counters();
+        let tinfo = find_table(tables_info, table_name)
+            .ok_or_else(|| SqliteError::NoSuchTable(table_name.to_string()))?;
+        tinfo.check_has_btree()?;
+
+        Ok(RowCursor::new(file, *page_size, tinfo, tinfo.full_row_idxs(), None, reverse))
+    }
+
+    /// Returns up to `limit` rows of a table in the main database whose rowid is greater than
+    /// `after`, as `SELECT rowid, * ... WHERE rowid > after ORDER BY rowid LIMIT limit` would.
+    /// Passing the last rowid of one page as `after` fetches the next, starting from
+    /// `i64::MIN` (so a row with exactly that rowid is never returned). Unlike OFFSET, which
+    /// reads every row it skips, this descends the b-tree straight to the first row of the page.
+    pub fn query_after_rowid(&mut self, table_name: &str, after: i64, limit: usize) -> Result<Rows<'_>, SqliteError> {
+        let mut cursor = self.open_table_cursor(table_name, false)?;
+        let tinfo = cursor.tinfo;
+        let column_names = std::iter::once("rowid".to_string())
+            .chain(tinfo.columns.iter().map(|col| col.name.clone()))
+            .collect();
+        let Some(min) = after.checked_add(1) else {
+            return Ok(RowSet { column_names, rows: vec![] }.into());
+        };
+        cursor.min = min;
+
+        let mut rows = vec![];
+        while rows.len() < limit && let Some(mut row) = cursor.next_row().transpose()? {
+            // The first leaf visited may hold rows at or below `after` too.
+            let Value::Integer(rowid) = row[tinfo.rowid_column()] else { continue };
+            if rowid >= min {
+                row.truncate(tinfo.columns.len());
+                row.insert(0, Value::Integer(rowid));
+                rows.push(row);
+            }
+        }
+
+        Ok(RowSet { column_names, rows }.into())
+    }
+
+    /// Fetches the row of a table in the main database with the given rowid (or `INTEGER
+    /// PRIMARY KEY`), as `SELECT * ... WHERE rowid = ?` would return it. Only the b-tree pages
+    /// on the path to the row are read.
+    pub fn get(&mut self, table_name: &str, rowid: i64) -> Result<Option<Vec<Value>>, SqliteError> {
+        let DatabaseFile { file, page_size, tables_info } = &mut self.main;
+        file.reset_counters();
+        let tinfo = find_table(tables_info, table_name)
+            .ok_or_else(|| SqliteError::NoSuchTable(table_name.to_string()))?;
+        tinfo.check_has_btree()?;
+
+        Ok(find_full_row(file, *page_size, tinfo, rowid)?)
+    }
+
+    /// Number of page loads performed by the last query, across all attached databases.
+    pub fn pages_read(&self) -> u64 {
+        self.main.file.pages_read + self.attached.iter().map(|(_, db_file)| db_file.file.pages_read).sum::<u64>()
+    }
+
+    /// A lightweight subset of `PRAGMA integrity_check`: walks the schema b-tree and every
+    /// table and index b-tree of the main database, failing with `Corrupt` on the first problem.
+    pub fn integrity_check(&mut self) -> Result<(), SqliteError> {
+        let DatabaseFile { file, page_size, tables_info } = &mut self.main;
+        file.reset_counters();
+
+        Ok(check_integrity(file, *page_size, tables_info)?)
+    }
+
+    fn database_file(&mut self, schema_name: Option<&str>) -> Result<&mut DatabaseFile> {
+        let db_file = match schema_name {
+            None => &mut self.main,
+            Some(name) if ident_eq(name, "main") => &mut self.main,
+            Some(name) => self.attached.iter_mut()
+                .find(|(attached_name, _)| ident_eq(attached_name, name))
+                .map(|(_, db_file)| db_file)
+                .ok_or_else(|| SqliteError::Misuse(format!("unknown database {}", name)))?,
+        };
+
+        Ok(db_file)
+    }
+}
+
+/// Resolves a column name to its index in a full row. `rowid`, `oid` and `_rowid_` name the
+/// rowid unless a declared column uses that name (see `TableInfo::rowid_column`).
+fn find_column(columns: &[Column], name: &str) -> Result<usize> {
+    let name = unquote_identifier(name);
+    let mut matches = columns.iter().enumerate().filter(|(_, col)| ident_eq(&col.name, name));
+    if let Some((idx, col)) = matches.next() {
+        // The columns of a join come from two tables, which may share a name.
+        if col.table.is_some() && matches.any(|(_, other)| other.table != col.table) {
+            bail!(SqliteError::UnsupportedSql(format!("ambiguous column name: {}", name)));
+        }
+        return Ok(idx);
+    }
+
+    if ["rowid", "oid", "_rowid_"].iter().any(|alias| ident_eq(name, alias)) {
+        return Ok(columns.iter().position(|col| col.rowid_alias).unwrap_or(columns.len()));
+    }
+    bail!(SqliteError::NoSuchColumn { column: name.to_string(), table: None })
+}
+
+/// Resolves `table.name` among the columns qualified by `table`. The rowid of a table with no
+/// INTEGER PRIMARY KEY is the pseudo-column after the others, which only a single table has.
+fn find_qualified_column(columns: &[Column], table: &str, name: &str) -> Result<usize> {
+    let (table, name) = (unquote_identifier(table), unquote_identifier(name));
+    let in_table = |col: &Column| col.table.as_deref().is_some_and(|qualifier| ident_eq(qualifier, table));
+    if let Some(idx) = columns.iter().position(|col| in_table(col) && ident_eq(&col.name, name)) {
+        return Ok(idx);
+    }
+
+    if ["rowid", "oid", "_rowid_"].iter().any(|alias| ident_eq(name, alias)) {
+        if let Some(idx) = columns.iter().position(|col| in_table(col) && col.rowid_alias) {
+            return Ok(idx);
+        }
+        if !columns.is_empty() && columns.iter().all(in_table) {
+            return Ok(columns.len());
+        }
+    }
+    bail!(SqliteError::NoSuchColumn { column: format!("{}.{}", table, name), table: None })
+}
+
+fn parse_select_items(cols_str: &str, columns: &[Column], aggregates: &mut Vec<Aggregate>) -> Result<Vec<(String, Expr)>> {
+    let alias_re = Regex::new(&format!(r"(?is)^(.+?)\s+AS\s+({})$", IDENTIFIER_PATTERN))?;
+    // A qualified column is named after the column alone, and `table.*` expands to its columns.
+    let qualified_re = Regex::new(&format!(r"(?s)^({0})\s*\.\s*({0}|\*)$", IDENTIFIER_PATTERN))?;
+
+    let mut items = vec![];
+    for item in split_top_level(cols_str, ',').into_iter().map(|s| s.trim()) {
+        let qualified = qualified_re.captures(item).filter(|caps| !caps[1].starts_with(|c: char| c.is_ascii_digit()));
+        let (expr, name) = match (alias_re.captures(item), &qualified) {
+            (Some(caps), _) => (caps.get(1).map_or("", |m| m.as_str()), unquote_identifier(&caps[2]).to_string()),
+            (None, Some(caps)) => (item, unquote_identifier(&caps[2]).to_string()),
+            (None, None) => (item, item.to_string()),
+        };
+
+        if expr == "*" && columns.is_empty() {
+            bail!(SqliteError::UnsupportedSql("no tables specified".to_string()));
+        } else if expr == "*" {
+            items.extend(columns.iter().enumerate().map(|(idx, col)| (col.name.clone(), Expr::Column(idx))));
+        } else if let Some(caps) = qualified.filter(|caps| &caps[2] == "*") {
+            let table = unquote_identifier(&caps[1]);
+            let len = items.len();
+            items.extend(columns.iter().enumerate()
+                .filter(|(_, col)| col.table.as_deref().is_some_and(|qualifier| ident_eq(qualifier, table)))
+                .map(|(idx, col)| (col.name.clone(), Expr::Column(idx))));
+            if items.len() == len {
+                bail!(SqliteError::UnsupportedSql(format!("no such table: {}", table)));
+            }
+        } else {
+            items.push((name, parse_expr_with_aggregates(expr, columns, &[], aggregates)?));
+        }
+    }
+
+    Ok(items)
+}
+
+/// English ordinal of `n`, as in SQLite's error messages: 1st, 2nd, 3rd, 4th, 11th, 21st.
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+/// Resolves an ORDER BY or GROUP BY term. As in SQLite, an integer term refers to a result
+/// column by position and a bare name matching a result column's name stands for that result
+/// column; anything else is an expression over the table's columns. `i` is the term's index,
+/// for error messages.
+fn parse_result_column_term(term: &str, i: usize, clause: &str, columns: &[Column], select_items: &[(String, Expr)], aggregates: &mut Vec<Aggregate>) -> Result<Expr> {
+    let position_re = Regex::new(r"^[+-]?\d+$")?;
+    let name_re = Regex::new(&format!("^{}$", IDENTIFIER_PATTERN))?;
+
+    if position_re.is_match(term) {
+        return match term.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|n| select_items.get(n)) {
+            Some((_, expr)) => Ok(expr.clone()),
+            None => bail!(SqliteError::UnsupportedSql(format!(
+                "{} {} term out of range - should be between 1 and {}", ordinal(i + 1), clause, select_items.len()))),
+        };
+    }
+    if name_re.is_match(term) && let Some((_, expr)) = select_items.iter().find(|(name, _)| ident_eq(name, unquote_identifier(term))) {
+        return Ok(expr.clone());
+    }
+    parse_expr_with_aggregates(term, columns, select_items, aggregates)
+}
+
+/// Parses the ORDER BY terms; aggregates they call are appended to `aggregates`.
+fn parse_order_by(sql: &str, columns: &[Column], select_items: &[(String, Expr)], aggregates: &mut Vec<Aggregate>) -> Result<Vec<OrderTerm>> {
+    let order_re = Regex::new(r"(?is)\bORDER\s+BY\s+(.+?)\s*;?\s*$")?;
+    let term_re = Regex::new(r"(?is)^(.+?)(?:\s+COLLATE\s+(\S+))?(?:\s+(ASC|DESC))?$")?;
+
+    let Some(caps) = order_re.captures(sql) else {
+        return Ok(vec![]);
+    };
+
+    split_top_level(&caps[1], ',').into_iter()
+        .enumerate()
+        .map(|(i, term)| {
+            let term_caps = term_re.captures(term.trim())
+                .ok_or_else(|| SqliteError::UnsupportedSql(format!("unsupported ORDER BY term: {}", term.trim())))?;
+            let expr = parse_result_column_term(term_caps[1].trim(), i, "ORDER BY", columns, select_items, aggregates)?;
+            // A trailing COLLATE also applies to a term naming a result column.
+            let collation = match term_caps.get(2) {
+                Some(name) => Collation::from_str(unquote_identifier(name.as_str()))
+                    .ok_or_else(|| SqliteError::UnsupportedSql(format!("no such collation sequence: {}", name.as_str())))?,
+                None => column_collation(&expr, columns).unwrap_or(Collation::Binary),
+            };
+            Ok(OrderTerm {
+                expr,
+                descending: term_caps.get(3).is_some_and(|m| m.as_str().eq_ignore_ascii_case("DESC")),
+                collation,
+            })
+        })
+        .collect()
+}
+
+/// Parses the GROUP BY terms, which may not refer to an aggregate.
+fn parse_group_by(sql: &str, columns: &[Column], select_items: &[(String, Expr)]) -> Result<Vec<Expr>> {
+    let group_re = Regex::new(r"(?is)^\s*GROUP\s+BY\s+(.+?)\s*;?\s*$")?;
+
+    let Some(caps) = group_re.captures(sql) else {
+        return Ok(vec![]);
+    };
+
+    split_top_level(&caps[1], ',').into_iter()
+        .enumerate()
+        .map(|(i, term)| {
+            let expr = parse_result_column_term(term.trim(), i, "GROUP BY", columns, select_items, &mut vec![])?;
+            let mut col_idxs = vec![];
+            referenced_columns(&expr, &mut col_idxs);
+            if col_idxs.iter().any(|&idx| idx > columns.len()) {
+                bail!(SqliteError::UnsupportedSql("aggregate functions are not allowed in the GROUP BY clause".to_string()));
+            }
+            Ok(expr)
+        })
+        .collect()
+}
+
+fn compare_sort_keys(order_terms: &[OrderTerm], a: &[Value], b: &[Value]) -> Ordering {
+    order_terms.iter().zip(a.iter().zip(b))
+        .map(|(term, (a, b))| {
+            let ordering = compare_values(a, b, term.collation);
+            if term.descending { ordering.reverse() } else { ordering }
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+fn sort_keys(row: &[Value], order_terms: &[OrderTerm]) -> Result<Vec<Value>> {
+    order_terms.iter().map(|term| evaluate(&term.expr, row)).collect()
+}
+
+/// Sorts rows by the ORDER BY terms, evaluating each term's expression once per row.
+fn sort_rows(rows: Vec<Vec<Value>>, order_terms: &[OrderTerm]) -> Result<Vec<Vec<Value>>> {
+    if order_terms.is_empty() {
+        return Ok(rows);
+    }
+
+    let mut keyed_rows = rows.into_iter()
+        .map(|row| Ok((sort_keys(&row, order_terms)?, row)))
+        .collect::<Result<Vec<_>>>()?;
+    keyed_rows.sort_by(|(a, _), (b, _)| compare_sort_keys(order_terms, a, b));
+
+    Ok(keyed_rows.into_iter().map(|(_, row)| row).collect())
+}
+
+/// A row waiting in the top-k heap. Rows with equal keys are ordered by arrival, which keeps
+/// the result identical to the stable full sort.
+struct HeapRow<'t> {
+    order_terms: &'t [OrderTerm],
+    keys: Vec<Value>,
+    seq: usize,
+    row: Vec<Value>,
+}
+
+impl Ord for HeapRow<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_sort_keys(self.order_terms, &self.keys, &other.keys).then(self.seq.cmp(&other.seq))
+    }
+}
+
+impl PartialOrd for HeapRow<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for HeapRow<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for HeapRow<'_> {}
+
+/// The first `k` rows in ORDER BY order. Rows are taken from `rows` one at a time and only `k`
+/// are kept: each new row replaces the current last one if it sorts before it.
+fn top_k_rows(rows: impl IntoIterator<Item = Result<Vec<Value>>>, order_terms: &[OrderTerm], k: usize) -> Result<Vec<Vec<Value>>> {
+    let mut heap = BinaryHeap::new();
+    for (seq, row) in rows.into_iter().enumerate() {
+        let row = row?;
+        heap.push(HeapRow { order_terms, keys: sort_keys(&row, order_terms)?, seq, row });
+        if heap.len() > k {
+            heap.pop();
+        }
+    }
+
+    Ok(heap.into_sorted_vec().into_iter().map(|heap_row| heap_row.row).collect())
+}
+
+/// Evaluates a LIMIT or OFFSET expression, which must come out as an integer. Text spelling
+/// a number and reals without a fractional part are converted, as integer affinity would.
+fn parse_limit_value(sql: &str) -> Result<i64> {
+    let value = match evaluate(&parse_expr(sql, &[])?, &[])? {
+        Value::Text(text) if text.trim().parse::<f64>().is_ok() => to_numeric(&Value::Text(text)),
+        value => value,
+    };
+    match value {
+        Value::Integer(n) => Ok(n),
+        Value::Real(r) if r.fract() == 0.0 && (-9.2e18..9.2e18).contains(&r) => Ok(r as i64),
+        _ => bail!(SqliteError::TypeError("datatype mismatch".to_string())),
+    }
+}
+
+/// Parses `LIMIT <count> [OFFSET <skip>]` or `LIMIT <skip>, <count>`. A negative count means no
+/// limit and a negative offset is treated as zero.
+fn parse_limit(sql: &str) -> Result<Option<Limit>> {
+    let limit_re = Regex::new(r"(?is)^\s*LIMIT\s+(.+?)\s*;?\s*$")?;
+    let offset_re = Regex::new(r"(?i)\bOFFSET\b")?;
+    let Some(caps) = limit_re.captures(sql) else {
+        return Ok(None);
+    };
+
+    let clause = &caps[1];
+    let masked = mask_nested(clause);
+    let (count, offset) = match (offset_re.find(&masked), split_top_level(clause, ',').as_slice()) {
+        (Some(m), _) => (&clause[..m.start()], Some(&clause[m.end()..])),
+        (None, [offset, count]) => (*count, Some(*offset)),
+        (None, _) => (clause, None),
+    };
+
+    Ok(Some(Limit {
+        count: usize::try_from(parse_limit_value(count)?).ok(),
+        offset: offset.map(parse_limit_value).transpose()?.map_or(0, |offset| offset.max(0) as usize),
+    }))
+}
+
+/// SUM stays an exact integer while every input is an integer, failing on overflow as
+/// SQLite does, and switches to a real total once a real value is added.
+fn sum_values<'a>(values: impl Iterator<Item = &'a Value>) -> Result<Value> {
+    let mut total = Value::Null;
+    for value in values {
+        // Only text that is wholly an integer adds as one; '3 apples' adds 3.0.
+        let operand = match (value, to_numeric(value)) {
+            (Value::Text(_) | Value::Blob(_), Value::Integer(integer)) if value.to_string().trim().parse::<i64>().is_err() => Value::Real(integer as f64),
+            (_, operand) => operand,
+        };
+        total = match (total, operand) {
+            (Value::Null, operand) => operand,
+            (Value::Integer(a), Value::Integer(b)) => match a.checked_add(b) {
+                Some(sum) => Value::Integer(sum),
+                None => bail!(SqliteError::TypeError("integer overflow".to_string())),
+            },
+            (a, b) => Value::Real(value_as_f64(&a).unwrap_or(0.0) + value_as_f64(&b).unwrap_or(0.0)),
+        };
+    }
+
+    Ok(total)
+}
+
+/// Drops values equal (under `collation`) to an earlier one, keeping the first of each so
+/// the remaining values stay in row order.
+fn distinct_values(values: Vec<Value>, collation: Collation) -> Vec<Value> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| compare_values(&values[a], &values[b], collation).then(a.cmp(&b)));
+
+    let mut keep = vec![false; values.len()];
+    for (i, &idx) in order.iter().enumerate() {
+        keep[idx] = i == 0 || compare_values(&values[order[i - 1]], &values[idx], collation).is_ne();
+    }
+
+    values.into_iter().zip(keep).filter_map(|(value, keep)| keep.then_some(value)).collect()
+}
+
+/// Drops rows whose result columns all equal (under each column's collation) those of an
+/// earlier row, keeping the first of each so the remaining rows stay in order.
+fn distinct_rows(rows: Vec<Vec<Value>>, items: &[(String, Expr)], columns: &[Column]) -> Result<Vec<Vec<Value>>> {
+    let terms: Vec<OrderTerm> = items.iter()
+        .map(|(_, expr)| OrderTerm {
+            expr: expr.clone(),
+            descending: false,
+            collation: column_collation(expr, columns).unwrap_or(Collation::Binary),
+        })
+        .collect();
+    let keys = rows.iter().map(|row| sort_keys(row, &terms)).collect::<Result<Vec<_>>>()?;
+
+    let mut order: Vec<usize> = (0..rows.len()).collect();
+    order.sort_by(|&a, &b| compare_sort_keys(&terms, &keys[a], &keys[b]).then(a.cmp(&b)));
+    let mut keep = vec![false; rows.len()];
+    for (i, &idx) in order.iter().enumerate() {
+        keep[idx] = i == 0 || compare_sort_keys(&terms, &keys[order[i - 1]], &keys[idx]).is_ne();
+    }
+
+    Ok(rows.into_iter().zip(keep).filter_map(|(row, keep)| keep.then_some(row)).collect())
+}
+
+/// Splits rows into the groups of rows with equal GROUP BY values, ordered by those values.
+/// Without GROUP BY terms all rows, even none, form a single group.
+fn group_rows(rows: Vec<Vec<Value>>, group_by: &[Expr], columns: &[Column]) -> Result<Vec<Vec<Vec<Value>>>> {
+    if group_by.is_empty() {
+        return Ok(vec![rows]);
+    }
+
+    let terms: Vec<OrderTerm> = group_by.iter()
+        .map(|expr| OrderTerm {
+            expr: expr.clone(),
+            descending: false,
+            collation: column_collation(expr, columns).unwrap_or(Collation::Binary),
+        })
+        .collect();
+    let mut keyed_rows = rows.into_iter()
+        .map(|row| Ok((sort_keys(&row, &terms)?, row)))
+        .collect::<Result<Vec<_>>>()?;
+    keyed_rows.sort_by(|(a, _), (b, _)| compare_sort_keys(&terms, a, b));
+
+    let mut groups: Vec<(Vec<Value>, Vec<Vec<Value>>)> = vec![];
+    for (keys, row) in keyed_rows {
+        match groups.last_mut() {
+            Some((group_keys, group)) if compare_sort_keys(&terms, group_keys, &keys).is_eq() => group.push(row),
+            _ => groups.push((keys, vec![row])),
+        }
+    }
+
+    Ok(groups.into_iter().map(|(_, group)| group).collect())
+}
+
+/// Folds one aggregate over the rows of a group, evaluating its argument per row first.
+/// NULL results are ignored, so MIN/MAX/SUM/AVG over only NULLs (or no rows) is NULL while
+/// TOTAL, always a real, is 0.0.
+fn aggregate_value(aggregate: &Aggregate, rows: &[Vec<Value>], columns: &[Column]) -> Result<Value> {
+    let Aggregate { func, arg, distinct } = aggregate;
+    let Some(arg) = arg else {
+        return Ok(Value::Integer(rows.len() as i64));
+    };
+    let collation = column_collation(arg, columns).unwrap_or(Collation::Binary);
+
+    let mut values = vec![];
+    for row in rows {
+        let value = evaluate(arg, row)?;
+        if value != Value::Null {
+            values.push(value);
+        }
+    }
+    if *distinct {
+        values = distinct_values(values, collation);
+    }
+
+    Ok(match func {
+        AggregateFunc::Count => Value::Integer(values.len() as i64),
+        // Of several equal values the first one is kept, which `min_by` does but `max_by` doesn't.
+        AggregateFunc::Min => values.into_iter().min_by(|a, b| compare_values(a, b, collation)).unwrap_or(Value::Null),
+        AggregateFunc::Max => values.into_iter().min_by(|a, b| compare_values(b, a, collation)).unwrap_or(Value::Null),
+        AggregateFunc::Sum => sum_values(values.iter())?,
+        AggregateFunc::Total => Value::Real(values.iter().map(|value| value_as_f64(&to_numeric(value)).unwrap_or(0.0)).sum()),
+        AggregateFunc::Avg if values.is_empty() => Value::Null,
+        AggregateFunc::Avg => {
+            let total: f64 = values.iter().map(|value| value_as_f64(&to_numeric(value)).unwrap_or(0.0)).sum();
+            Value::Real(total / values.len() as f64)
+        }
+    })
+}
+
+/// What a SELECT reads its rows from: a table's b-tree or the rows of a subquery in FROM.
+enum RowSource<'a> {
+    Table { file: &'a mut Pager, page_size: u32, tinfo: &'a TableInfo, tables_info: &'a [TableInfo] },
+    Rows(Vec<Vec<Value>>),
+}
+
+impl RowSource<'_> {
+    /// Produces the rows matching `predicate`. Only the columns in `used_col_idxs` need to be
+    /// filled in; a table leaves the others NULL.
+    fn rows(self, used_col_idxs: &[usize], predicate: Option<&Expr>) -> Result<Vec<Vec<Value>>> {
+        let index_lookup = self.index_lookup(predicate);
+        let (file, page_size, tinfo) = match self {
+            RowSource::Table { file, page_size, tinfo, .. } => (file, page_size, tinfo),
+            RowSource::Rows(rows) => return match predicate {
+                Some(predicate) => filter_rows(rows, predicate),
+                None => Ok(rows),
+            },
+        };
+
+        match (index_lookup, predicate) {
+            (Some((index_rootpage, filter)), Some(predicate)) => {
+                let mut rows = get_cols_data_with_index(file, tinfo, page_size, used_col_idxs, index_rootpage, &filter)?;
+                apply_real_affinity(&mut rows, &tinfo.full_row_idxs(), &tinfo.columns);
+                // The index lookup only applied one of the AND-ed conditions.
+                filter_rows(rows, predicate)
+            }
+            (_, predicate) => get_cols_data_with_filter(file, page_size, tinfo, used_col_idxs, predicate),
+        }
+    }
+
+    /// The root page of an index on a table column that `predicate` compares for equality,
+    /// and the filter to look up in it.
+    fn index_lookup(&self, predicate: Option<&Expr>) -> Option<(u32, Filter)> {
+        let RowSource::Table { tinfo, tables_info, .. } = self else {
+            return None;
+        };
+
+        predicate
+            .map(Filter::from_predicate)
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|(col_idx, filter)| {
+                let col_name = &tinfo.columns.get(col_idx)?.name;
+                tables_info.iter()
+                    .find(|t| t.tpe == "index"
+                        && ident_eq(&t.tbl_name, &tinfo.name)
+                        && t.index_col.as_ref().is_some_and(|c| ident_eq(c, col_name)))
+                    .map(|index| (index.rootpage, filter))
+            })
+    }
+
+    /// Whether the rows can be read from a table b-tree already sorted by `order_terms`, which
+    /// is when they sort by the rowid alone and no index lookup is used. Gives the direction,
+    /// `true` being descending.
+    fn rowid_order(&self, order_terms: &[OrderTerm], predicate: Option<&Expr>) -> Option<bool> {
+        let RowSource::Table { tinfo, .. } = self else {
+            return None;
+        };
+        match order_terms {
+            [term] if term.expr == Expr::Column(tinfo.rowid_column()) && self.index_lookup(predicate).is_none() => Some(term.descending),
+            _ => None,
+        }
+    }
+
+    /// Like `rows`, but walks the table b-tree in rowid order, or in reverse, and stops once
+    /// `limit` rows match.
+    fn rows_in_rowid_order(self, used_col_idxs: &[usize], predicate: Option<&Expr>, descending: bool, limit: Option<usize>) -> Result<Vec<Vec<Value>>> {
+        let RowSource::Table { file, page_size, tinfo, .. } = self else {
+            return self.rows(used_col_idxs, predicate);
+        };
+
+        let mut cursor = RowCursor::new(file, page_size, tinfo, used_col_idxs.to_vec(), predicate.cloned(), descending);
+        let mut rows = vec![];
+        while rows.len() < limit.unwrap_or(usize::MAX) && let Some(row) = cursor.next_row() {
+            rows.push(row?);
+        }
+
+        Ok(rows)
+    }
+}
+
+/// Blanks out everything inside parentheses and quotes, keeping byte offsets, so keywords can
+/// be searched for at the top level of a statement only.
+fn mask_nested(s: &str) -> String {
+    let mut masked = String::with_capacity(s.len());
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+
+    for c in s.chars() {
+        let nested = quote.is_some() || depth > 0;
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '[') => quote = Some(']'),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            _ => {}
+        }
+        if nested && (quote.is_some() || depth > 0) {
+            masked.extend(std::iter::repeat_n(' ', c.len_utf8()));
+        } else {
+            masked.push(c);
+        }
+    }
+
+    masked
+}
+
+/// Byte index of the parenthesis that closes the one at `open`, skipping quoted text.
+fn closing_paren(s: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+
+    for (i, c) in s[open..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '[') => quote = Some(']'),
+            (None, '(') => depth += 1,
+            (None, ')') if depth == 1 => return Some(open + i),
+            (None, ')') => depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Database header fields readable with `PRAGMA name`, and their offsets in the header.
+const HEADER_PRAGMAS: [(&str, u64); 3] = [("schema_version", 40), ("user_version", 60), ("application_id", 68)];
+
+/// Reads a 4-byte big-endian field of the database header, as seen through the WAL.
+fn read_header_field(file: &mut Pager, offset: u64) -> Result<i32> {
+    let mut field = [0; 4];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut field)
+        .context(format!("Failed to read database header field at offset {}", offset))?;
+
+    Ok(i32::from_be_bytes(field))
+}
+
+/// Matches `PRAGMA [schema.]name` for one of `HEADER_PRAGMAS`, giving the schema name, the
+/// pragma's name and its header offset.
+fn parse_header_pragma(sql: &str) -> Result<Option<(Option<&str>, &'static str, u64)>> {
+    let pragma_regex = Regex::new(
+        &format!(r"(?i)^\s*PRAGMA\s+(?:({0})\s*\.\s*)?(\w+)\s*;?\s*$", IDENTIFIER_PATTERN)
+    )?;
+    let Some(caps) = pragma_regex.captures(sql) else {
+        return Ok(None);
+    };
+
+    Ok(HEADER_PRAGMAS.iter()
+        .find(|(name, _)| caps[2].eq_ignore_ascii_case(name))
+        .map(|&(name, offset)| (caps.get(1).map(|m| unquote_identifier(m.as_str())), name, offset)))
+}
+
+/// Runs a query to the end, for a subquery or view whose rows feed the query around it.
+fn execute_sql_query(db: &mut Database, sql: &str) -> Result<RowSet> {
+    let rows = query_rows(db, sql)?;
+    let column_names = rows.column_names.clone();
+    Ok(RowSet { column_names, rows: rows.collect::<Result<_, _>>()? })
+}
+
+fn query_rows<'a>(db: &'a mut Database, sql: &str) -> Result<Rows<'a>> {
+    if let Some((schema_name, name, offset)) = parse_header_pragma(sql)? {
+        let DatabaseFile { file, .. } = db.database_file(schema_name)?;
+        let value = read_header_field(file, offset)?;
+        return Ok(RowSet {
+            column_names: vec![name.to_string()],
+            rows: vec![vec![Value::Integer(value as i64)]],
+        }.into());
+    }
+
+    if let Some(Compound { first, others, rest }) = split_compound(sql)? {
+        let mut rows = execute_sql_query(db, first)?;
+        for (operator, select_sql) in others {
+            let right = execute_sql_query(db, select_sql)?;
+            if right.column_names.len() != rows.column_names.len() {
+                bail!(SqliteError::UnsupportedSql(format!(
+                    "SELECTs to the left and right of {} do not have the same number of result columns", operator)));
+            }
+            rows.rows = combine_rows(operator, rows.rows, right.rows);
+        }
+        return select_from_rows(db, "*", rows, rest);
+    }
+
+    // COUNT of a non-NULL constant, such as COUNT(1), counts every row just like COUNT(*).
+    let count_regex = Regex::new(
+        &format!(r"(?i)^\s*SELECT\s+(COUNT\s*\(\s*(?:\*|[-+]?(?:\d+\.?\d*|\.\d+)|'(?:[^']|'')*')\s*\))\s+FROM\s+(?:({0})\s*\.\s*)?({0})\s*;?\s*$", IDENTIFIER_PATTERN)
+    )?;
+    if let Some(caps) = count_regex.captures(sql) {
+        let DatabaseFile { file, page_size, tables_info } = db.database_file(caps.get(2).map(|m| unquote_identifier(m.as_str())))?;
+        let page_size = *page_size;
+        let table_name = unquote_identifier(&caps[3]).to_string();
+        if let Some(tinfo) = find_table(tables_info, &table_name) {
+            tinfo.check_has_btree()?;
+            let count = count_rows(file, page_size, tinfo.rootpage)?;
+            return Ok(RowSet {
+                column_names: vec![caps[1].to_string()],
+                rows: vec![vec![Value::Integer(count as i64)]],
+            }.into());
+        }
+    }
+
+    // MIN or MAX of the rowid is the first or last key of the table b-tree, read by descending
+    // its leftmost or rightmost children without visiting any other leaf.
+    let rowid_bound_regex = Regex::new(
+        &format!(r"(?i)^\s*SELECT\s+((MIN|MAX)\s*\(\s*({0})\s*\))\s+FROM\s+(?:({0})\s*\.\s*)?({0})\s*;?\s*$", IDENTIFIER_PATTERN)
+    )?;
+    if let Some(caps) = rowid_bound_regex.captures(sql) {
+        let DatabaseFile { file, page_size, tables_info } = db.database_file(caps.get(4).map(|m| unquote_identifier(m.as_str())))?;
+        let page_size = *page_size;
+        if let Some(tinfo) = find_table(tables_info, unquote_identifier(&caps[5]))
+            && find_column(&tinfo.columns, &caps[3]).is_ok_and(|idx| idx == tinfo.rowid_column()) {
+            tinfo.check_has_btree()?;
+            let last = caps[2].eq_ignore_ascii_case("MAX");
+            let mut cursor = RowCursor::new(file, page_size, tinfo, vec![tinfo.rowid_column()], None, last);
+            let value = match cursor.next_row().transpose()? {
+                Some(row) => row[tinfo.rowid_column()].clone(),
+                None => Value::Null,
+            };
+            return Ok(RowSet { column_names: vec![caps[1].to_string()], rows: vec![vec![value]] }.into());
+        }
+    }
+
+    let SelectParts { cols_str, source, rest } = split_select(sql)?;
+    match source {
+        FromSource::Subquery(subquery_sql) => {
+            let subquery = execute_sql_query(db, subquery_sql)?;
+            select_from_rows(db, cols_str, subquery, rest)
+        }
+        FromSource::None => select_from_rows(db, cols_str, RowSet { column_names: vec![], rows: vec![vec![]] }, rest),
+        FromSource::Join { left, right, on } => {
+            let (columns, left_len) = join_columns(db, &left, &right)?;
+            let select = parse_select(db, cols_str, &columns, rest)?;
+            let rows = join_rows(db, &left, &right, on, &columns, left_len)?;
+            execute_select(select, &columns, RowSource::Rows(rows))
+        }
+        FromSource::Table(table) => {
+            let TableRef { schema_name, table_name, .. } = table;
+            let no_such_table = || SqliteError::NoSuchTable(table_name.to_string());
+            if let Some(view) = find_view(&db.database_file(schema_name)?.tables_info, table_name) {
+                let view_name = view.name.clone();
+                let (view_sql, column_names) = view.view_select()?;
+                let mut rows = execute_sql_query(db, &view_sql)?;
+                if let Some(column_names) = column_names {
+                    if column_names.len() != rows.column_names.len() {
+                        bail!(SqliteError::UnsupportedSql(format!("expected {} columns for '{}' but got {}",
+                            column_names.len(), view_name, rows.column_names.len())));
+                    }
+                    rows.column_names = column_names;
+                }
+                return select_from_rows(db, cols_str, rows, rest).map_err(|e| SqliteError::in_table(e, &view_name));
+            }
+
+            // Parsing may run subqueries, so the table is looked up again for the scan itself.
+            let tinfo = find_table(&db.database_file(schema_name)?.tables_info, table_name).ok_or_else(no_such_table)?;
+            tinfo.check_has_btree()?;
+            let (columns, tinfo_name) = (qualified_columns(tinfo, table.qualifier()), tinfo.name.clone());
+            let select = parse_select(db, cols_str, &columns, rest).map_err(|e| SqliteError::in_table(e, &tinfo_name))?;
+
+            let DatabaseFile { file, page_size, tables_info } = db.database_file(schema_name)?;
+            let tinfo = find_table(tables_info, table_name).ok_or_else(no_such_table)?;
+            let source = RowSource::Table { file, page_size: *page_size, tinfo, tables_info };
+            execute_select(select, &tinfo.columns, source)
+        }
+    }
+}
+
+/// A table's columns, qualified by `qualifier`.
+fn qualified_columns(tinfo: &TableInfo, qualifier: &str) -> Vec<Column> {
+    tinfo.columns.iter()
+        .map(|col| Column { table: Some(qualifier.to_string()), ..col.clone() })
+        .collect()
+}
+
+/// The columns of a join: those of `left`, then those of `right`, whose count is also given.
+fn join_columns(db: &mut Database, left: &TableRef, right: &TableRef) -> Result<(Vec<Column>, usize)> {
+    let mut table_columns = |table: &TableRef| -> Result<Vec<Column>> {
+        let tables_info = &db.database_file(table.schema_name)?.tables_info;
+        if find_view(tables_info, table.table_name).is_some() {
+            bail!(SqliteError::UnsupportedSql(JOINS_SUPPORTED.to_string()));
+        }
+        let tinfo = find_table(tables_info, table.table_name)
+            .ok_or_else(|| SqliteError::NoSuchTable(table.table_name.to_string()))?;
+        tinfo.check_has_btree()?;
+        Ok(qualified_columns(tinfo, table.qualifier()))
+    };
+    let mut columns = table_columns(left)?;
+    let left_len = columns.len();
+    columns.extend(table_columns(right)?);
+
+    Ok((columns, left_len))
+}
+
+/// Joins the rows of `left` and `right` that satisfy `on`, each joined row holding the values
+/// of `left`'s columns and then `right`'s. When `on` equates a column of one table to the
+/// INTEGER PRIMARY KEY of the other, that other table is read by looking each row's key up in
+/// its b-tree; otherwise every pair of rows is tried.
+fn join_rows(db: &mut Database, left: &TableRef, right: &TableRef, on: Option<&str>, columns: &[Column], left_len: usize) -> Result<Vec<Vec<Value>>> {
+    let on = on.map(|on| parse_expr_with_subqueries(on, columns, &mut |sql| execute_sql_query(db, sql))).transpose()?;
+    let lookup = on.as_ref().and_then(|on| rowid_join_key(on, columns, left_len));
+    // The table whose rows are looked up is the inner one.
+    let right_outer = lookup.is_some_and(|key| key >= left_len);
+    let (outer, inner) = if right_outer { (right, left) } else { (left, right) };
+
+    let outer_rows = table_rows(db, outer)?;
+    let inner_rows = match lookup {
+        Some(_) => vec![],
+        None => table_rows(db, inner)?,
+    };
+    let DatabaseFile { file, page_size, tables_info } = db.database_file(inner.schema_name)?;
+    let inner_tinfo = find_table(tables_info, inner.table_name)
+        .ok_or_else(|| SqliteError::NoSuchTable(inner.table_name.to_string()))?;
+
+    let mut rows = vec![];
+    for outer_row in outer_rows {
+        let found;
+        let candidates = match lookup {
+            Some(key) => {
+                let key = if right_outer { key - left_len } else { key };
+                // Whether a key that is not an integer matches is left to `on`, checked below.
+                found = match to_numeric(&outer_row[key]) {
+                    Value::Integer(rowid) => find_full_row(file, *page_size, inner_tinfo, rowid)?,
+                    Value::Real(real) if real.fract() == 0.0 => find_full_row(file, *page_size, inner_tinfo, real as i64)?,
+                    _ => None,
+                };
+                found.as_slice()
+            }
+            None => &inner_rows,
+        };
+        for inner_row in candidates {
+            let row = match right_outer {
+                true => [inner_row.as_slice(), &outer_row].concat(),
+                false => [outer_row.as_slice(), inner_row].concat(),
+            };
+            if on.as_ref().map_or(Ok(true), |on| evaluate(on, &row).map(|value| is_truthy(&value)))? {
+                rows.push(row);
+            }
+        }
+    }
+
+    Ok(rows)
+}
+
+/// The column a join looks rows up by, when `on` is `a = b` with one of `a` and `b` the INTEGER
+/// PRIMARY KEY of one table and the other a column of the other table.
+fn rowid_join_key(on: &Expr, columns: &[Column], left_len: usize) -> Option<usize> {
+    let Expr::Compare(CompareOp::Eq, lhs, rhs, _) = on else {
+        return None;
+    };
+    [(lhs, rhs), (rhs, lhs)].into_iter().find_map(|(key, primary_key)| match (key.as_ref(), primary_key.as_ref()) {
+        (Expr::Column(key), Expr::Column(primary_key)) if (*key < left_len) != (*primary_key < left_len)
+            && columns.get(*primary_key).is_some_and(|col| col.rowid_alias) => Some(*key),
+        _ => None,
+    })
+}
+
+/// Every row of a table, as `SELECT *` gives them.
+fn table_rows(db: &mut Database, table: &TableRef) -> Result<Vec<Vec<Value>>> {
+    let DatabaseFile { file, page_size, tables_info } = db.database_file(table.schema_name)?;
+    let tinfo = find_table(tables_info, table.table_name)
+        .ok_or_else(|| SqliteError::NoSuchTable(table.table_name.to_string()))?;
+    let mut rows = get_cols_data_with_filter(file, *page_size, tinfo, &tinfo.full_row_idxs(), None)?;
+    for row in &mut rows {
+        row.truncate(tinfo.columns.len());
+    }
+
+    Ok(rows)
+}
+
+/// Runs a SELECT over the result of a subquery or view in FROM, whose result columns become
+/// the outer query's columns.
+fn select_from_rows<'a>(db: &mut Database, cols_str: &str, subquery: RowSet, rest: &str) -> Result<Rows<'a>> {
+    let columns: Vec<Column> = subquery.column_names.iter().map(|name| Column::from_strs(name, "")).collect();
+    let select = parse_select(db, cols_str, &columns, rest)?;
+    execute_select(select, &columns, RowSource::Rows(subquery.rows))
+}
+
+/// Where a SELECT's FROM clause points.
+enum FromSource<'s> {
+    Table(TableRef<'s>),
+    /// `left [INNER | CROSS] JOIN right [ON condition]`, or `left, right`.
+    Join { left: TableRef<'s>, right: TableRef<'s>, on: Option<&'s str> },
+    /// The SQL of a parenthesized subquery.
+    Subquery(&'s str),
+    /// No FROM clause: the result columns are evaluated once, against a row with no columns.
+    None,
+}
+
+/// A table named in FROM, with the alias it may be given.
+struct TableRef<'s> {
+    schema_name: Option<&'s str>,
+    table_name: &'s str,
+    alias: Option<&'s str>,
+}
+
+impl TableRef<'_> {
+    /// The name that qualifies the table's columns: its alias, or its own name without one.
+    fn qualifier(&self) -> &str {
+        self.alias.unwrap_or(self.table_name)
+    }
+}
+
+/// A `SELECT <cols_str> FROM <source> <rest>` statement split into its parts; `rest` holds
+/// the WHERE and ORDER BY clauses.
+struct SelectParts<'s> {
+    cols_str: &'s str,
+    source: FromSource<'s>,
+    rest: &'s str,
+}
+
+/// The operator joining two SELECTs of a compound SELECT.
+#[derive(Clone, Copy, PartialEq)]
+enum CompoundOperator {
+    UnionAll,
+    Union,
+    Intersect,
+    Except,
+}
+
+impl fmt::Display for CompoundOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompoundOperator::UnionAll => write!(f, "UNION ALL"),
+            CompoundOperator::Union => write!(f, "UNION"),
+            CompoundOperator::Intersect => write!(f, "INTERSECT"),
+            CompoundOperator::Except => write!(f, "EXCEPT"),
+        }
+    }
+}
+
+/// SELECTs joined by compound operators, applied left to right. `rest` holds the ORDER BY and
+/// LIMIT after the last SELECT, which apply to the combined rows rather than to that SELECT.
+struct Compound<'s> {
+    first: &'s str,
+    others: Vec<(CompoundOperator, &'s str)>,
+    rest: &'s str,
+}
+
+/// Splits `sql` at the compound operators outside parentheses and quotes, or gives `None` for
+/// a simple SELECT.
+fn split_compound(sql: &str) -> Result<Option<Compound<'_>>> {
+    let masked = mask_nested(sql);
+    let operator_regex = Regex::new(r"(?i)\b(UNION(\s+ALL)?|INTERSECT|EXCEPT)\b")?;
+    let operators: Vec<_> = operator_regex.captures_iter(&masked).collect();
+    let Some(first_operator) = operators.first().and_then(|caps| caps.get(0)) else {
+        return Ok(None);
+    };
+
+    let mut others = vec![];
+    let mut start = first_operator.end();
+    for (i, caps) in operators.iter().enumerate() {
+        let operator = match caps[1].to_ascii_uppercase().as_str() {
+            "INTERSECT" => CompoundOperator::Intersect,
+            "EXCEPT" => CompoundOperator::Except,
+            _ if caps.get(2).is_some() => CompoundOperator::UnionAll,
+            _ => CompoundOperator::Union,
+        };
+        let end = operators.get(i + 1).and_then(|caps| caps.get(0)).map_or(sql.len(), |m| m.start());
+        others.push((operator, &sql[start..end]));
+        start = operators.get(i + 1).and_then(|caps| caps.get(0)).map_or(sql.len(), |m| m.end());
+    }
+
+    // ORDER BY and LIMIT belong to the compound, so they are cut off the last SELECT.
+    let clause_regex = Regex::new(r"(?i)\b(ORDER\s+BY|LIMIT)\b")?;
+    let mut rest = "";
+    if let Some((_, last)) = others.last_mut() {
+        let offset = last.as_ptr() as usize - sql.as_ptr() as usize;
+        if let Some(m) = clause_regex.find(&masked[offset..offset + last.len()]) {
+            rest = &last[m.start()..];
+            *last = &last[..m.start()];
+        }
+    }
+
+    Ok(Some(Compound { first: &sql[..first_operator.start()], others, rest }))
+}
+
+/// Compares rows column by column under the BINARY collation.
+fn compare_rows(a: &[Value], b: &[Value]) -> Ordering {
+    a.iter().zip(b).map(|(a, b)| compare_values(a, b, Collation::Binary)).find(|o| o.is_ne()).unwrap_or(Ordering::Equal)
+}
+
+/// Combines the rows of the SELECTs either side of `operator`. All but UNION ALL drop
+/// duplicate rows and give the result in sorted order, as SQLite does. Of rows that compare
+/// equal but differ, such as `1` and `1.0`, the last one is kept, again as in SQLite.
+fn combine_rows(operator: CompoundOperator, left: Vec<Vec<Value>>, right: Vec<Vec<Value>>) -> Vec<Vec<Value>> {
+    let sorted_distinct = |mut rows: Vec<Vec<Value>>| {
+        rows.reverse();
+        rows.sort_by(|a, b| compare_rows(a, b));
+        rows.dedup_by(|a, b| compare_rows(a, b).is_eq());
+        rows
+    };
+
+    match operator {
+        CompoundOperator::UnionAll => left.into_iter().chain(right).collect(),
+        CompoundOperator::Union => sorted_distinct(left.into_iter().chain(right).collect()),
+        CompoundOperator::Intersect | CompoundOperator::Except => {
+            let right = sorted_distinct(right);
+            let keep = operator == CompoundOperator::Intersect;
+            sorted_distinct(left).into_iter()
+                .filter(|row| right.binary_search_by(|other| compare_rows(other, row)).is_ok() == keep)
+                .collect()
+        }
+    }
+}
+
+fn split_select(sql: &str) -> Result<SelectParts<'_>> {
+    let subquery_regex = Regex::new(r"(?is)^\s*SELECT\s+(.+?)\s+FROM\s*\(")?;
+    let from_regex = Regex::new(r"(?i)\bFROM\b")?;
+    // The outer query's own FROM must be the first one, not one inside a later subquery.
+    if let Some(caps) = subquery_regex.captures(sql).filter(|caps| !from_regex.is_match(&caps[1])) {
+        let open = caps.get(0).map_or(0, |m| m.end() - 1);
+        let close = closing_paren(sql, open)
+            .ok_or_else(|| SqliteError::UnsupportedSql("incomplete input".to_string()))?;
+
+        let (_, rest) = parse_alias(&sql[close + 1..])?;
+        if parse_join(rest)?.is_some() {
+            bail!(SqliteError::UnsupportedSql(JOINS_SUPPORTED.to_string()));
+        }
+        return Ok(SelectParts {
+            cols_str: caps.get(1).map_or("", |m| m.as_str()),
+            source: FromSource::Subquery(&sql[open + 1..close]),
+            rest,
+        });
+    }
+
+    let select_regex = Regex::new(
+        &format!(r"(?is)SELECT\s+(.+?)\s+FROM\s+(?:({0})\s*\.\s*)?({0})", IDENTIFIER_PATTERN)
+    )?;
+    if let Some(caps) = select_regex.captures(sql) {
+        let (alias, rest) = parse_alias(&sql[caps.get(0).map_or(0, |m| m.end())..])?;
+        let left = TableRef {
+            schema_name: caps.get(2).map(|m| unquote_identifier(m.as_str())),
+            table_name: caps.get(3).map_or("", |m| unquote_identifier(m.as_str())),
+            alias,
+        };
+        let (source, rest) = match parse_join(rest)? {
+            Some((right, on, rest)) => (FromSource::Join { left, right, on }, rest),
+            None => (FromSource::Table(left), rest),
+        };
+        return Ok(SelectParts { cols_str: caps.get(1).map_or("", |m| m.as_str()), source, rest });
+    }
+
+    let select_only_regex = Regex::new(r"(?is)^\s*SELECT\s+(.+?)\s*;?\s*$")?;
+    if let Some(body) = select_only_regex.captures(sql).and_then(|caps| caps.get(1)).map(|m| m.as_str()) {
+        let clause_regex = Regex::new(r"(?i)\b(WHERE|GROUP\s+BY|HAVING|ORDER\s+BY|LIMIT)\b")?;
+        let clauses_start = clause_regex.find(&mask_nested(body)).map_or(body.len(), |m| m.start());
+        return Ok(SelectParts { cols_str: body[..clauses_start].trim_end(), source: FromSource::None, rest: &body[clauses_start..] });
+    }
+
+    bail!(SqliteError::UnsupportedSql(format!("unsupported statement: {}", sql.trim())))
+}
+
+/// Splits off the `[AS] alias` that may follow a FROM clause's table or subquery, giving the
+/// alias and the text after it.
+fn parse_alias(rest: &str) -> Result<(Option<&str>, &str)> {
+    let alias_regex = Regex::new(&format!(r"(?is)^\s*(?:AS\s+)?({})", IDENTIFIER_PATTERN))?;
+    Ok(match alias_regex.captures(rest) {
+        Some(alias_caps) if !alias_caps[1].starts_with(|c: char| c.is_ascii_digit())
+            && !["WHERE", "GROUP", "HAVING", "ORDER", "LIMIT", "JOIN", "NATURAL", "LEFT", "RIGHT", "FULL", "INNER", "CROSS", "ON", "USING"]
+                .iter().any(|keyword| ident_eq(&alias_caps[1], keyword)) => {
+            let alias = alias_caps.get(1).map_or("", |m| unquote_identifier(m.as_str()));
+            (Some(alias), &rest[alias_caps.get(0).map_or(0, |m| m.end())..])
+        }
+        _ => (None, rest),
+    })
+}
+
+const JOINS_SUPPORTED: &str = "only inner joins of two tables are supported";
+
+/// Parses the second table of a join following the first one's alias, with the join's `ON`
+/// condition if it has one, and gives the text after them. Only inner joins of two tables are
+/// supported: outer and natural joins, `USING` and a third table fail.
+fn parse_join(rest: &str) -> Result<Option<(TableRef<'_>, Option<&str>, &str)>> {
+    let join_regex = Regex::new(r"(?i)^\s*(,|(NATURAL\s+)?((LEFT|RIGHT|FULL)(\s+OUTER)?\s+|INNER\s+|CROSS\s+)?JOIN\b)")?;
+    let Some(join_caps) = join_regex.captures(rest) else {
+        return Ok(None);
+    };
+    let table_regex = Regex::new(&format!(r"(?is)^\s*(?:({0})\s*\.\s*)?({0})", IDENTIFIER_PATTERN))?;
+    let after_join = &rest[join_caps.get(0).map_or(0, |m| m.end())..];
+    let table_caps = match table_regex.captures(after_join) {
+        Some(table_caps) if join_caps.get(2).is_none() && join_caps.get(4).is_none() => table_caps,
+        _ => bail!(SqliteError::UnsupportedSql(JOINS_SUPPORTED.to_string())),
+    };
+    let (alias, rest) = parse_alias(&after_join[table_caps.get(0).map_or(0, |m| m.end())..])?;
+    let right = TableRef {
+        schema_name: table_caps.get(1).map(|m| unquote_identifier(m.as_str())),
+        table_name: table_caps.get(2).map_or("", |m| unquote_identifier(m.as_str())),
+        alias,
+    };
+
+    let on_regex = Regex::new(r"(?is)^\s*ON\s+")?;
+    let (on, rest) = match on_regex.find(rest) {
+        Some(on_match) => {
+            let rest = &rest[on_match.end()..];
+            let clause_regex = Regex::new(r"(?i)\b(WHERE|GROUP\s+BY|HAVING|ORDER\s+BY|LIMIT)\b")?;
+            let masked = mask_nested(rest);
+            let on_end = clause_regex.find(&masked).map_or(rest.len(), |m| m.start());
+            if Regex::new(r"(?i)\bJOIN\b")?.is_match(&masked[..on_end]) {
+                bail!(SqliteError::UnsupportedSql(JOINS_SUPPORTED.to_string()));
+            }
+            let on = rest[..on_end].trim();
+            (Some(on.strip_suffix(';').unwrap_or(on).trim_end()), &rest[on_end..])
+        }
+        None => (None, rest),
+    };
+    if join_regex.is_match(rest) || Regex::new(r"(?i)^\s*USING\b")?.is_match(rest) {
+        bail!(SqliteError::UnsupportedSql(JOINS_SUPPORTED.to_string()));
+    }
+
+    Ok(Some((right, on, rest)))
+}
+
+/// Fails with SQLite's syntax error for `leftover`, text of a statement that no clause took,
+/// unless it is only whitespace and the closing `;`.
+fn check_consumed(leftover: &str) -> Result<()> {
+    let leftover = leftover.trim();
+    let leftover = leftover.strip_suffix(';').unwrap_or(leftover);
+    let tokens = expr::tokenize(leftover)?;
+    match tokens.first() {
+        None => Ok(()),
+        Some(_) if tokens.len() == 1 && ["WHERE", "HAVING"].iter().any(|keyword| ident_eq(leftover, keyword)) => {
+            bail!(SqliteError::UnsupportedSql("incomplete input".to_string()))
+        }
+        Some(token) => bail!(SqliteError::UnsupportedSql(format!("near \"{}\": syntax error", token))),
+    }
+}
+
+/// Names of the columns `sql` returns, worked out from its result column list without
+/// running it: `*` expands to the source's columns and aliases replace the expression text.
+fn result_column_names(db: &mut Database, sql: &str) -> Result<Vec<String>> {
+    if let Some((_, name, _)) = parse_header_pragma(sql)? {
+        return Ok(vec![name.to_string()]);
+    }
+    if let Some(compound) = split_compound(sql)? {
+        return result_column_names(db, compound.first);
+    }
+    let SelectParts { cols_str, source, .. } = split_select(sql)?;
+    let columns = match source {
+        FromSource::Subquery(subquery_sql) => result_column_names(db, subquery_sql)?.iter()
+            .map(|name| Column::from_strs(name, ""))
+            .collect(),
+        FromSource::None => vec![],
+        FromSource::Join { left, right, .. } => join_columns(db, &left, &right)?.0,
+        FromSource::Table(table) => {
+            let TableRef { schema_name, table_name, .. } = table;
+            let tables_info = &db.database_file(schema_name)?.tables_info;
+            if let Some(view) = find_view(tables_info, table_name) {
+                let column_names = match view.view_select()? {
+                    (_, Some(column_names)) => column_names,
+                    (view_sql, None) => result_column_names(db, &view_sql)?,
+                };
+                column_names.iter().map(|name| Column::from_strs(name, "")).collect()
+            } else {
+                let tinfo = find_table(tables_info, table_name)
+                    .ok_or_else(|| SqliteError::NoSuchTable(table_name.to_string()))?;
+                qualified_columns(tinfo, table.qualifier())
+            }
+        }
+    };
+
+    let (_, cols_str) = strip_distinct(cols_str)?;
+    Ok(parse_select_items(cols_str, &columns, &mut vec![])?.into_iter().map(|(name, _)| name).collect())
+}
+
+/// Splits a leading `DISTINCT` (or the default, `ALL`) off a result column list, giving whether
+/// it was `DISTINCT`.
+fn strip_distinct(cols_str: &str) -> Result<(bool, &str)> {
+    let quantifier_re = Regex::new(r"(?is)^\s*(DISTINCT|ALL)\s+(.+)$")?;
+    Ok(match quantifier_re.captures(cols_str) {
+        Some(caps) => (caps[1].eq_ignore_ascii_case("DISTINCT"), caps.get(2).map_or("", |m| m.as_str())),
+        None => (false, cols_str),
+    })
+}
+
+/// A SELECT parsed against the columns of its row source. Result columns, HAVING and ORDER
+/// BY read the values of `aggregates` from the columns after the rowid pseudo-column.
+struct Select {
+    distinct: bool,
+    items: Vec<(String, Expr)>,
+    aggregates: Vec<Aggregate>,
+    predicate: Option<Expr>,
+    group_by: Vec<Expr>,
+    having: Option<Expr>,
+    order_terms: Vec<OrderTerm>,
+    limit: Option<Limit>,
+}
+
+/// Parses the part of a SELECT around its row source: `cols_str` is the result column list and
+/// `rest` the text following the FROM clause (WHERE, GROUP BY, HAVING, ORDER BY and LIMIT).
+/// Subqueries in WHERE are run against `db` while parsing.
+fn parse_select(db: &mut Database, cols_str: &str, columns: &[Column], rest: &str) -> Result<Select> {
+    let (distinct, cols_str) = strip_distinct(cols_str)?;
+    let mut aggregates = vec![];
+    let items = parse_select_items(cols_str, columns, &mut aggregates)?;
+
+    // Each clause is searched for before the one that must follow it.
+    let masked = mask_nested(rest);
+    let clause_start = |pattern: &str, end: usize| -> Result<usize> {
+        Ok(Regex::new(pattern)?.find(&masked[..end]).map_or(end, |m| m.start()))
+    };
+    let limit_start = clause_start(r"(?i)\bLIMIT\b", rest.len())?;
+    let order_start = clause_start(r"(?i)\bORDER\s+BY\b", limit_start)?;
+    let having_start = clause_start(r"(?i)\bHAVING\b", order_start)?;
+    let group_start = clause_start(r"(?i)\bGROUP\s+BY\b", having_start)?;
+
+    let having_re = Regex::new(r"(?is)^\s*HAVING\s+(.+?)\s*;?\s*$")?;
+    let having = match having_re.captures(&rest[having_start..order_start]) {
+        Some(having_caps) => Some(parse_expr_with_aggregates(&having_caps[1], columns, &items, &mut aggregates)?),
+        None => {
+            check_consumed(&rest[having_start..order_start])?;
+            None
+        }
+    };
+    let group_by = parse_group_by(&rest[group_start..having_start], columns, &items)?;
+    let aggregate_query = !group_by.is_empty() || !aggregates.is_empty();
+    if having.is_some() && !aggregate_query {
+        bail!(SqliteError::UnsupportedSql("HAVING clause on a non-aggregate query".to_string()));
+    }
+
+    let order_terms = parse_order_by(&rest[order_start..limit_start], columns, &items, &mut aggregates)?;
+    // Which of the rows merged by DISTINCT would give the sort key is undefined otherwise.
+    if distinct && let Some(i) = order_terms.iter().position(|term| !items.iter().any(|(_, expr)| *expr == term.expr)) {
+        bail!(SqliteError::UnsupportedSql(format!("{} ORDER BY term does not match any column in the result set", ordinal(i + 1))));
+    }
+    if let Some(aggregate) = aggregates.first().filter(|_| !aggregate_query) {
+        bail!(SqliteError::UnsupportedSql(format!("misuse of aggregate: {}()", aggregate.func.name())));
+    }
+    let limit = parse_limit(&rest[limit_start..])?;
+
+    let where_re = Regex::new(r"(?is)^\s*WHERE\s+(.+?)\s*;?\s*$")?;
+    let predicate = match where_re.captures(&rest[..group_start]) {
+        Some(where_caps) => Some(parse_expr_with_subqueries(&where_caps[1], columns, &mut |sql| execute_sql_query(db, sql))?),
+        None => {
+            check_consumed(&rest[..group_start])?;
+            None
+        }
+    };
+
+    Ok(Select { distinct, items, aggregates, predicate, group_by, having, order_terms, limit })
+}
+
+/// Runs a parsed SELECT over the rows of `source`, which are described by `columns`. The
+/// clauses are applied as stages, in this order:
+///
+/// 1. WHERE filters the rows as they are read from the source.
+/// 2. GROUP BY splits them into groups; a query with aggregates but no GROUP BY is one group.
+///    Each group becomes a single row holding its first row's values and its aggregates.
+/// 3. HAVING filters those group rows.
+/// 4. DISTINCT drops rows whose result columns repeat an earlier row's.
+/// 5. ORDER BY sorts the rows, unless they were read from a table in rowid order.
+/// 6. LIMIT and OFFSET cut the sorted rows down.
+/// 7. The result columns are evaluated for the rows that are left.
+///
+/// ORDER BY terms naming a result column are resolved to its expression when parsing, so
+/// evaluating the result columns last gives the same rows while skipping those LIMIT drops.
+fn execute_select<'a>(select: Select, columns: &[Column], source: RowSource<'a>) -> Result<Rows<'a>> {
+    let Select { distinct, items, aggregates, predicate, group_by, having, order_terms, limit } = select;
+
+    // Only the columns the query reads are materialized; the others stay NULL in each row.
+    let mut used_col_idxs = vec![];
+    for expr in predicate.iter()
+        .chain(&group_by)
+        .chain(&having)
+        .chain(order_terms.iter().map(|term| &term.expr))
+        .chain(items.iter().map(|(_, expr)| expr))
+        .chain(aggregates.iter().filter_map(|aggregate| aggregate.arg.as_ref())) {
+        referenced_columns(expr, &mut used_col_idxs);
+    }
+    used_col_idxs.retain(|&idx| idx <= columns.len());
+    used_col_idxs.sort_unstable();
+    used_col_idxs.dedup();
+
+    // Sorting by rowid alone reads the table in that order, stopping at the LIMIT.
+    let aggregate_query = !group_by.is_empty() || !aggregates.is_empty();
+    let rowid_order = source.rowid_order(&order_terms, predicate.as_ref()).filter(|_| !aggregate_query && !distinct);
+
+    // Without stages that need every row, a table is read as the result is iterated.
+    let scan_order = match rowid_order {
+        Some(descending) => Some(descending),
+        None if order_terms.is_empty() && !aggregate_query && !distinct && source.index_lookup(predicate.as_ref()).is_none() => Some(false),
+        None => None,
+    };
+    if let Some(descending) = scan_order
+        && let RowSource::Table { file, page_size, tinfo, .. } = source {
+        let (column_names, items) = items.into_iter().unzip();
+        let cursor = RowCursor::new(file, page_size, tinfo, used_col_idxs, predicate, descending);
+        let scan = TableScan {
+            cursor,
+            items,
+            offset: limit.as_ref().map_or(0, |limit| limit.offset),
+            remaining: limit.as_ref().and_then(|limit| limit.count).unwrap_or(usize::MAX),
+        };
+        return Ok(Rows { column_names, rows: RowStream::Scan(Box::new(scan)) });
+    }
+    // Sorting a table for a LIMIT takes the rows from a cursor as it walks the table, so only
+    // those the LIMIT can reach are held at once.
+    if let Some(end) = limit.as_ref().and_then(Limit::end)
+        && !order_terms.is_empty() && rowid_order.is_none() && !aggregate_query && !distinct && having.is_none()
+        && source.index_lookup(predicate.as_ref()).is_none()
+        && let RowSource::Table { file, page_size, tinfo, .. } = source {
+        let mut cursor = RowCursor::new(file, page_size, tinfo, used_col_idxs, predicate, false);
+        let rows = top_k_rows(std::iter::from_fn(|| cursor.next_row()), &order_terms, end)?;
+        return limit_and_project(rows, limit.as_ref(), &items);
+    }
+    let mut rows = match rowid_order {
+        Some(descending) => source.rows_in_rowid_order(&used_col_idxs, predicate.as_ref(), descending, limit.as_ref().and_then(Limit::end))?,
+        None => source.rows(&used_col_idxs, predicate.as_ref())?,
+    };
+
+    if aggregate_query {
+        rows = group_rows(rows, &group_by, columns)?.into_iter()
+            .map(|group| {
+                let mut row = group.first().cloned().unwrap_or_default();
+                row.resize(columns.len() + 1, Value::Null);
+                for aggregate in &aggregates {
+                    row.push(aggregate_value(aggregate, &group, columns)?);
+                }
+                Ok(row)
+            })
+            .collect::<Result<_>>()?;
+    }
+    if let Some(having) = &having {
+        rows = rows.into_iter()
+            .filter_map(|row| match evaluate(having, &row) {
+                Ok(value) if is_truthy(&value) => Some(Ok(row)),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            })
+            .collect::<Result<_>>()?;
+    }
+
+    if distinct {
+        rows = distinct_rows(rows, &items, columns)?;
+    }
+
+    // With a LIMIT only the rows it can reach need sorting.
+    let rows = match limit.as_ref().and_then(Limit::end) {
+        _ if rowid_order.is_some() => rows,
+        Some(end) if !order_terms.is_empty() => top_k_rows(rows.into_iter().map(Ok), &order_terms, end)?,
+        _ => sort_rows(rows, &order_terms)?,
+    };
+    limit_and_project(rows, limit.as_ref(), &items)
+}
+
+/// Applies the LIMIT to rows already in their final order and evaluates the result columns.
+fn limit_and_project<'a>(rows: Vec<Vec<Value>>, limit: Option<&Limit>, items: &[(String, Expr)]) -> Result<Rows<'a>> {
+    let rows = match limit {
+        Some(limit) => limit.apply(rows),
+        None => rows,
+    };
+
+    // Each result column is evaluated on its own, so one column can appear several times.
+    let column_names = items.iter().map(|(name, _)| name.clone()).collect();
+    let rows = rows.iter()
+        .map(|row| items.iter().map(|(_, expr)| evaluate(expr, row)).collect::<Result<Vec<Value>>>())
+        .collect::<Result<_>>()?;
+
+    Ok(RowSet { column_names, rows }.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_PAGE_SIZE: usize = 512;
+
+    /// A database image with a bare header on page 1, followed by an interior page of
+    /// `page_type` for each entry of `children`. Each page's cells point to its children in
+    /// order, the last one in the right-most pointer.
+    fn interior_pages(page_type: u8, children: &[&[u32]]) -> Pager {
+        Pager::from_bytes(interior_pages_image(page_type, children)).unwrap()
+    }
+
+    fn interior_pages_image(page_type: u8, children: &[&[u32]]) -> Vec<u8> {
+        let mut image = vec![0u8; TEST_PAGE_SIZE * (children.len() + 1)];
+        image[..16].copy_from_slice(b"SQLite format 3\0");
+        image[16..18].copy_from_slice(&(TEST_PAGE_SIZE as u16).to_be_bytes());
+
+        for (i, page_children) in children.iter().enumerate() {
+            let page = &mut image[TEST_PAGE_SIZE * (i + 1)..TEST_PAGE_SIZE * (i + 2)];
+            let (right_child, cell_children) = page_children.split_last().expect("a page has a child");
+            page[0] = page_type;
+            page[3..5].copy_from_slice(&(cell_children.len() as u16).to_be_bytes());
+            page[5..7].copy_from_slice(&(TEST_PAGE_SIZE as u16 / 2).to_be_bytes());
+            page[8..12].copy_from_slice(&right_child.to_be_bytes());
+            for (j, child) in cell_children.iter().enumerate() {
+                let cell_offset = TEST_PAGE_SIZE / 2 + j * 16;
+                page[12 + j * 2..14 + j * 2].copy_from_slice(&(cell_offset as u16).to_be_bytes());
+                page[cell_offset..cell_offset + 4].copy_from_slice(&child.to_be_bytes());
+                // A table cell's key is a rowid; an index cell's a record of a key and a rowid.
+                let key: &[u8] = if page_type == 0x05 { &[j as u8] } else { &[5, 3, 1, 1, j as u8, j as u8] };
+                page[cell_offset + 4..cell_offset + 4 + key.len()].copy_from_slice(key);
+            }
+        }
+
+        image
+    }
+
+    #[test]
+    fn pager_reads_an_in_memory_image() {
+        let image = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let mut pager = Pager::from_bytes(image.clone()).unwrap();
+        assert_eq!((pager.page_size, pager.usable_size), (4096, 4096));
+        assert!(pager.wal.is_none());
+        assert_eq!(pager.page_count().unwrap() as usize, image.len() / 4096);
+
+        let mut page = vec![0; 4096];
+        pager.seek(SeekFrom::Start(4096)).unwrap();
+        pager.read_exact(&mut page).unwrap();
+        assert_eq!(page, image[4096..8192]);
+        assert_eq!(pager.pages_read, 1);
+
+        assert!(matches!(Pager::from_bytes(vec![0; 100]).err().and_then(|e| e.downcast().ok()), Some(SqliteError::NotADatabase)));
+    }
+
+    #[test]
+    fn uri_paths() {
+        assert_eq!(uri_path("sample.db"), "sample.db");
+        assert_eq!(uri_path("file:sample.db?mode=ro"), "sample.db");
+        assert_eq!(uri_path("file:///tmp/data.db#part"), "/tmp/data.db");
+        assert_eq!(uri_path("file://localhost/tmp/data.db"), "/tmp/data.db");
+    }
+
+    #[test]
+    fn table_info_lists_columns_and_affinities() {
+        assert_eq!(SCHEMA_TABLE.to_string(), "table sqlite_schema (rootpage 1): type TEXT, name TEXT, tbl_name TEXT, rootpage INTEGER, sql TEXT");
+
+        let db = DatabaseFile::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/typed.db")).unwrap();
+        let typed = find_table(&db.tables_info, "typed").unwrap().to_string();
+        assert!(typed.starts_with("table typed (rootpage 2): id INTEGER, count INTEGER, label TEXT, notes TEXT, data BLOB"), "{}", typed);
+        assert!(typed.ends_with(", ratio REAL, price NUMERIC, when NUMERIC"), "{}", typed);
+    }
+
+    #[test]
+    fn varint_round_trips() {
+        let mut values = vec![0, 1, u64::MAX, u64::MAX - 1, i64::MAX as u64, (-1i64) as u64];
+        // Each side of every 7-bit group boundary, and the largest 64-bit value.
+        for bits in (7..=63).step_by(7).chain([64]) {
+            let boundary = 1u128 << bits;
+            for value in [boundary - 1, boundary, boundary + 1] {
+                if let Ok(value) = u64::try_from(value) {
+                    values.push(value);
+                }
+            }
+        }
+
+        for value in values {
+            let bytes = write_varint(value);
+            assert_eq!(read_varint(&bytes), (value, bytes.len()), "{:#x}", value);
+        }
+    }
+
+    #[test]
+    fn varint_lengths_are_canonical() {
+        for (value, len) in [(0, 1), (0x7f, 1), (0x80, 2), (0x3fff, 2), (0x4000, 3), (0x00ff_ffff_ffff_ffff, 8), (0x0100_0000_0000_0000, 9), (u64::MAX, 9)] {
+            assert_eq!(write_varint(value).len(), len, "{:#x}", value);
+        }
+        assert_eq!(write_varint(u64::MAX), [0xff; 9]);
+        // Bytes after the varint are not part of it.
+        assert_eq!(read_varint(&[0x81, 0x00, 0x7f]), (0x80, 2));
+    }
+
+    #[test]
+    fn field_offsets_skip_the_preceding_fields() {
+        // NULL, a 1-byte integer, a real, the constant 0, empty text, 3 bytes of text, an 8-byte
+        // integer and a 1-byte blob.
+        let serial_types = [0, 1, 7, 8, 13, 19, 6, 14];
+        let offsets: Vec<usize> = (0..=serial_types.len()).map(|i| field_offset(&serial_types, 9, i).unwrap()).collect();
+        assert_eq!(offsets, [9, 9, 10, 18, 18, 18, 21, 29, 30]);
+        // Two text fields of nearly 2^63 bytes each overflow the offset of the field after them.
+        assert_eq!(field_offset(&[u64::MAX, u64::MAX, 1], 20, 2), None);
+
+        // A record of 42, 'abc' and 1.5, with its 4-byte header.
+        let mut record = vec![4, 1, 19, 7, 42];
+        record.extend_from_slice(b"abc");
+        record.extend_from_slice(&1.5f64.to_be_bytes());
+        let serial_types = [1, 19, 7];
+        assert_eq!(record[field_offset(&serial_types, 4, 0).unwrap()], 42);
+        assert_eq!(&record[field_offset(&serial_types, 4, 1).unwrap()..][..3], b"abc");
+        assert_eq!(&record[field_offset(&serial_types, 4, 2).unwrap()..], 1.5f64.to_be_bytes());
+    }
+
+    /// Page 2 of a two-page image holds, at offset 100, a table cell with rowid 1 whose record
+    /// header lists `serial_types`, followed by a one-byte body.
+    fn cell_with_serial_types(serial_types: &[u64]) -> Pager {
+        let mut header: Vec<u8> = serial_types.iter().flat_map(|&serial_type| write_varint(serial_type)).collect();
+        header.insert(0, header.len() as u8 + 1);
+        let mut image = vec![0u8; TEST_PAGE_SIZE * 2];
+        image[..16].copy_from_slice(b"SQLite format 3\0");
+        image[16..18].copy_from_slice(&(TEST_PAGE_SIZE as u16).to_be_bytes());
+        let cell = [&[header.len() as u8 + 1, 1][..], &header, &[7]].concat();
+        image[TEST_PAGE_SIZE + 100..TEST_PAGE_SIZE + 100 + cell.len()].copy_from_slice(&cell);
+        Pager::from_bytes(image).unwrap()
+    }
+
+    #[test]
+    fn huge_serial_types_are_corrupt() {
+        let corrupt_on_page_2 = |result: Result<(Record, i64)>| {
+            let error = SqliteError::from(result.err().expect("the read should fail"));
+            assert!(matches!(error, SqliteError::Corrupt { page: Some(2), .. }), "{:?}", error);
+        };
+
+        // A text field of nearly 2^63 bytes runs past the payload.
+        let mut file = cell_with_serial_types(&[u64::MAX, 1]);
+        corrupt_on_page_2(get_cell_data(&mut file, TEST_PAGE_SIZE as u64, 100, false));
+        // Two of them put the field after them past the end of the address space.
+        let mut file = cell_with_serial_types(&[u64::MAX, u64::MAX, 1]);
+        corrupt_on_page_2(get_cell_fields(&mut file, TEST_PAGE_SIZE as u64, 100, false, Some(&[2])));
+
+        let mut file = cell_with_serial_types(&[1]);
+        let (record, rowid) = get_cell_data(&mut file, TEST_PAGE_SIZE as u64, 100, false).unwrap();
+        assert_eq!((record.data, rowid), (vec![vec![7]], 1));
+        let mut payload = Payload::new(2, vec![0; 4], 4, 0);
+        assert!(payload.read(&mut file, usize::MAX, 2).is_err());
+    }
+
+    #[test]
+    fn keywords_match_in_any_case_and_identifiers_keep_theirs() {
+        use expr::{Token, tokenize};
+
+        assert_eq!(tokenize("NaMe iS nOt NuLl AnD x").unwrap(), [
+            Token::Identifier("NaMe".to_string()),
+            Token::Keyword("IS"),
+            Token::Keyword("NOT"),
+            Token::Keyword("NULL"),
+            Token::Keyword("AND"),
+            Token::Identifier("x".to_string()),
+        ]);
+        // Words only meaningful in context stay identifiers.
+        assert_eq!(tokenize("like End").unwrap(), [Token::Identifier("like".to_string()), Token::Identifier("End".to_string())]);
+    }
+
+    fn assert_cycle_at(result: Result<()>, page_num: u32) {
+        let error = SqliteError::from(result.expect_err("the walk should fail"));
+        assert!(matches!(&error, SqliteError::Corrupt { page: Some(page), .. } if *page == page_num), "{:?}", error);
+        assert!(error.to_string().contains(&format!("page cycle detected at page {}", page_num)), "{}", error);
+    }
+
+    #[test]
+    fn table_walk_detects_cycle() {
+        // Page 2 points to pages 3 and 4, and page 3 back to page 2.
+        let mut file = interior_pages(0x05, &[&[3, 4], &[2], &[2]]);
+        let mut leaves = 0;
+        assert_cycle_at(walk_table_btree(&mut file, TEST_PAGE_SIZE as u32, 2, |_, _| {
+            leaves += 1;
+            Ok(())
+        }), 2);
+        assert_eq!(leaves, 0);
+    }
+
+    #[test]
+    fn index_walk_detects_cycle() {
+        // Page 2 points to page 3, whose first cell points back to page 3 itself.
+        let mut file = interior_pages(0x02, &[&[3], &[3, 4], &[2]]);
+        assert_cycle_at(walk_btree(&mut file, TEST_PAGE_SIZE as u32, 2, true, &mut HashSet::new(), &mut |_, _, _| Ok(())), 3);
+
+        // Walking the entries in key order, as index lookups do, and checking integrity.
+        let mut keys = vec![];
+        assert_cycle_at(walk_index_btree(&mut file, TEST_PAGE_SIZE as u32, 2, |_| true, |key, _| {
+            keys.push(key.clone());
+            true
+        }), 3);
+        assert!(keys.is_empty());
+        let filter = Filter { collation: Collation::Binary, value: Value::Integer(0) };
+        assert_cycle_at(get_rowids_index(&mut file, 2, TEST_PAGE_SIZE as u32, &filter).map(|_| ()), 3);
+        let mut check = IntegrityCheck { visited: HashSet::new(), last_rowid: None };
+        assert_cycle_at(check_btree(&mut file, TEST_PAGE_SIZE as u32, 2, true, &mut check), 3);
+    }
+
+    #[test]
+    fn index_walk_visits_entries_in_key_order() {
+        // Page 2's two entries, with rowids 0 and 1, follow leaves 3 and 4, and its right-most
+        // child is leaf 5. Each leaf has a single entry whose rowid is its page number.
+        let mut image = interior_pages_image(0x02, &[&[3, 4, 5]]);
+        image.resize(TEST_PAGE_SIZE * 5, 0);
+        for leaf in 3..=5 {
+            let page = &mut image[TEST_PAGE_SIZE * (leaf - 1)..TEST_PAGE_SIZE * leaf];
+            page[0] = 0x0a;
+            page[3..5].copy_from_slice(&1u16.to_be_bytes());
+            page[8..10].copy_from_slice(&400u16.to_be_bytes());
+            page[400..406].copy_from_slice(&[5, 3, 1, 1, 0, leaf as u8]);
+        }
+        let mut file = Pager::from_bytes(image).unwrap();
+
+        let mut entries = vec![];
+        walk_index_btree(&mut file, TEST_PAGE_SIZE as u32, 2, |_| true, |_, rowid| {
+            entries.push(rowid);
+            true
+        }).unwrap();
+        // Each interior entry comes after the subtree before it.
+        assert_eq!(entries, [3, 0, 4, 1, 5]);
+
+        // Stopping at the first interior entry skips the rest of the tree.
+        let mut entries = vec![];
+        walk_index_btree(&mut file, TEST_PAGE_SIZE as u32, 2, |key| key.is_some(), |_, rowid| {
+            entries.push(rowid);
+            rowid != 0
+        }).unwrap();
+        assert_eq!(entries, [3, 0]);
+    }
+}
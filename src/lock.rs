@@ -0,0 +1,59 @@
+use std::fs::File;
+use std::io;
+
+// SQLite locks byte ranges just past the first gigabyte of the file (the lock-byte page), see
+// https://www.sqlite.org/lockingv3.html. A writer holds PENDING_BYTE while it waits for readers
+// to finish and the whole shared range once it has the EXCLUSIVE lock.
+const PENDING_BYTE: i64 = 0x4000_0000;
+const RESERVED_BYTE: i64 = PENDING_BYTE + 1;
+const SHARED_FIRST: i64 = PENDING_BYTE + 2;
+const SHARED_SIZE: i64 = 510;
+
+/// The strongest lock another process holds on a database file, as far as readers care.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockState {
+    /// No lock, or only SHARED locks held by other readers.
+    Unlocked,
+    /// A writer has started a transaction but not touched the file yet; reading is still safe.
+    Reserved,
+    /// A writer holds or is about to take the EXCLUSIVE lock and may be rewriting pages.
+    Exclusive,
+}
+
+/// Checks SQLite's lock bytes without taking any lock ourselves. Locks held by this
+/// process are not reported, since POSIX record locks are per process.
+#[cfg(unix)]
+pub fn lock_state(file: &File) -> io::Result<LockState> {
+    if is_write_locked(file, PENDING_BYTE, 1)? || is_write_locked(file, SHARED_FIRST, SHARED_SIZE)? {
+        Ok(LockState::Exclusive)
+    } else if is_write_locked(file, RESERVED_BYTE, 1)? {
+        Ok(LockState::Reserved)
+    } else {
+        Ok(LockState::Unlocked)
+    }
+}
+
+#[cfg(not(unix))]
+pub fn lock_state(_file: &File) -> io::Result<LockState> {
+    Ok(LockState::Unlocked)
+}
+
+/// Whether a read lock on `len` bytes at `start` would be refused because another process
+/// holds a write lock on part of the range.
+#[cfg(unix)]
+fn is_write_locked(file: &File, start: i64, len: i64) -> io::Result<bool> {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: `flock` is plain old data, so all zeroes is a valid value.
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_RDLCK as _;
+    lock.l_whence = libc::SEEK_SET as _;
+    lock.l_start = start as _;
+    lock.l_len = len as _;
+
+    // SAFETY: the descriptor is open for the lifetime of `file` and `lock` outlives the call.
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETLK, &mut lock) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(lock.l_type != libc::F_UNLCK as _)
+}
@@ -1,803 +1,591 @@
-use std::fmt::format;
-use anyhow::{Result, bail, Context};
+use anyhow::{Context, Result, bail};
+use codecrafters_sqlite::{Database, Value, csv_field, is_internal_table, strip_comments};
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
-use regex::Regex;
-
-struct Column {
-    name: String,
-    tpe: SqlType,
-}
-
-struct Record {
-    data: Vec<Vec<u8>>
-}
-
-#[derive(Debug)]
-enum PageType {
-    InteriorIndex = 0x2,
-    InteriorTable = 0x5,
-    LeafIndex = 0xa,
-    LeafTable = 0xd
-}
-
-impl PageType {
-    fn from_u8(value: u8) -> Result<Self> {
-        match value {
-            0x02 => Ok(PageType::InteriorIndex),
-            0x05 => Ok(PageType::InteriorTable),
-            0x0a => Ok(PageType::LeafIndex),
-            0x0d => Ok(PageType::LeafTable),
-            _ => bail!("Received wrong value for page type")
-        }
-    }
-}
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::time::Instant;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-enum SqlType {
-    Integer,
-    Text,
-    Real,
-    Blob,
-    Null,
-}
-
-impl SqlType {
-    fn from_str(s: &str) -> Self {
-        match s.to_lowercase().as_str() {
-            "integer" | "int" => SqlType::Integer,
-            "text" | "varchar" | "char" => SqlType::Text,
-            "real" | "float" | "double" => SqlType::Real,
-            "blob" => SqlType::Blob,
-            _ => SqlType::Text,
+enum OutputMode {
+    List,
+    Json,
+    Csv,
+    /// Each value as `column=CLASS:literal`, showing the storage class it was read with.
+    Typed,
+}
+
+struct Shell {
+    mode: OutputMode,
+    headers: bool,
+    timer: bool,
+    /// What NULL prints as in list and CSV output; empty by default, as in the sqlite3 shell.
+    null_value: String,
+    /// Whether reading commands stops at the first error.
+    bail: bool,
+    /// What goes between the fields of a row in list and CSV output.
+    separator: String,
+}
+
+impl Shell {
+    /// Switches the output mode, resetting the separator to the mode's default like sqlite3.
+    fn set_mode(&mut self, mode: OutputMode) {
+        self.mode = mode;
+        match mode {
+            OutputMode::List | OutputMode::Typed => self.separator = "|".to_string(),
+            OutputMode::Csv => self.separator = ",".to_string(),
+            OutputMode::Json => {}
         }
     }
 
-    fn to_string(&self) -> &str {
-        match self {
-            SqlType::Integer => "INTEGER",
-            SqlType::Text => "TEXT",
-            SqlType::Real => "REAL",
-            SqlType::Blob => "BLOB",
-            SqlType::Null => "NULL",
+    fn display_value(&self, value: &Value) -> String {
+        match value {
+            Value::Null => self.null_value.clone(),
+            value => value.to_string(),
         }
     }
 }
 
-impl Column {
-    fn from_strs(name: &str, col_type: &str) -> Self {
-        Column {
-            name: name.to_string(),
-            tpe: SqlType::from_str(col_type),
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
         }
     }
+    out.push('"');
+    out
 }
 
-struct TableInfo {
-    tpe: String,
-    tbl_name: String,
-    rootpage: u32,
-    columns: Vec<Column>,
-    index_col: Option<String>
-}
-
-fn read_varint(data: &[u8]) -> (u64, usize) {
-    let mut i = 0;
-    let mut val: u64 = 0;
-
-    while i < 8 && i < data.len() {
-        let current_byte = data[i];
-        i += 1;
-
-        val = (val << 7) | ((current_byte & 0x7F) as u64);
-
-        if current_byte & 0x80 == 0 {
-            return (val, i);
-        }
+fn json_value(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Integer(_) | Value::Real(_) => value.to_string(),
+        Value::Text(_) | Value::Blob(_) => json_string(&value.to_string()),
     }
+}
 
-    if i < data.len() {
-        let current_byte = data[i];
-        val = (val << 8) | (current_byte as u64);
-        i += 1;
+fn print_json_objects(objects: &[Vec<(&str, String)>]) {
+    for (i, fields) in objects.iter().enumerate() {
+        let members: Vec<String> = fields.iter()
+            .map(|(key, value)| format!("{}:{}", json_string(key), value))
+            .collect();
+        let open = if i == 0 { "[" } else { "" };
+        let close = if i + 1 == objects.len() { "]" } else { "," };
+        println!("{}{{{}}}{}", open, members.join(","), close);
     }
+}
 
-    (val, i)
+/// A value with its storage class, e.g. `INT:5` or `TEXT:'abc'`; NULL is just `NULL`.
+fn typed_value(value: &Value) -> String {
+    let class = match value {
+        Value::Null => return "NULL".to_string(),
+        Value::Integer(_) => "INT",
+        Value::Real(_) => "REAL",
+        Value::Text(_) => "TEXT",
+        Value::Blob(_) => "BLOB",
+    };
+    format!("{}:{:?}", class, value)
 }
 
-fn get_serial_type_size(serial_type: u64) -> usize {
-    match serial_type {
-        0 | 8 | 9 => 0,
-        1 => 1,
-        2 => 2,
-        3 => 3,
-        4 => 4,
-        5 => 6,
-        6 | 7 => 8,
-        n if n >= 12 && n % 2 == 0 => ((n - 12) / 2) as usize,
-        n if n >= 13 && n % 2 == 1 => ((n - 13) / 2) as usize,
-        _ => 0,
+fn execute_dbinfo_command(db: &Database) -> Result<()> {
+    println!("database page size: {}", db.page_size());
+    println!("number of tables: {}", db.schema_entry_count());
+    println!("auto-vacuum: {}", db.auto_vacuum());
+    println!("autovacuum top root: {}", db.largest_root_page());
+    println!("incremental vacuum: {}", db.incremental_vacuum() as u8);
+    if let Some(frame) = db.wal_commit_frame() {
+        println!("wal commit frame: {}", frame);
     }
+
+    Ok(())
 }
 
-fn extract_integer(buffer: &[u8]) -> Result<i64> {
-    match buffer.len() {
-        0 => Ok(0),
-        1 => Ok(buffer[0] as i8 as i64),
-        2 => {
-            let bytes: [u8; 2] = buffer[0..2].try_into()
-                .context("Failed to read 2 bytes")?;
-            Ok(i16::from_be_bytes(bytes) as i64)
-        }
-        3 => {
-            let b1 = buffer[0] as i32;
-            let b2 = buffer[1] as i32;
-            let b3 = buffer[2] as i32;
-            let mut value = (b1 << 16) | (b2 << 8) | b3;
-            if value & 0x800000 != 0 {
-                value |= 0xFF000000u32 as i32;
-            }
-            Ok(value as i64)
-        }
-        4 => {
-            let bytes: [u8; 4] = buffer[0..4].try_into()
-                .context("Failed to read 4 bytes")?;
-            Ok(i32::from_be_bytes(bytes) as i64)
-        }
-        5 => {
-            let mut bytes = [0u8; 8];
-            bytes[2..8].copy_from_slice(&buffer);
-            let mut value = i64::from_be_bytes(bytes);
-            if value & 0x800000000000 != 0 {
-                value |= 0xFFFF000000000000u64 as i64;
+fn execute_tables_command(db: &Database, mode: OutputMode) -> Result<()> {
+    match mode {
+        OutputMode::List | OutputMode::Csv | OutputMode::Typed => {
+            for table in db.table_names() {
+                print!("{} ", table);
             }
-            Ok(value)
-        }
-        6 => {
-            let bytes: [u8; 8] = buffer[0..8]
-                .try_into()
-                .context("Failed to read 8 bytes")?;
-            Ok(i64::from_be_bytes(bytes))
+            println!();
         }
-        8 => Ok(0),
-        9 => Ok(1),
-        _ => bail!("Invalid buffer length for integer type: {}", buffer.len()),
-    }
-}
-
-fn extract_real(buffer: &[u8]) -> Result<f64> {
-    match buffer.len() {
-        8 => {
-            let bytes: [u8; 8] = buffer[0..8]
-                .try_into()
-                .context("Failed to read 8 bytes for float")?;
-            Ok(f64::from_be_bytes(bytes))
+        OutputMode::Json => {
+            let objects: Vec<Vec<(&str, String)>> = db.schema_objects().iter()
+                .filter(|o| (o.kind == "table" || o.kind == "view") && !is_internal_table(o.name))
+                .map(|o| vec![
+                    ("name", json_string(o.name)),
+                    ("type", json_string(o.kind)),
+                    ("sql", json_string(o.sql)),
+                ])
+                .collect();
+            print_json_objects(&objects);
         }
-        _ => bail!("Invalid buffer length for floating type: {}", buffer.len()),
     }
-}
 
-fn extract_string(buffer: &[u8]) -> String {
-    String::from_utf8_lossy(buffer).to_string()
+    Ok(())
 }
 
-fn parse_columns(sql_str: &str) -> Result<Vec<Column>> {
-    if sql_str.is_empty() {
-        return Ok(vec![]);
-    }
-
-    let create_re = Regex::new(r#"(?si)CREATE\s+TABLE\s+["']?\w+["']?\s*\((.*)\)"#)?;
-
-    if let Some(caps) = create_re.captures(sql_str) {
-        let cols_section = &caps[1];
-        let col_re = Regex::new(r#"(?i)(?:"([^"]+)"|(\w+))\s+(integer|text|real|blob|int|varchar|char|float|double)\b"#)?;
-
-        let columns: Vec<Column> = col_re.captures_iter(cols_section)
-            .map(|c| {
-                let name = if let Some(quoted) = c.get(1) {
-                    quoted.as_str()
-                } else {
-                    &c[2]
-                };
-                Column::from_strs(name, &c[3])
-            })
-            .collect();
+fn execute_schema_command(db: &Database, mode: OutputMode) -> Result<()> {
+    let objects = db.schema_objects();
+    let objects = objects.iter().filter(|o| !o.sql.is_empty());
 
-        return Ok(columns);
+    match mode {
+        OutputMode::List | OutputMode::Csv | OutputMode::Typed => {
+            for object in objects {
+                println!("{};", object.sql);
+            }
+        }
+        OutputMode::Json => {
+            let objects: Vec<Vec<(&str, String)>> = objects
+                .map(|o| vec![
+                    ("name", json_string(o.name)),
+                    ("type", json_string(o.kind)),
+                    ("sql", json_string(o.sql)),
+                ])
+                .collect();
+            print_json_objects(&objects);
+        }
     }
 
-    Ok(vec![])
+    Ok(())
 }
 
-fn get_cell_data(file: &mut File, page_offset: u64, cell_offset: u16, index_cell: bool) -> Result<(Record, u64)> {
-    let absolute_offset = page_offset + cell_offset as u64;
-
-    file.seek(SeekFrom::Start(absolute_offset))?;
-    let mut payload_size_buf = [0u8; 9];
-    file.read_exact(&mut payload_size_buf)?;
-    let (payload_size, payload_size_bytes) = read_varint(&payload_size_buf);
-
-    let mut rowid = 0;
-    let mut total_header_bytes = payload_size_bytes;
-
-    if !index_cell {
-        file.seek(SeekFrom::Start(absolute_offset + payload_size_bytes as u64))?;
-        let mut rowid_buf = [0u8; 9];
-        file.read_exact(&mut rowid_buf)?;
-        let (rowid_val, rowid_bytes) = read_varint(&rowid_buf);
-        rowid = rowid_val;
-        total_header_bytes += rowid_bytes;
-    }
-
-    file.seek(SeekFrom::Start(absolute_offset + total_header_bytes as u64))?;
-
-    let mut record_buffer = vec![0u8; payload_size as usize];
-    file.read_exact(&mut record_buffer)
-        .context(format!("Failed to read record ({} bytes) at cell offset {}", payload_size, cell_offset))?;
-
-    let (header_size, mut header_pos) = read_varint(&record_buffer);
-
-    let mut body_offset = header_size as usize;
-    let mut data: Vec<Vec<u8>> = vec![];
+fn execute_hexdump_command(db: &mut Database, page_arg: Option<&str>) -> Result<()> {
+    let page_num: u32 = match page_arg {
+        Some(arg) => arg.parse().context(format!("Invalid page number: {}", arg))?,
+        None => bail!("Usage: .hexdump <page>"),
+    };
+    let page = db.read_page(page_num)?;
 
-    while header_pos < header_size as usize {
-        let (serial_type, bytes) = read_varint(&record_buffer[header_pos..]);
-        let serial_type_size = get_serial_type_size(serial_type);
-        data.push(Vec::from(&record_buffer[body_offset..body_offset + serial_type_size]));
-        body_offset += serial_type_size;
-        header_pos += bytes;
+    for (i, chunk) in page.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let (left, right) = hex.split_at(hex.len().min(8));
+        let ascii: String = chunk.iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        println!("{:08x}  {:<23}  {:<23}  |{}|", i * 16, left.join(" "), right.join(" "), ascii);
     }
 
-    Ok((Record { data }, rowid))
+    Ok(())
 }
 
-fn read_tbl_info(file: &mut File, cell_offset: u16) -> Result<TableInfo> {
-    let (record, _) = get_cell_data(file, 0, cell_offset, false)?;
-
-    if record.data.len() < 5 {
-        bail!("Expected at least 5 columns in sqlite_schema, found {}", record.data.len());
+fn parse_toggle(command: &str, arg: Option<&str>) -> Result<bool> {
+    match arg {
+        Some("on") => Ok(true),
+        Some("off") => Ok(false),
+        _ => bail!("Usage: {} on|off", command),
     }
-
-    let type_str = extract_string(&record.data[0]);
-    let name_str = extract_string(&record.data[1]);
-    let tbl_name_str = extract_string(&record.data[2]);
-    let rootpage_int = extract_integer(&record.data[3])? as u32;
-    let sql_str = extract_string(&record.data[4]);
-
-    let index_col = if type_str == "index" {
-        let index_re = Regex::new(r"(?i)CREATE\s+INDEX\s+\w+\s+on\s+\w+\s*\(\s*(\w+)\s*\)")?;
-        index_re.captures(&sql_str)
-            .map(|caps| caps[1].to_string())
-    } else {
-        None
-    };
-
-    Ok(TableInfo {
-        tpe: type_str,
-        tbl_name: tbl_name_str,
-        rootpage: rootpage_int,
-        columns: parse_columns(&sql_str)?,
-        index_col
-    })
 }
 
-fn execute_dbinfo_command(args: Vec<String>) -> Result<()> {
-    let mut file = File::open(&args[1])?;
-    let mut header = [0; 100];
-    file.read_exact(&mut header)?;
+fn execute_sql_query_command(db: &mut Database, sql: &str, shell: &Shell) -> Result<()> {
+    // Rows are read from the database as they are printed, so the time covers both.
+    let start = Instant::now();
+    let result = db.query(sql)?;
+    let column_names = result.column_names().to_vec();
+    let mut rows = result.peekable();
 
-    let page_size: u16 = u16::from_be_bytes([header[16], header[17]]);
-
-    let mut page_header = [0; 8];
-    file.read_exact(&mut page_header)?;
-    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]);
-
-    println!("database page size: {}", page_size);
-    println!("number of tables: {}", n_cells);
-
-    Ok(())
-}
-
-fn get_tables_info(file: &mut File) -> Result<Vec<TableInfo>> {
-    file.seek(SeekFrom::Start(100))?;
-
-    let mut page_header = [0; 8];
-    file.read_exact(&mut page_header)?;
-    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]);
-
-    let n_bytes = (n_cells * 2) as usize;
-    let mut cell_array_contents = vec![0u8; n_bytes];
-    file.read_exact(&mut cell_array_contents)?;
-
-    let mut result = vec![];
-    let mut i = 0;
-    while i < n_bytes {
-        let cell_offset = u16::from_be_bytes([cell_array_contents[i], cell_array_contents[i + 1]]);
-        result.push(read_tbl_info(file, cell_offset)?);
-        i += 2
+    // Like the sqlite3 shell, an empty result prints nothing at all, not even the header row.
+    let headers = shell.headers && rows.peek().is_some();
+    match shell.mode {
+        OutputMode::List => {
+            if headers {
+                println!("{}", column_names.join(&shell.separator));
+            }
+            for row in rows {
+                let row_vec: Vec<String> = row?.iter().map(|v| shell.display_value(v)).collect();
+                println!("{}", row_vec.join(&shell.separator));
+            }
+        }
+        OutputMode::Json => {
+            let rows = rows.collect::<Result<Vec<_>, _>>()?;
+            let objects: Vec<Vec<(&str, String)>> = rows.iter()
+                .map(|row| column_names.iter()
+                    .map(|name| name.as_str())
+                    .zip(row.iter().map(json_value))
+                    .collect())
+                .collect();
+            print_json_objects(&objects);
+        }
+        OutputMode::Csv => {
+            if headers {
+                let header: Vec<String> = column_names.iter().map(|name| csv_field(name, &shell.separator)).collect();
+                println!("{}", header.join(&shell.separator));
+            }
+            for row in rows {
+                let row_vec: Vec<String> = row?.iter().map(|v| csv_field(&shell.display_value(v), &shell.separator)).collect();
+                println!("{}", row_vec.join(&shell.separator));
+            }
+        }
+        OutputMode::Typed => {
+            // Every value carries its column name, so there is no header row.
+            for row in rows {
+                let row_vec: Vec<String> = column_names.iter().zip(row?)
+                    .map(|(name, value)| format!("{}={}", name, typed_value(&value)))
+                    .collect();
+                println!("{}", row_vec.join(&shell.separator));
+            }
+        }
     }
+    let elapsed = start.elapsed();
 
-    Ok(result)
-}
-
-fn execute_tables_command(args: Vec<String>) -> Result<()> {
-    let mut file = File::open(&args[1])?;
-    let v = get_tables_info(&mut file)?;
-
-    for table in v {
-        print!("{} ", table.tbl_name);
+    if shell.timer {
+        println!("Run Time: real {:.6} pages {}", elapsed.as_secs_f64(), db.pages_read());
     }
-    println!();
 
     Ok(())
 }
 
-fn get_rows_from_leaf_page(file: &mut File, current_page: u32, page_size: u16) -> Result<u64> {
-    let page_offset: u64 = (page_size as u32 * (current_page - 1)) as u64;
-
-    file.seek(SeekFrom::Start(page_offset))?;
-    let mut page_header = [0; 8];
-    file.read_exact(&mut page_header)?;
-    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]) as u64;
+/// Splits a dot-command into its arguments. As in the sqlite3 shell an argument may be quoted
+/// to hold spaces, and escapes such as `\t` are resolved inside double quotes.
+fn command_args(command: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut chars = command.chars().peekable();
 
-    Ok(n_cells)
-}
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
 
-fn count_rows_in_page(file: &mut File, current_page: u32, page_size: u16) -> Result<u64> {
-    let page_offset: u64 = (page_size as u32 * (current_page - 1)) as u64;
-    file.seek(SeekFrom::Start(page_offset))?;
-
-    let mut page_type_buf = [0; 1];
-    file.read_exact(&mut page_type_buf)
-        .context(format!("Failed to read page type at page {}", current_page))?;
-    let page_type_enum = PageType::from_u8(page_type_buf[0])?;
-
-    match page_type_enum {
-        PageType::InteriorTable => {
-            file.seek(SeekFrom::Start(page_offset))?;
-            let mut page_header = [0; 12];
-            file.read_exact(&mut page_header)
-                .context(format!("Failed to read page header at interior page {}", current_page))?;
-            let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]);
-
-            let n_bytes = (n_cells * 2) as usize;
-            let mut cell_array_contents = vec![0u8; n_bytes];
-            file.read_exact(&mut cell_array_contents)
-                .context(format!("Failed to read cell array ({} bytes) at interior page {}", n_bytes, current_page))?;
-
-            let mut total_count = 0u64;
-            for i in (0..n_bytes).step_by(2) {
-                let cell_offset = u16::from_be_bytes([cell_array_contents[i], cell_array_contents[i + 1]]);
-                let (left_page, _) = extract_interior_cell_data(file, page_offset, cell_offset, false)?;
-                total_count += count_rows_in_page(file, left_page, page_size)?;
+        let mut arg = String::new();
+        if c == '\'' || c == '"' {
+            chars.next();
+            while let Some(next) = chars.next() {
+                match next {
+                    _ if next == c => break,
+                    '\\' if c == '"' => match chars.next() {
+                        Some('t') => arg.push('\t'),
+                        Some('n') => arg.push('\n'),
+                        Some('r') => arg.push('\r'),
+                        Some(other) => arg.push(other),
+                        None => arg.push('\\'),
+                    },
+                    _ => arg.push(next),
+                }
+            }
+        } else {
+            while let Some(&next) = chars.peek() {
+                if next.is_whitespace() {
+                    break;
+                }
+                arg.push(next);
+                chars.next();
             }
-
-            let right_page = u32::from_be_bytes([page_header[8], page_header[9], page_header[10], page_header[11]]);
-            total_count += count_rows_in_page(file, right_page, page_size)?;
-
-            Ok(total_count)
-        }
-        PageType::LeafTable => {
-            get_rows_from_leaf_page(file, current_page, page_size)
         }
-        _ => bail!("Unhandled page type!")
+        args.push(arg);
     }
-}
 
-fn get_table_count(file: &mut File, tinfo: &TableInfo, page_size: u16) -> Result<u64> {
-    count_rows_in_page(file, tinfo.rootpage, page_size)
+    args
 }
 
-fn get_page_data_with_filter(file: &mut File, col_idxs: &Vec<usize>, col_types: &Vec<SqlType>, page_size: u16, page_num: u32, filter_col: &Option<usize>, filter_val: &Option<String>) -> Result<Vec<Vec<String>>> {
-    let page_offset: u64 = (page_size as u32 * (page_num - 1)) as u64;
-
-    file.seek(SeekFrom::Start(page_offset))?;
-    let mut page_header = [0; 8];
-    file.read_exact(&mut page_header)
-        .context(format!("Failed to read page header at leaf page {}", page_num))?;
-    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]) as u64;
-
-    let n_bytes = (n_cells * 2) as usize;
-    let mut cell_array_contents = vec![0u8; n_bytes];
-    file.read_exact(&mut cell_array_contents)
-        .context(format!("Failed to read cell array ({} bytes) at leaf page {}", n_bytes, page_num))?;
-
-    let mut results: Vec<Vec<String>> = vec![Vec::new(); col_idxs.len()];
-    for i in (0..n_bytes).step_by(2) {
-        let cell_offset = u16::from_be_bytes([cell_array_contents[i], cell_array_contents[i + 1]]);
-        let (record, rowid) = get_cell_data(file, page_offset, cell_offset, false)?;
-
-        if let (Some(fcol), Some(fval)) = (filter_col, filter_val) {
-            let record_filter_val = extract_string(&record.data[*fcol]);
-            if !record_filter_val.eq_ignore_ascii_case(fval) {
-                continue;
-            }
-        }
-
-        for (coli, col_idx) in col_idxs.iter().enumerate() {
-            let value = if *col_idx == 0 && record.data[0].is_empty() {
-                rowid.to_string()
-            } else {
-                let record_idx = *col_idx;
-
-                match &col_types[coli] {
-                    SqlType::Integer => extract_integer(&record.data[record_idx])?.to_string(),
-                    SqlType::Text => extract_string(&record.data[record_idx]),
-                    SqlType::Real => extract_real(&record.data[record_idx])?.to_string(),
-                    _ => bail!("Unsupported data type: {:?}", col_types[coli])
-                }
+fn execute_command(db: &mut Database, command: &str, shell: &mut Shell) -> Result<()> {
+    let args = if command.starts_with('.') { command_args(command) } else { vec![] };
+    let mut words = args.iter().map(String::as_str);
+    match words.next().unwrap_or("") {
+        ".dbinfo" => {
+            execute_dbinfo_command(db)?;
+        },
+        ".tables" => {
+            execute_tables_command(db, shell.mode)?;
+        },
+        ".schema" => {
+            execute_schema_command(db, shell.mode)?;
+        },
+        ".check" => {
+            db.integrity_check()?;
+            println!("ok");
+        },
+        ".hexdump" => {
+            execute_hexdump_command(db, words.next())?;
+        },
+        ".headers" => {
+            shell.headers = parse_toggle(".headers", words.next())?;
+        },
+        ".timer" => {
+            shell.timer = parse_toggle(".timer", words.next())?;
+        },
+        ".bail" => {
+            shell.bail = parse_toggle(".bail", words.next())?;
+        },
+        ".read" => {
+            execute_read_command(db, words.next(), shell)?;
+        },
+        ".nullvalue" => {
+            let Some(null_value) = words.next() else {
+                bail!("Usage: .nullvalue STRING");
             };
-
-            results[coli].push(value);
+            shell.null_value = null_value.to_string();
+        },
+        ".separator" => {
+            let Some(separator) = words.next() else {
+                bail!("Usage: .separator COL");
+            };
+            shell.separator = separator.to_string();
+        },
+        ".mode" => {
+            let mode = match words.next() {
+                Some("list") => OutputMode::List,
+                Some("csv") => OutputMode::Csv,
+                Some("json") => OutputMode::Json,
+                Some("typed") => OutputMode::Typed,
+                _ => bail!("mode should be one of: csv json list typed"),
+            };
+            shell.set_mode(mode);
+        },
+        _ => {
+            execute_sql_query_command(db, command, shell)?;
         }
     }
 
-    Ok(results)
+    Ok(())
 }
 
-fn extract_interior_cell_data(file: &mut File, page_offset: u64, cell_offset: u16, index_cell: bool) -> Result<(u32, u64)> {
-    let absolute_offset = page_offset + cell_offset as u64;
-    file.seek(SeekFrom::Start(absolute_offset))?;
-
-    let mut left_page_buf = [0; 4];
-    file.read_exact(&mut left_page_buf)
-        .context(format!("Failed to read left page pointer at cell offset {} (absolute: {})", cell_offset, absolute_offset))?;
-
-    if !index_cell {
-        let mut key_varint_buf = [0; 9];
-        file.read_exact(&mut key_varint_buf)?;
-        return Ok((u32::from_be_bytes(left_page_buf), read_varint(&key_varint_buf).0));
-    }
-
-    Ok((u32::from_be_bytes(left_page_buf), 0u64))
-}
+/// Splits buffered input into the complete statements it holds, each ended by a `;` outside
+/// quotes and comments, and the unfinished text after the last one.
+fn split_statements(input: &str) -> (Vec<&str>, &str) {
+    let mut statements = vec![];
+    let mut quote: Option<char> = None;
+    // The text closing the comment being skipped, and where the comment's body starts.
+    let mut comment: Option<(&str, usize)> = None;
+    let mut start = 0;
 
-fn get_cols_data_with_filter(file: &mut File, page_size: u16, current_page: u32, col_idxs: &Vec<usize>, col_types: &Vec<SqlType>, filter_col: &Option<usize>, filter_val: &Option<String>, columns: &mut Vec<Vec<String>>) -> Result<()> {
-    let page_offset: u64 = (page_size as u32 * (current_page - 1)) as u64;
-    file.seek(SeekFrom::Start(page_offset))?;
-
-    let mut page_type_buf = [0; 1];
-    file.read_exact(&mut page_type_buf)
-        .context(format!("Failed to read page type at page {}", current_page))?;
-    let page_type_enum = PageType::from_u8(page_type_buf[0])?;
-
-    match page_type_enum {
-        PageType::InteriorTable => {
-            file.seek(SeekFrom::Start(page_offset))?;
-            let mut page_header = [0; 12];
-            file.read_exact(&mut page_header)
-                .context(format!("Failed to read page header at interior page {}", current_page))?;
-            let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]);
-
-            let n_bytes = (n_cells * 2) as usize;
-            let mut cell_array_contents = vec![0u8; n_bytes];
-            file.read_exact(&mut cell_array_contents)
-                .context(format!("Failed to read cell array ({} bytes) at interior page {}", n_bytes, current_page))?;
-
-            let mut i = 0;
-            while i < n_bytes {
-                let cell_offset = u16::from_be_bytes([cell_array_contents[i], cell_array_contents[i + 1]]);
-                let (left_page, _) = extract_interior_cell_data(file, page_offset, cell_offset, false)?;
-                get_cols_data_with_filter(file, page_size, left_page, col_idxs, col_types, filter_col, filter_val, columns)?;
-                i += 2
+    for (i, c) in input.char_indices() {
+        if let Some((close, body)) = comment {
+            if i >= body && input[body..i + c.len_utf8()].ends_with(close) {
+                comment = None;
             }
-
-            let right_page = u32::from_be_bytes([page_header[8], page_header[9], page_header[10], page_header[11]]);
-            get_cols_data_with_filter(file, page_size, right_page, col_idxs, col_types, filter_col, filter_val, columns)?;
-
-            Ok(())
+            continue;
         }
-        PageType::LeafTable => {
-            let page_data = get_page_data_with_filter(file, col_idxs, col_types, page_size, current_page, filter_col, filter_val)?;
-            for (col_idx, col_data) in page_data.into_iter().enumerate() {
-                columns[col_idx].extend(col_data);
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '[') => quote = Some(']'),
+            (None, '-') if input[i..].starts_with("--") => comment = Some(("\n", i + 2)),
+            (None, '/') if input[i..].starts_with("/*") => comment = Some(("*/", i + 2)),
+            (None, ';') => {
+                statements.push(&input[start..i]);
+                start = i + 1;
             }
-            Ok(())
+            _ => {}
         }
-        _ => bail!("Unhandled page type!")
     }
-}
 
-fn find_child_page_for_rowid(file: &mut File, page_offset: u64, rowid: u64) -> Result<u32> {
-    file.seek(SeekFrom::Start(page_offset))?;
-    let mut page_header = [0; 12];
-    file.read_exact(&mut page_header)?;
-    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]);
-
-    let n_bytes = (n_cells * 2) as usize;
-    let mut cell_array_contents = vec![0u8; n_bytes];
-    file.read_exact(&mut cell_array_contents)?;
-
-    let mut i = 0;
-    while i < n_bytes {
-        let cell_offset = u16::from_be_bytes([cell_array_contents[i], cell_array_contents[i + 1]]);
-        let (left_child, cell_key) = extract_interior_cell_data(file, page_offset, cell_offset, false)?;
-        if rowid <= cell_key {
-            return Ok(left_child);
+    (statements, &input[start..])
+}
+
+/// Whether `sql` holds nothing but whitespace and comments.
+fn is_blank(sql: &str) -> bool {
+    strip_comments(sql).trim().is_empty()
+}
+
+/// Runs the commands read from `input`. SQL may span several lines and runs once a `;` ends
+/// it, while dot-commands run as soon as their line is read. Errors are reported and reading
+/// goes on, unless `.bail on` is in effect; returns whether any command failed. For a script
+/// (`.read`), errors name its path and the line and text of the failing statement.
+fn run_commands(db: &mut Database, shell: &mut Shell, input: &mut dyn BufRead, interactive: bool, script: Option<&str>) -> Result<bool> {
+    let mut buffer = String::new();
+    let mut failed = false;
+    let (mut line_num, mut start_line) = (0, 1);
+    // Reports a failing command and returns whether it failed.
+    let run = |db: &mut Database, shell: &mut Shell, command: &str, start_line: usize| {
+        let Err(e) = execute_command(db, command.trim(), shell) else {
+            return false;
+        };
+        match script {
+            Some(path) => eprintln!("Error: {} line {}: {:#}\n  {}", path, start_line, e, command.trim()),
+            None => eprintln!("Error: {:#}", e),
         }
-        i += 2
-    }
+        true
+    };
 
-    Ok(u32::from_be_bytes([page_header[8], page_header[9], page_header[10], page_header[11]]))
-}
+    loop {
+        if interactive {
+            print!("{}", if is_blank(&buffer) { "sqlite> " } else { "   ...> " });
+            io::stdout().flush()?;
+        }
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        line_num += 1;
 
-fn find_row_in_leaf(file: &mut File, page_offset: u64, col_idxs: &Vec<usize>, col_types: &Vec<SqlType>, tinfo: &TableInfo, rowid: u64) -> Result<Vec<String>> {
-    file.seek(SeekFrom::Start(page_offset))?;
-    let mut page_header = [0; 8];
-    file.read_exact(&mut page_header)?;
-    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]) as u64;
-
-    let n_bytes = (n_cells * 2) as usize;
-    let mut cell_array_contents = vec![0u8; n_bytes];
-    file.read_exact(&mut cell_array_contents)?;
-
-    for i in (0..n_bytes).step_by(2) {
-        let cell_offset = u16::from_be_bytes([cell_array_contents[i], cell_array_contents[i + 1]]);
-
-        let (record, cell_rowid) = get_cell_data(file, page_offset, cell_offset, false)?;
-
-        if cell_rowid == rowid {
-            let mut results: Vec<String> = vec![];
-            for (coli, col_idx) in col_idxs.iter().enumerate() {
-                let value = if *col_idx == 0 && record.data[0].is_empty() {
-                    rowid.to_string()
-                } else {
-                    match &col_types[coli] {
-                        SqlType::Integer => extract_integer(&record.data[*col_idx])?.to_string(),
-                        SqlType::Text => extract_string(&record.data[*col_idx]),
-                        SqlType::Real => extract_real(&record.data[*col_idx])?.to_string(),
-                        _ => bail!("Unsupported data type: {:?}", col_types[coli])
-                    }
-                };
-                results.push(value);
+        if is_blank(&buffer) && line.trim_start().starts_with('.') {
+            buffer.clear();
+            if matches!(line.split_whitespace().next(), Some(".quit" | ".exit")) {
+                break;
+            }
+            failed |= run(db, shell, &line, line_num);
+        } else {
+            if is_blank(&buffer) {
+                start_line = line_num;
             }
-            return Ok(results);
+            buffer.push_str(&line);
+            let (statements, unfinished) = split_statements(&buffer);
+            for statement in statements.into_iter().filter(|statement| !is_blank(statement)) {
+                failed |= run(db, shell, statement, start_line);
+                start_line = line_num;
+            }
+            buffer = unfinished.to_string();
         }
-    }
-
-    bail!("Rowid {} not found in leaf page", rowid)
-}
-
-fn find_row_by_rowid(file: &mut File, curr_page: u32, page_size: u16, col_idxs: &Vec<usize>, col_types: &Vec<SqlType>, tinfo: &TableInfo, rowid: u64) -> Result<Vec<String>> {
-    let page_offset = (page_size as u32 * (curr_page - 1)) as u64;
-    file.seek(SeekFrom::Start(page_offset))?;
-
-    let mut page_type_buf = [0; 1];
-    file.read_exact(&mut page_type_buf)?;
-    let page_type = PageType::from_u8(page_type_buf[0])?;
 
-    match page_type {
-        PageType::InteriorTable => {
-            let child_page = find_child_page_for_rowid(file, page_offset, rowid)?;
-            find_row_by_rowid(file, child_page, page_size, col_idxs, col_types, tinfo, rowid)
+        if failed && shell.bail {
+            return Ok(true);
         }
-        PageType::LeafTable => {
-            find_row_in_leaf(file, page_offset, col_idxs, col_types, tinfo, rowid)
-        }
-        _ => bail!("Unexpected page type in table btree")
     }
-}
-
-fn get_rows_by_rowids(file: &mut File, page_size: u16, col_idxs: &Vec<usize>, col_types: &Vec<SqlType>, rowids: &Vec<u64>, tinfo: &TableInfo) -> Result<Vec<Vec<String>>> {
-    let mut results = Vec::new();
 
-    for &rowid in rowids {
-        let row = find_row_by_rowid(file, tinfo.rootpage, page_size, col_idxs, col_types, tinfo, rowid)?;
-        results.push(row);
+    // Like the sqlite3 shell, a last statement without a semicolon still runs at end of input.
+    if !is_blank(&buffer) {
+        failed |= run(db, shell, &buffer, start_line);
     }
 
-    Ok(results)
+    Ok(failed)
 }
 
-fn get_index_page_data(file: &mut File, index_curr_page: u32, page_size: u16, index_col: &Column, index_val: &str) -> Result<Vec<u64>> {
-    let page_offset: u64 = (page_size as u32 * (index_curr_page - 1)) as u64;
-
-    file.seek(SeekFrom::Start(page_offset))?;
-    let mut page_header = [0; 8];
-    file.read_exact(&mut page_header)
-        .context(format!("Failed to read page header at leaf page {}", index_curr_page))?;
-    let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]) as u64;
-
-    let n_bytes = (n_cells * 2) as usize;
-    let mut cell_array_contents = vec![0u8; n_bytes];
-    file.read_exact(&mut cell_array_contents)
-        .context(format!("Failed to read cell array ({} bytes) at leaf page {}", n_bytes, index_curr_page))?;
-
-    let mut result: Vec<u64> = vec![];
-    for i in (0..n_bytes).step_by(2) {
-        let cell_offset = u16::from_be_bytes([cell_array_contents[i], cell_array_contents[i + 1]]);
-        let (record, _) = get_cell_data(file, page_offset, cell_offset, true)?;
-        let value = extract_string(&record.data[0]);
-        let rowid = extract_integer(&record.data[1])? as u64;
-
-        if value.eq_ignore_ascii_case(index_val) {
-            result.push(rowid);
-        }
+/// Reads commands from stdin.
+fn run_shell(db: &mut Database, shell: &mut Shell) -> Result<()> {
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+    if run_commands(db, shell, &mut stdin.lock(), interactive, None)? {
+        std::process::exit(1);
     }
-
-    Ok(result)
+    Ok(())
 }
 
-fn get_rowids_index(file: &mut File, index_curr_page: u32, page_size: u16, index_col: &Column, index_val: &str, rowids: &mut Vec<u64>) -> Result<()> {
-    let page_offset: u64 = (page_size as u32 * (index_curr_page - 1)) as u64;
-    file.seek(SeekFrom::Start(page_offset))?;
-
-    let mut page_type_buf = [0; 1];
-    file.read_exact(&mut page_type_buf)
-        .context(format!("Failed to read page type at index page {}", index_curr_page))?;
-    let page_type_enum = PageType::from_u8(page_type_buf[0])?;
-
-    match page_type_enum {
-        PageType::InteriorIndex => {
-            file.seek(SeekFrom::Start(page_offset))?;
-            let mut page_header = [0; 12];
-            file.read_exact(&mut page_header)
-                .context(format!("Failed to read page header at interior index page {}", index_curr_page))?;
-            let n_cells = u16::from_be_bytes([page_header[3], page_header[4]]);
-
-            let n_bytes = (n_cells * 2) as usize;
-            let mut cell_array_contents = vec![0u8; n_bytes];
-            file.read_exact(&mut cell_array_contents)
-                .context(format!("Failed to read cell array ({} bytes) at interior index page {}", n_bytes, index_curr_page))?;
-
-            let mut i = 0;
-            while i < n_bytes {
-                let cell_offset = u16::from_be_bytes([cell_array_contents[i], cell_array_contents[i + 1]]);
-                let (left_page, _) = extract_interior_cell_data(file, page_offset, cell_offset, true)?;
-                get_rowids_index(file, left_page, page_size, index_col, index_val, rowids)?;
-                i += 2
-            }
-
-            let right_page = u32::from_be_bytes([page_header[8], page_header[9], page_header[10], page_header[11]]);
-            get_rowids_index(file, right_page, page_size, index_col, index_val, rowids)?;
-            Ok(())
-        }
-        PageType::LeafIndex => {
-            let page_rowids = get_index_page_data(file, index_curr_page, page_size, index_col, index_val)?;
-            rowids.extend(page_rowids);
-            Ok(())
+/// Runs the commands in a script file. Failing commands are reported as they happen and then
+/// fail `.read` itself; with `.bail on` the rest of the script is skipped.
+fn execute_read_command(db: &mut Database, path: Option<&str>, shell: &mut Shell) -> Result<()> {
+    let Some(path) = path else {
+        bail!("Usage: .read FILE");
+    };
+    let file = File::open(path).with_context(|| format!("cannot open \"{}\"", path))?;
+    if run_commands(db, shell, &mut BufReader::new(file), false, Some(path))? {
+        match shell.bail {
+            true => bail!("stopped reading {} at the first error", path),
+            false => bail!("errors while reading {}", path),
         }
-        _ => bail!("Unhandled page type!")
     }
-}
 
-fn get_cols_data_with_index(file: &mut File, tinfo: &TableInfo, page_size: u16, col_idxs: &Vec<usize>, col_types: &Vec<SqlType>, index_rootpage: u32, index_col: &Column, index_val: &str) -> Result<Vec<Vec<String>>> {
-    let mut rowids: Vec<u64> = vec![];
-    get_rowids_index(file, index_rootpage, page_size, index_col, index_val, & mut rowids)?;
-
-    Ok(get_rows_by_rowids(file, page_size, col_idxs, col_types, &rowids, &tinfo)?)
-}
-
-fn find_index_root_page(tables_info: &[TableInfo], table_name: &str) -> Option<u32> {
-    for entry in tables_info {
-        if entry.tpe == "index" && entry.tbl_name == table_name {
-            return Some(entry.rootpage);
-        }
-    }
-    None
+    Ok(())
 }
 
-fn execute_sql_query_command(args: &Vec<String>) -> Result<()> {
-    let mut file = File::open(&args[1])?;
-    let tables_info = get_tables_info(&mut file)?;
-
-    file.seek(SeekFrom::Start(0))?;
-    let mut header = [0; 100];
-    file.read_exact(&mut header)
-        .context("Failed to read database header")?;
-
-    let page_size: u16 = u16::from_be_bytes([header[16], header[17]]);
-
-    let count_regex = Regex::new(
-        r"(?i)SELECT\s+COUNT\s*\(\s*\*\s*\)\s+FROM\s+(\w+)"
-    )?;
-    if let Some(caps) = count_regex.captures(&args[2]) {
-        let table_name = caps[1].to_string();
-        for tinfo in &tables_info {
-            if tinfo.tbl_name.eq(&table_name) {
-                println!("{}", get_table_count(& mut file, tinfo, page_size)?);
-                return Ok(());
+/// What the command line asks for: the shell settings its options give, the database to open
+/// and the commands to run, in order.
+struct CliOptions {
+    shell: Shell,
+    db_path: String,
+    commands: Vec<String>,
+}
+
+/// Parses the arguments after the program name. Options may be spelled with one dash or two,
+/// as in the sqlite3 shell. The database is given by `--db PATH` or as the first positional
+/// argument; commands come from `--query SQL` and the remaining positional arguments.
+fn parse_cli(args: impl IntoIterator<Item = String>) -> Result<CliOptions> {
+    let mut shell = Shell { mode: OutputMode::List, headers: false, timer: false, null_value: String::new(), bail: false, separator: "|".to_string() };
+    let mut db_path = None;
+    let mut commands = vec![];
+    // Where the first positional argument went in `commands`; without `--db` it is the database.
+    let mut first_positional = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let option = arg.strip_prefix('-').filter(|option| option.starts_with('-')).unwrap_or(&arg);
+        let mut value = |name: &str| args.next().with_context(|| format!("missing argument to {}", name));
+        match option {
+            "-json" => shell.set_mode(OutputMode::Json),
+            "-list" => shell.set_mode(OutputMode::List),
+            "-csv" => shell.set_mode(OutputMode::Csv),
+            "-typed" => shell.set_mode(OutputMode::Typed),
+            "-separator" => shell.separator = value(option)?,
+            "-header" => shell.headers = true,
+            "-noheader" => shell.headers = false,
+            "-nullvalue" => shell.null_value = value(option)?,
+            "-db" => db_path = Some(value(option)?),
+            "-query" => commands.push(value(option)?),
+            _ => {
+                first_positional.get_or_insert(commands.len());
+                commands.push(arg);
             }
         }
     }
 
-    let select_regex = Regex::new(
-        r"(?i)SELECT\s+(.+?)\s+FROM\s+(\w+)"
-    )?;
-    if let Some(caps) = select_regex.captures(&args[2]) {
-        let cols_str = &caps[1];
-        let table_name = caps[2].to_string();
-
-        let tinfo = tables_info
-            .iter()
-            .find(|t| t.tbl_name == table_name)
-            .ok_or_else(|| anyhow::anyhow!("Table '{}' not found", table_name))?;
-
-        let col_names: Vec<String> = cols_str
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .collect();
-
-        let mut col_idxs: Vec<usize> = vec![];
-        let mut col_types: Vec<SqlType> = vec![];
-        for column_name in &col_names {
-            for (idx, col) in tinfo.columns.iter().enumerate() {
-                if col.name.eq(column_name) {
-                    col_types.push(col.tpe);
-                    col_idxs.push(idx);
-                    break;
-                }
-            }
-        }
-
-        let where_re = Regex::new(r#"(?i)WHERE\s+(\w+)\s*=\s*['"]([^'"]+)['"]"#)?;
-        let mut filter_col: Option<usize> = None;
-        let mut filter_val: Option<String> = None;
-
-        if let Some(caps) = where_re.captures(&args[2]) {
-            let col_name = caps[1].to_string();
-            filter_val = Some(caps[2].to_string());
+    let db_path = match (db_path, first_positional) {
+        (Some(db_path), _) => db_path,
+        (None, Some(i)) => commands.remove(i),
+        (None, None) => bail!("Missing <database path>"),
+    };
 
-            for (idx, col) in tinfo.columns.iter().enumerate() {
-                if col.name == col_name {
-                    filter_col = Some(idx);
-                    break;
-                }
-            }
+    Ok(CliOptions { shell, db_path, commands })
+}
 
-            let index_info = tables_info.iter()
-                .find(|t| t.tpe == "index"
-                    && t.tbl_name == table_name
-                    && t.index_col.as_ref().map_or(false, |c| c.eq_ignore_ascii_case(&col_name)));
+fn main() -> Result<()> {
+    let CliOptions { mut shell, db_path, commands } = parse_cli(std::env::args().skip(1))?;
+    let mut db = Database::open(&db_path)?;
 
-            if let Some(index) = index_info {
-                let index_col = tinfo.columns.iter()
-                    .find(|c| c.name.eq_ignore_ascii_case(&col_name))
-                    .ok_or_else(|| anyhow::anyhow!("Column not found"))?;
+    // Without commands, they are read from stdin instead.
+    if commands.is_empty() {
+        return run_shell(&mut db, &mut shell);
+    }
 
-                let rows = get_cols_data_with_index(&mut file, tinfo, page_size, &col_idxs, &col_types, index.rootpage, index_col, filter_val.as_ref().unwrap())?;
+    // Like the sqlite3 shell, every command is run in order, so dot-commands such as
+    // `.headers on` affect the commands that follow them.
+    for command in &commands {
+        execute_command(&mut db, command, &mut shell)?;
+    }
 
-                for row in rows {
-                    println!("{}", row.join("|"));
-                }
-                return Ok(());
-            }
-        }
+    Ok(())
+}
 
-        let mut columns = vec![Vec::new(); col_idxs.len()];
-        get_cols_data_with_filter(&mut file, page_size, tinfo.rootpage, &col_idxs, &col_types, &filter_col, &filter_val, &mut columns)?;
-        let n_rows = columns[0].len();
-        for i in 0..n_rows {
-            let row_vec: Vec<&str> = columns.iter().map(|col| col[i].as_str()).collect();
-            println!("{}", row_vec.join("|"));
-        }
+#[cfg(test)]
+mod tests {
+    use super::{CliOptions, OutputMode, parse_cli, split_statements};
 
-        return Ok(());
+    fn parse(args: &[&str]) -> anyhow::Result<CliOptions> {
+        parse_cli(args.iter().map(|arg| arg.to_string()))
     }
 
-    bail!("Failed to find table name")
-}
+    #[test]
+    fn statements_end_at_semicolons_outside_quotes_and_comments() {
+        let (statements, rest) = split_statements("SELECT 'a;b'; SELECT [c;d] -- e;\nFROM t; /* ; */ SELECT 3");
+        assert_eq!(statements, ["SELECT 'a;b'", " SELECT [c;d] -- e;\nFROM t"]);
+        assert_eq!(rest, " /* ; */ SELECT 3");
 
-fn main() -> Result<()> {
-    let args = std::env::args().collect::<Vec<_>>();
-    match args.len() {
-        0 | 1 => bail!("Missing <database path> and <command>"),
-        2 => bail!("Missing <command>"),
-        _ => {}
+        let (statements, rest) = split_statements("SELECT name,\ncolor\nFROM apples;\n");
+        assert_eq!(statements, ["SELECT name,\ncolor\nFROM apples"]);
+        assert_eq!(rest, "\n");
     }
 
-    let command = &args[2];
-    match command.as_str() {
-        ".dbinfo" => {
-            execute_dbinfo_command(args)?;
-        },
-        ".tables" => {
-            execute_tables_command(args)?;
-        },
-        _ => {
-            execute_sql_query_command(&args)?;
+    #[test]
+    fn flags_and_positionals_give_the_same_options() {
+        let forms: [&[&str]; 4] = [
+            &["sample.db", "SELECT 1"],
+            &["--db", "sample.db", "--query", "SELECT 1"],
+            &["--query", "SELECT 1", "-db", "sample.db"],
+            &["-db", "sample.db", "SELECT 1"],
+        ];
+        for args in forms {
+            let options = parse(args).unwrap();
+            assert_eq!((options.db_path.as_str(), options.commands), ("sample.db", vec!["SELECT 1".to_string()]), "{:?}", args);
         }
+
+        // Without `--db` the first positional is the database, wherever the options are.
+        let options = parse(&["--csv", "--query", ".tables", "sample.db", "SELECT 2", "--header"]).unwrap();
+        assert_eq!(options.db_path, "sample.db");
+        assert_eq!(options.commands, [".tables", "SELECT 2"]);
+        assert_eq!(options.shell.mode, OutputMode::Csv);
+        assert!(options.shell.headers);
     }
 
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn missing_database_or_option_value() {
+        assert_eq!(parse(&[]).err().unwrap().to_string(), "Missing <database path>");
+        assert_eq!(parse(&["--query", "SELECT 1"]).err().unwrap().to_string(), "Missing <database path>");
+        assert_eq!(parse(&["sample.db", "--query"]).err().unwrap().to_string(), "missing argument to -query");
+        assert_eq!(parse(&["--db"]).err().unwrap().to_string(), "missing argument to -db");
+    }
+}
@@ -0,0 +1,28 @@
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use crate::{Row, Value};
+
+/// NULL is a unit (`null` in JSON), numbers and text their natural counterparts and blobs a
+/// byte string, which JSON serializers write as an array of numbers.
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Null => serializer.serialize_unit(),
+            Value::Integer(value) => serializer.serialize_i64(*value),
+            Value::Real(value) => serializer.serialize_f64(*value),
+            Value::Text(value) => serializer.serialize_str(value),
+            Value::Blob(value) => serializer.serialize_bytes(value),
+        }
+    }
+}
+
+/// A row is a map from column name to value, in column order, like an object of the shell's
+/// `-json` output; a `Vec<Row>` from `Rows::into_rows` is then a sequence of them.
+impl Serialize for Row {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.values.len()))?;
+        for (name, value) in self.column_names.iter().zip(&self.values) {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
@@ -0,0 +1,135 @@
+use anyhow::{Context, Result, bail};
+use crate::SqliteError;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+const WAL_HEADER_SIZE: u64 = 32;
+const WAL_FRAME_HEADER_SIZE: u64 = 24;
+
+pub struct Wal {
+    file: File,
+    pages: HashMap<u32, u64>,
+    commit_frame: Option<u32>,
+    /// The database size in pages as of the last commit frame.
+    db_size: Option<u32>,
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([buffer[offset], buffer[offset + 1], buffer[offset + 2], buffer[offset + 3]])
+}
+
+fn wal_checksum(data: &[u8], seed: (u32, u32), big_endian: bool) -> (u32, u32) {
+    let (mut s0, mut s1) = seed;
+
+    for chunk in data.chunks_exact(8) {
+        let (x0, x1) = if big_endian {
+            (read_u32(chunk, 0), read_u32(chunk, 4))
+        } else {
+            (u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+             u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]))
+        };
+        s0 = s0.wrapping_add(x0).wrapping_add(s1);
+        s1 = s1.wrapping_add(x1).wrapping_add(s0);
+    }
+
+    (s0, s1)
+}
+
+impl Wal {
+    /// Opens `<db_path>-wal` if present and indexes the frames belonging to committed
+    /// transactions. Frames after the last commit frame (a partially written transaction)
+    /// and frames that fail the salt/checksum validation are ignored.
+    pub fn open(db_path: &str, page_size: u32) -> Result<Option<Wal>> {
+        let mut file = match File::open(format!("{}-wal", db_path)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context("Failed to open WAL file"),
+        };
+
+        let mut header = [0u8; WAL_HEADER_SIZE as usize];
+        if file.read_exact(&mut header).is_err() {
+            return Ok(None);
+        }
+
+        let big_endian = match read_u32(&header, 0) {
+            0x377f0682 => false,
+            0x377f0683 => true,
+            magic => bail!(SqliteError::corrupt(None, format!("invalid WAL magic number: {:#x}", magic))),
+        };
+
+        let wal_page_size = read_u32(&header, 8);
+        if wal_page_size != page_size {
+            bail!(SqliteError::corrupt(None, format!("WAL page size {} does not match database page size {}", wal_page_size, page_size)));
+        }
+
+        let mut checksum = wal_checksum(&header[0..24], (0, 0), big_endian);
+        if checksum != (read_u32(&header, 24), read_u32(&header, 28)) {
+            return Ok(None);
+        }
+
+        let mut pages = HashMap::new();
+        let mut pending = HashMap::new();
+        let mut commit_frame = None;
+        let mut db_size = None;
+
+        let mut frame_header = [0u8; WAL_FRAME_HEADER_SIZE as usize];
+        let mut page = vec![0u8; page_size as usize];
+        let mut frame_offset = WAL_HEADER_SIZE;
+        let mut frame_idx = 1u32;
+
+        loop {
+            if file.read_exact(&mut frame_header).is_err() || file.read_exact(&mut page).is_err() {
+                break;
+            }
+
+            if frame_header[8..16] != header[16..24] {
+                break;
+            }
+
+            checksum = wal_checksum(&frame_header[0..8], checksum, big_endian);
+            checksum = wal_checksum(&page, checksum, big_endian);
+            if checksum != (read_u32(&frame_header, 16), read_u32(&frame_header, 20)) {
+                break;
+            }
+
+            let page_num = read_u32(&frame_header, 0);
+            pending.insert(page_num, frame_offset + WAL_FRAME_HEADER_SIZE);
+
+            let db_size_after_commit = read_u32(&frame_header, 4);
+            if db_size_after_commit != 0 {
+                pages.extend(pending.drain());
+                commit_frame = Some(frame_idx);
+                db_size = Some(db_size_after_commit);
+            }
+
+            frame_offset += WAL_FRAME_HEADER_SIZE + page_size as u64;
+            frame_idx += 1;
+        }
+
+        Ok(Some(Wal { file, pages, commit_frame, db_size }))
+    }
+
+    /// 1-based index of the last commit frame the snapshot was built from, if any.
+    pub fn commit_frame(&self) -> Option<u32> {
+        self.commit_frame
+    }
+
+    pub fn db_size(&self) -> Option<u32> {
+        self.db_size
+    }
+
+    pub fn contains(&self, page_num: u32) -> bool {
+        self.pages.contains_key(&page_num)
+    }
+
+    pub fn read_page_at(&mut self, page_num: u32, offset: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+        let page_offset = match self.pages.get(&page_num) {
+            Some(page_offset) => *page_offset,
+            None => return Ok(0),
+        };
+
+        self.file.seek(SeekFrom::Start(page_offset + offset))?;
+        self.file.read(buf)
+    }
+}
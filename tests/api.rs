@@ -0,0 +1,333 @@
+mod common;
+
+use codecrafters_sqlite::{Database, SqlType, SqliteError, Value};
+use common::{SAMPLE, fixture, open, query, query_err};
+use std::collections::HashMap;
+
+#[test]
+fn attached_database_is_queried_by_schema_name() {
+    let mut db = open(SAMPLE);
+    db.attach("other", &fixture("names")).unwrap();
+    assert_eq!(query(&mut db, r#"SELECT name FROM other."my table" WHERE id = 2"#), ["b"]);
+    assert_eq!(query(&mut db, r#"SELECT COUNT(*) FROM OTHER."my table""#), ["2"]);
+    assert_eq!(query(&mut db, "SELECT name FROM main.apples WHERE id = 1"), ["Granny Smith"]);
+    assert_eq!(query(&mut db, "PRAGMA other.user_version"), ["0"]);
+
+    assert!(matches!(query_err(&mut db, "SELECT * FROM other.apples"), SqliteError::NoSuchTable(_)));
+    assert!(matches!(query_err(&mut db, "SELECT * FROM third.apples"), SqliteError::Misuse(_)));
+    assert!(matches!(db.attach("main", &fixture("names")), Err(SqliteError::Misuse(_))));
+    assert!(matches!(db.attach("Other", &fixture("names")), Err(SqliteError::Misuse(_))));
+}
+
+#[test]
+fn read_page_returns_raw_bytes() {
+    let mut db = open(SAMPLE);
+    let page = db.read_page(1).unwrap();
+    assert_eq!(page.len(), 4096);
+    assert_eq!(&page[..16], b"SQLite format 3\0");
+    // Page 2 is the `apples` table's leaf page; its header comes first.
+    assert_eq!(db.read_page(2).unwrap()[0], 0x0d);
+    assert!(matches!(db.read_page(5), Err(SqliteError::Misuse(_))));
+    assert!(matches!(db.read_page(0), Err(SqliteError::Misuse(_))));
+}
+
+#[test]
+fn errors_can_be_matched() {
+    let mut db = open(SAMPLE);
+    match query_err(&mut db, "SELECT * FROM missing") {
+        SqliteError::NoSuchTable(table) => assert_eq!(table, "missing"),
+        error => panic!("expected NoSuchTable, got {:?}", error),
+    }
+    match query_err(&mut db, "SELECT nope FROM apples") {
+        SqliteError::NoSuchColumn { column, table } => {
+            assert_eq!(column, "nope");
+            assert_eq!(table.as_deref(), Some("apples"));
+        }
+        error => panic!("expected NoSuchColumn, got {:?}", error),
+    }
+    assert!(matches!(Database::open_bytes(b"not a database".to_vec()), Err(SqliteError::NotADatabase)));
+    assert!(matches!(Database::open("tests/fixtures/missing.db"), Err(SqliteError::Io(_))));
+
+    assert_eq!(query_err(&mut db, "SELECT * FROM missing").to_string(), "no such table: missing");
+}
+
+#[test]
+fn prepare_reports_result_columns() {
+    let mut db = open(&fixture("orders"));
+    let statement = db.prepare("SELECT id, item AS bee, * FROM orders").unwrap();
+    assert_eq!(statement.column_count(), 6);
+    assert_eq!(statement.column_names(), ["id", "bee", "id", "item", "price", "quantity"]);
+
+    let statement = db.prepare("SELECT COUNT(*), price * 2 FROM orders").unwrap();
+    assert_eq!(statement.column_names(), ["COUNT(*)", "price * 2"]);
+    assert!(matches!(db.prepare("SELECT * FROM missing"), Err(SqliteError::NoSuchTable(_))));
+}
+
+#[test]
+fn query_maps_keys_rows_by_column_name() {
+    let mut db = open(&fixture("orders"));
+    let maps = db.query_maps("SELECT item AS name, price * 2, quantity FROM orders WHERE id IN (2, 3)").unwrap();
+    assert_eq!(maps.len(), 2);
+    assert_eq!(maps[0], HashMap::from([
+        ("name".to_string(), Value::Text("book".to_string())),
+        ("price * 2".to_string(), Value::Integer(30)),
+        ("quantity".to_string(), Value::Integer(2)),
+    ]));
+    assert_eq!(maps[1]["quantity"], Value::Null);
+    // The rightmost of two columns with one name wins.
+    let maps = db.query_maps("SELECT price AS x, item AS x FROM orders WHERE id = 1").unwrap();
+    assert_eq!(maps, [HashMap::from([("x".to_string(), Value::Text("pen".to_string()))])]);
+    assert!(db.query_maps("SELECT * FROM orders WHERE id > 9").unwrap().is_empty());
+}
+
+/// A leaf table page holding `cells`, each a rowid and a record of text and small integers.
+fn leaf_page(page: &mut [u8], header_offset: usize, cells: &[(u8, &[Value])]) {
+    page[header_offset] = 0x0d;
+    page[header_offset + 3..header_offset + 5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    let mut content_start = page.len();
+    for (i, (rowid, values)) in cells.iter().enumerate() {
+        let (mut header, mut body) = (vec![], vec![]);
+        for value in *values {
+            match value {
+                Value::Integer(n) => {
+                    header.push(1);
+                    body.push(*n as u8);
+                }
+                Value::Text(text) => {
+                    header.push(13 + 2 * text.len() as u8);
+                    body.extend_from_slice(text.as_bytes());
+                }
+                value => panic!("unsupported value {:?}", value),
+            }
+        }
+        let record = [&[header.len() as u8 + 1][..], &header, &body].concat();
+        let cell = [&[record.len() as u8, *rowid][..], &record].concat();
+        content_start -= cell.len();
+        page[content_start..content_start + cell.len()].copy_from_slice(&cell);
+        let pointer = header_offset + 8 + 2 * i;
+        page[pointer..pointer + 2].copy_from_slice(&(content_start as u16).to_be_bytes());
+    }
+    page[header_offset + 5..header_offset + 7].copy_from_slice(&(content_start as u16).to_be_bytes());
+}
+
+#[test]
+fn database_built_in_memory() {
+    let text = |s: &str| Value::Text(s.to_string());
+    let mut image = vec![0u8; 1024];
+    image[..16].copy_from_slice(b"SQLite format 3\0");
+    image[16..18].copy_from_slice(&512u16.to_be_bytes());
+    image[18..24].copy_from_slice(&[1, 1, 0, 64, 32, 32]);
+    image[28..32].copy_from_slice(&2u32.to_be_bytes());
+    image[44..48].copy_from_slice(&4u32.to_be_bytes());
+    image[56..60].copy_from_slice(&1u32.to_be_bytes());
+    let schema_row = [text("table"), text("notes"), text("notes"), Value::Integer(2), text("CREATE TABLE notes (body TEXT)")];
+    leaf_page(&mut image[..512], 100, &[(1, &schema_row)]);
+    leaf_page(&mut image[512..], 0, &[(1, &[text("hello")]), (2, &[text("world")])]);
+
+    let mut db = Database::open_bytes(image).unwrap();
+    assert_eq!(db.table_names(), ["notes"]);
+    assert_eq!(db.page_size(), 512);
+    assert_eq!(query(&mut db, "SELECT rowid, body FROM notes"), ["1|hello", "2|world"]);
+    db.integrity_check().unwrap();
+}
+
+#[test]
+fn columns_and_their_affinities() {
+    let db = open(&fixture("typed"));
+    let columns: Vec<(String, SqlType)> = db.columns("typed").unwrap().into_iter().map(|c| (c.name, c.affinity)).collect();
+    let expected = [
+        ("id", SqlType::Integer), ("count", SqlType::Integer), ("label", SqlType::Text), ("notes", SqlType::Text),
+        ("data", SqlType::Blob), ("untyped", SqlType::Blob), ("score", SqlType::Real), ("ratio", SqlType::Real),
+        ("price", SqlType::Numeric), ("when", SqlType::Numeric),
+    ];
+    assert_eq!(columns, expected.map(|(name, affinity)| (name.to_string(), affinity)));
+    assert!(matches!(db.columns("missing"), Err(SqliteError::NoSuchTable(name)) if name == "missing"));
+}
+
+/// Splits CSV text into records of fields, undoing the quoting `query_to_csv` applies.
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let (mut records, mut record, mut field) = (vec![], vec![], String::new());
+    let (mut chars, mut quoted) = (text.chars().peekable(), false);
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (_, '"') => quoted = !quoted,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (_, c) => field.push(c),
+        }
+    }
+    records
+}
+
+#[test]
+fn query_to_csv_round_trips() {
+    let mut db = open(SAMPLE);
+    let mut out = vec![];
+    db.query_to_csv("SELECT id, name AS \"apple, name\" FROM apples WHERE id < 3", &mut out, true).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "id,\"apple, name\"\n1,\"Granny Smith\"\n2,Fuji\n");
+
+    let mut out = vec![];
+    db.query_to_csv("SELECT 'say \"hi\"', 'two\nlines', NULL, 2.5, 'a,b'", &mut out, false).unwrap();
+    assert_eq!(parse_csv(&String::from_utf8(out).unwrap()), [["say \"hi\"", "two\nlines", "", "2.5", "a,b"]]);
+}
+
+/// A writer that fails once it has taken `capacity` bytes.
+struct Full {
+    written: Vec<u8>,
+    capacity: usize,
+}
+
+impl std::io::Write for Full {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.written.len() + buf.len() > self.capacity {
+            return Err(std::io::Error::other("writer is full"));
+        }
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn query_to_csv_streams_rows() {
+    let mut db = open(&fixture("btree"));
+    let mut out = Full { written: vec![], capacity: usize::MAX };
+    db.query_to_csv("SELECT id FROM t", &mut out, false).unwrap();
+    let all_pages = db.pages_read();
+    assert_eq!(parse_csv(&String::from_utf8(out.written).unwrap()).len(), 300);
+
+    // A writer failing on the second row stops the scan a page or two in.
+    let mut out = Full { written: vec![], capacity: 2 };
+    assert!(matches!(db.query_to_csv("SELECT id FROM t", &mut out, false), Err(SqliteError::Io(_))));
+    assert_eq!(out.written, b"1\n");
+    assert!(db.pages_read() < all_pages / 4, "{} of {} pages read", db.pages_read(), all_pages);
+}
+
+#[test]
+fn get_fetches_a_row_by_rowid() {
+    let mut db = open(&fixture("btree"));
+    let row = db.get("t", 300).unwrap().expect("row 300 exists");
+    assert_eq!(row, [Value::Integer(300), Value::Text(format!("row 300 {}", "x".repeat(20)))]);
+    // The interior root and one leaf.
+    assert_eq!(db.pages_read(), 2);
+
+    for rowid in [301, 0, -5] {
+        assert_eq!(db.get("t", rowid).unwrap(), None, "rowid {}", rowid);
+    }
+    assert!(matches!(db.get("missing", 1), Err(SqliteError::NoSuchTable(_))));
+}
+
+#[test]
+fn table_cursor_walks_rows_lazily() {
+    let mut db = open(&fixture("btree"));
+    let first: Vec<Vec<Value>> = db.table_cursor("t").unwrap().take(2).collect::<Result<_, _>>().unwrap();
+    assert_eq!(first.iter().map(|row| row[0].clone()).collect::<Vec<_>>(), [Value::Integer(1), Value::Integer(2)]);
+    // The root and the first leaf, not every page of the table.
+    assert_eq!(db.pages_read(), 2);
+
+    let last = db.table_cursor_desc("t").unwrap().next().unwrap().unwrap();
+    assert_eq!(last[0], Value::Integer(300));
+    assert_eq!(db.table_cursor("t").unwrap().count(), 300);
+    assert!(matches!(db.table_cursor("missing").err(), Some(SqliteError::NoSuchTable(_))));
+}
+
+#[test]
+fn rows_carry_column_names_and_iterate_lazily() {
+    let mut db = open(SAMPLE);
+    let mut rows = db.query("SELECT id, name AS apple FROM apples WHERE id > 2").unwrap();
+    assert_eq!(rows.column_names(), ["id", "apple"]);
+    assert_eq!(rows.next().unwrap().unwrap(), [Value::Integer(3), Value::Text("Honeycrisp".to_string())]);
+    assert_eq!(rows.next().unwrap().unwrap()[0], Value::Integer(4));
+    assert!(rows.next().is_none());
+
+    // A plain scan reads pages only as rows are asked for.
+    let mut db = open(&fixture("btree"));
+    let first = db.query("SELECT v FROM t").unwrap().next().unwrap().unwrap();
+    assert_eq!(first, [Value::Text(format!("row 001 {}", "x".repeat(20)))]);
+    assert_eq!(db.pages_read(), 2);
+    assert_eq!(db.query("SELECT v FROM t").unwrap().count(), 300);
+    let all_pages = db.pages_read();
+    assert!(all_pages > 20, "{}", all_pages);
+    // Sorting by anything but the rowid reads every row first.
+    db.query("SELECT v FROM t ORDER BY v DESC").unwrap().next().unwrap().unwrap();
+    assert_eq!(db.pages_read(), all_pages);
+}
+
+#[test]
+fn like_patterns_bind_as_parameters() {
+    let mut db = open(SAMPLE);
+    let mut names = |sql: &str, params: &[Value]| -> Vec<String> {
+        db.query_params(sql, params).unwrap().map(|row| row.unwrap()[0].to_string()).collect()
+    };
+    let sql = "SELECT name FROM apples WHERE name LIKE ?";
+    assert_eq!(names(sql, &[Value::Text("g%".into())]), ["Granny Smith", "Golden Delicious"]);
+    assert_eq!(names(sql, &[Value::Text("%r%".into())]), ["Granny Smith", "Honeycrisp"]);
+    assert_eq!(names(sql, &[Value::Text("fuj_".into())]), ["Fuji"]);
+    // Without wildcards the pattern only matches the whole value, ignoring ASCII case.
+    assert_eq!(names(sql, &[Value::Text("FUJI".into())]), ["Fuji"]);
+    assert!(names(sql, &[Value::Text("Fuj".into())]).is_empty());
+    // A quote in the pattern is part of the value, not the SQL.
+    assert!(names(sql, &[Value::Text("%' OR '1' = '1".into())]).is_empty());
+    assert_eq!(names("SELECT name FROM apples WHERE name LIKE ?1 AND color NOT LIKE ?2", &[Value::Text("%e%".into()), Value::Text("%green".into())]), ["Honeycrisp", "Golden Delicious"]);
+
+    assert!(matches!(db.query_params(sql, &[]), Err(SqliteError::Misuse(_))));
+}
+
+#[test]
+fn query_after_rowid_pages_through_a_table() {
+    let mut db = open(&fixture("btree"));
+    let page = |db: &mut Database, after: i64, limit: usize| -> Vec<i64> {
+        let rows = db.query_after_rowid("t", after, limit).unwrap();
+        assert_eq!(rows.column_names()[..2], ["rowid", "id"]);
+        rows.map(|row| match row.unwrap()[0] { Value::Integer(rowid) => rowid, ref value => panic!("{:?}", value) }).collect()
+    };
+
+    // Two calls cover the table, each starting after the last rowid of the one before.
+    let first = page(&mut db, i64::MIN, 200);
+    let second = page(&mut db, *first.last().unwrap(), 200);
+    assert_eq!(second.len(), 100);
+    assert_eq!([first, second].concat(), (1..=300).collect::<Vec<_>>());
+    assert!(page(&mut db, 300, 200).is_empty());
+
+    // A page deep in the table descends to it rather than reading the rows before it.
+    assert_eq!(page(&mut db, 250, 3), [251, 252, 253]);
+    assert!(db.pages_read() <= 3, "{}", db.pages_read());
+
+    let mut db = open(&fixture("keys"));
+    let rowids = |db: &mut Database, after: i64| -> Vec<String> {
+        db.query_after_rowid("big_ids", after, 2).unwrap().map(|row| row.unwrap()[0].to_string()).collect()
+    };
+    assert_eq!(rowids(&mut db, i64::MIN), ["-1", "4294967296"]);
+    assert_eq!(rowids(&mut db, 4294967296), ["72057594037927936", "9223372036854775807"]);
+    assert!(rowids(&mut db, i64::MAX).is_empty());
+    assert!(matches!(db.query_after_rowid("missing", 0, 1), Err(SqliteError::NoSuchTable(_))));
+}
+
+#[test]
+fn rows_collect_into_rows_with_column_names() {
+    let mut db = open(SAMPLE);
+    let rows = db.query("SELECT id, name AS apple FROM apples WHERE id < 3").unwrap().into_rows().unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[1].column_names(), ["id", "apple"]);
+    assert_eq!(rows[1].values(), [Value::Integer(2), Value::Text("Fuji".to_string())]);
+    assert_eq!(rows[1].get("APPLE"), Some(&Value::Text("Fuji".to_string())));
+    assert_eq!(rows[1].get("name"), None);
+    assert_eq!(rows[0].clone().into_values(), [Value::Integer(1), Value::Text("Granny Smith".to_string())]);
+
+    // Rows already iterated are not collected again, and an error stops the collection.
+    let mut rows = db.query("SELECT id FROM apples").unwrap();
+    rows.next().unwrap().unwrap();
+    assert_eq!(rows.into_rows().unwrap().len(), 3);
+    db.set_max_rows_scanned(Some(2));
+    assert!(matches!(db.query("SELECT id FROM apples").unwrap().into_rows(), Err(SqliteError::ScanLimitExceeded { limit: 2 })));
+}
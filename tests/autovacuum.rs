@@ -0,0 +1,45 @@
+mod common;
+
+use codecrafters_sqlite::{AutoVacuum, Database, SqliteError};
+use common::{SAMPLE, fixture, open, query, shell};
+
+#[test]
+fn auto_vacuum_database_is_read() {
+    let mut db = open(&fixture("autovacuum"));
+    assert_eq!(db.auto_vacuum(), AutoVacuum::Full);
+    assert_eq!(query(&mut db, "SELECT COUNT(*), SUM(id) FROM t"), ["100|5050"]);
+    assert_eq!(query(&mut db, "SELECT v FROM t WHERE id = 100"), ["row 100 xxxxxxxxxxxxxxxxxxxx"]);
+    db.integrity_check().unwrap();
+
+    let run = shell(&[&fixture("autovacuum"), ".dbinfo"]);
+    assert!(run.stdout.contains("auto-vacuum: full\n"), "{}", run.stdout);
+}
+
+#[test]
+fn pointer_map_page_is_not_a_btree_page() {
+    // The table's root is page 3; its right-most child pointer is made to point at page 2.
+    let mut image = std::fs::read(fixture("autovacuum")).unwrap();
+    image[2 * 512 + 8..2 * 512 + 12].copy_from_slice(&2u32.to_be_bytes());
+    let mut db = Database::open_bytes(image).unwrap();
+    let error = db.query("SELECT * FROM t").and_then(|rows| rows.collect::<Result<Vec<_>, _>>()).unwrap_err();
+    assert!(matches!(&error, SqliteError::Corrupt { page: Some(2), detail } if detail.contains("pointer-map page")), "{:?}", error);
+}
+
+#[test]
+fn largest_root_page_and_incremental_flag() {
+    let mut db = open(&fixture("incremental"));
+    assert_eq!(db.auto_vacuum(), AutoVacuum::Incremental);
+    assert_eq!(db.largest_root_page(), 5);
+    assert!(db.incremental_vacuum());
+    assert_eq!(query(&mut db, "SELECT v FROM b WHERE v = 'uno'"), ["uno"]);
+    db.integrity_check().unwrap();
+
+    let run = shell(&[&fixture("incremental"), ".dbinfo"]);
+    assert!(run.stdout.contains("auto-vacuum: incremental\nautovacuum top root: 5\nincremental vacuum: 1\n"), "{}", run.stdout);
+
+    let db = open(&fixture("autovacuum"));
+    assert_eq!((db.largest_root_page(), db.incremental_vacuum()), (3, false));
+    // Without auto-vacuum both fields are zero.
+    let db = open(SAMPLE);
+    assert_eq!((db.auto_vacuum(), db.largest_root_page(), db.incremental_vacuum()), (AutoVacuum::None, 0, false));
+}
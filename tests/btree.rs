@@ -0,0 +1,204 @@
+mod common;
+
+use codecrafters_sqlite::{Database, SqliteError};
+use common::{SAMPLE, fixture, open, query, query_err, shell};
+use std::fs;
+
+/// The page size of the `btree` fixture, whose table `t` has its interior root on page 2 and
+/// whose index `s_x` is a single leaf on page 26.
+const PAGE_SIZE: usize = 512;
+/// Offsets in the schema records on page 1 of the rootpage of tables `t` and `s` and of index
+/// `s_x`.
+const T_ROOTPAGE: usize = 464;
+const S_ROOTPAGE: usize = 422;
+const S_X_ROOTPAGE: usize = 381;
+
+/// Opens the `btree` fixture with `bytes` written over the image at each offset.
+fn patched(edits: &[(usize, &[u8])]) -> Result<Database, SqliteError> {
+    let mut image = fs::read(fixture("btree")).unwrap();
+    for (offset, bytes) in edits {
+        image[*offset..*offset + bytes.len()].copy_from_slice(bytes);
+    }
+    Database::open_bytes(image)
+}
+
+fn page_offset(page_num: usize) -> usize {
+    (page_num - 1) * PAGE_SIZE
+}
+
+fn assert_corrupt(error: SqliteError, detail_part: &str) {
+    match error {
+        SqliteError::Corrupt { detail, .. } => assert!(detail.contains(detail_part), "{}", detail),
+        error => panic!("expected Corrupt, got {:?}", error),
+    }
+}
+
+#[test]
+fn self_referencing_page_fails_cleanly() {
+    // The root's right-most child pointer points back at the root.
+    let mut db = patched(&[(page_offset(2) + 8, &2u32.to_be_bytes())]).unwrap();
+    for sql in ["SELECT * FROM t", "SELECT COUNT(*) FROM t", "SELECT v FROM t WHERE id = 300", "SELECT MAX(id) FROM t"] {
+        assert_corrupt(query_err(&mut db, sql), "page cycle detected at page 2");
+    }
+}
+
+#[test]
+fn scan_limit_stops_a_query() {
+    let mut db = open(&fixture("btree"));
+    db.set_max_rows_scanned(Some(100));
+    assert!(matches!(query_err(&mut db, "SELECT * FROM t"), SqliteError::ScanLimitExceeded { limit: 100 }));
+    assert_eq!(query(&mut db, "SELECT id FROM t WHERE id BETWEEN 10 AND 12"), ["10", "11", "12"]);
+    // COUNT(*) counts cells without scanning rows, so the limit doesn't apply to it.
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM t"), ["300"]);
+
+    db.set_max_rows_scanned(None);
+    assert_eq!(query(&mut db, "SELECT COUNT(v) FROM t"), ["300"]);
+}
+
+#[test]
+fn invalid_page_size_is_corrupt() {
+    for page_size in [0u16, 256, 1000] {
+        let Err(error) = patched(&[(16, &page_size.to_be_bytes())]) else {
+            panic!("page size {} was accepted", page_size);
+        };
+        assert_corrupt(error, &format!("invalid page size {}", page_size));
+    }
+}
+
+#[test]
+fn reserved_bytes_must_leave_room_for_cells() {
+    let Err(error) = patched(&[(20, &[40])]) else {
+        panic!("40 reserved bytes of a 512 byte page were accepted");
+    };
+    assert_corrupt(error, "40 reserved bytes");
+}
+
+#[test]
+fn huge_cell_count_is_an_error() {
+    let mut db = patched(&[(page_offset(2) + 3, &[0xff, 0xff])]).unwrap();
+    for sql in ["SELECT v FROM t WHERE id = 5", "SELECT COUNT(*) FROM t", "SELECT * FROM t"] {
+        assert!(db.query(sql).and_then(|rows| rows.collect::<Result<Vec<_>, _>>()).is_err(), "{}", sql);
+    }
+}
+
+#[test]
+fn empty_index_record_is_corrupt() {
+    // The index's only cell becomes a one-byte record whose header lists no fields.
+    let cell = page_offset(26) + 507;
+    let mut db = patched(&[(cell, &[1, 1])]).unwrap();
+    assert_corrupt(query_err(&mut db, "SELECT x FROM s WHERE x = 5"), "index entry with 0 fields");
+}
+
+#[test]
+fn count_matches_full_decode() {
+    let mut db = open(&fixture("btree"));
+    let decoded = query(&mut db, "SELECT * FROM t").len();
+    assert_eq!(decoded, 300);
+    for sql in ["SELECT COUNT(*) FROM t", "SELECT COUNT(id) FROM t", "SELECT COUNT(*) FROM t WHERE v IS NOT NULL"] {
+        assert_eq!(query(&mut db, sql), [decoded.to_string()], "{}", sql);
+    }
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM s"), ["1"]);
+}
+
+#[test]
+fn rowids_in_ascending_order() {
+    let mut db = open(&fixture("btree"));
+    assert_eq!(db.rowids("t").unwrap(), (1..=300).collect::<Vec<i64>>());
+    assert!(matches!(db.rowids("missing"), Err(SqliteError::NoSuchTable(_))));
+
+    let mut db = open(&fixture("keys"));
+    assert_eq!(db.rowids("aliased").unwrap(), [10, 20]);
+    assert_eq!(db.rowids("not_aliased").unwrap(), [1, 2]);
+}
+
+#[test]
+fn rowid_ranges_read_only_their_pages() {
+    let mut db = open(&fixture("btree"));
+    query(&mut db, "SELECT v FROM t");
+    let full_scan = db.pages_read();
+    assert!(full_scan > 20, "{}", full_scan);
+
+    for sql in ["SELECT id FROM t WHERE rowid BETWEEN 100 AND 110", "SELECT id FROM t WHERE id >= 100 AND id <= 110 AND v LIKE 'row%'"] {
+        assert_eq!(query(&mut db, sql), (100..=110).map(|id| id.to_string()).collect::<Vec<_>>(), "{}", sql);
+        // The root and the leaf holding the range.
+        assert!(db.pages_read() <= 3, "{} read {} pages", sql, db.pages_read());
+    }
+    assert_eq!(query(&mut db, "SELECT id FROM t WHERE rowid > 298"), ["299", "300"]);
+    assert!(db.pages_read() <= 3, "{}", db.pages_read());
+
+    // OR can't narrow the range, so every page is read.
+    query(&mut db, "SELECT id FROM t WHERE rowid BETWEEN 100 AND 110 OR v = 'x'");
+    assert_eq!(db.pages_read(), full_scan);
+}
+
+#[test]
+fn unselected_blobs_are_not_read() {
+    let mut db = open(&fixture("blobs"));
+    assert_eq!(query(&mut db, "SELECT length(data) FROM docs"), ["2", "10000", "20000"]);
+    let with_blobs = db.pages_read();
+    assert!(with_blobs > 50, "{}", with_blobs);
+
+    // `name` comes before the blob, in the part of each record stored on the leaf.
+    assert_eq!(query(&mut db, "SELECT id, name FROM docs"), ["1|small", "2|large", "3|larger"]);
+    assert!(db.pages_read() <= 3, "{}", db.pages_read());
+}
+
+#[test]
+fn integrity_check_passes_on_good_databases() {
+    for path in [SAMPLE.to_string(), fixture("btree"), fixture("values"), fixture("autovacuum"), fixture("wal")] {
+        open(&path).integrity_check().unwrap_or_else(|e| panic!("{}: {}", path, e));
+    }
+    let run = shell(&[&fixture("btree"), ".check"]);
+    assert_eq!(run.stdout, "ok\n");
+}
+
+#[test]
+fn integrity_check_reports_the_first_problem() {
+    let check = |edits: &[(usize, &[u8])]| patched(edits).unwrap().integrity_check().unwrap_err();
+
+    // The root's right-most child pointer points back at the root.
+    assert_corrupt(check(&[(page_offset(2) + 8, &2u32.to_be_bytes())]), "page cycle detected at page 2");
+    // Table `s` rooted at one of `t`'s leaves.
+    assert_corrupt(check(&[(S_ROOTPAGE, &[3])]), "page 3 is referenced more than once");
+    // ... or at the index's leaf page.
+    assert_corrupt(check(&[(page_offset(2) + 8, &26u32.to_be_bytes())]), "page 26: unexpected page type LeafIndex in a table b-tree");
+    // The root's first two children swapped, so rowids 15 to 28 come before 1.
+    assert_corrupt(check(&[(page_offset(2) + 507, &4u32.to_be_bytes()), (page_offset(2) + 502, &3u32.to_be_bytes())]),
+        "page 3: rowid 1 out of order (follows 28)");
+    // The index leaf's only cell pointer aimed into the page header.
+    assert_corrupt(check(&[(page_offset(26) + 8, &5u16.to_be_bytes())]),
+        "page 26: cell 0 at offset 5 is outside the cell content area 507..512");
+}
+
+#[test]
+fn page_zero_is_a_clean_error() {
+    // A table whose schema row says rootpage 0 can't be scanned.
+    let mut db = patched(&[(T_ROOTPAGE, &[0])]).unwrap();
+    for sql in ["SELECT * FROM t", "SELECT COUNT(*) FROM t"] {
+        assert!(matches!(query_err(&mut db, sql), SqliteError::UnsupportedSql(m) if m.contains("rootpage 0")), "{}", sql);
+    }
+    // An index lookup through a rootpage of 0.
+    let mut db = patched(&[(S_X_ROOTPAGE, &[0])]).unwrap();
+    assert_corrupt(query_err(&mut db, "SELECT x FROM s WHERE x = 5"), "invalid page number 0");
+    // A child pointer to page 0, reached by scans, counts and rowid lookups alike.
+    let mut db = patched(&[(page_offset(2) + 8, &0u32.to_be_bytes())]).unwrap();
+    for sql in ["SELECT * FROM t", "SELECT COUNT(*) FROM t", "SELECT v FROM t WHERE id = 300"] {
+        assert_corrupt(query_err(&mut db, sql), "invalid page number 0");
+    }
+}
+
+#[test]
+fn max_rowid_reads_the_rightmost_leaf() {
+    let mut db = open(&fixture("btree"));
+    for (sql, expected) in [("SELECT MAX(rowid) FROM t", "300"), ("SELECT max(id) FROM t", "300"), ("SELECT MIN(rowid) FROM t", "1")] {
+        assert_eq!(query(&mut db, sql), [expected]);
+        // The interior root and one leaf.
+        assert_eq!(db.pages_read(), 2, "{}", sql);
+    }
+
+    let mut db = open(&fixture("keys"));
+    assert_eq!(query(&mut db, "SELECT MAX(rowid), MIN(rowid) FROM big_ids"), ["9223372036854775807|-9223372036854775808"]);
+    assert_eq!(db.pages_read(), 1);
+    let mut db = open(&fixture("empty"));
+    assert_eq!(query(&mut db, "SELECT MAX(rowid) FROM vacant"), [""]);
+}
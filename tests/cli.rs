@@ -0,0 +1,190 @@
+mod common;
+
+use common::{fixture, shell, shell_with_stdin, temp_dir};
+
+#[test]
+fn json_tables_and_schema() {
+    let names = fixture("names");
+    let run = shell(&["-json", &names, ".tables", ".schema"]);
+    assert!(run.success, "{}", run.stderr);
+    let object = r#"[{"name":"my table","type":"table","sql":"CREATE TABLE \"my table\" (id INTEGER PRIMARY KEY, name TEXT)"}]"#;
+    assert_eq!(run.stdout, format!("{}\n{}\n", object, object));
+
+    let run = shell(&["sample.db", ".tables"]);
+    assert_eq!(run.stdout, "apples oranges \n");
+}
+
+#[test]
+fn json_query_results() {
+    let names = fixture("names");
+    let run = shell(&["-json", &names, "SELECT * FROM [my table]"]);
+    assert_eq!(run.stdout, "[{\"id\":1,\"name\":\"a\"},\n{\"id\":2,\"name\":\"b\"}]\n");
+}
+
+#[test]
+fn hexdump_shows_the_header_magic() {
+    let run = shell(&["sample.db", ".hexdump 1"]);
+    assert!(run.success, "{}", run.stderr);
+    let lines: Vec<&str> = run.stdout.lines().collect();
+    assert_eq!(lines[0], "00000000  53 51 4c 69 74 65 20 66  6f 72 6d 61 74 20 33 00  |SQLite format 3.|");
+    // A 4096 byte page in lines of 16 bytes.
+    assert_eq!(lines.len(), 256);
+    assert!(lines[255].starts_with("00000ff0  "));
+}
+
+#[test]
+fn hexdump_past_the_end_is_an_error() {
+    let run = shell(&["sample.db", ".hexdump 999999"]);
+    assert!(!run.success);
+    assert_eq!(run.stderr, "Error: page 999999 is past the end of the database, which has 4 pages\n");
+    assert!(run.stdout.is_empty());
+
+    let run = shell(&["sample.db", ".hexdump 0"]);
+    assert!(!run.success);
+    assert_eq!(run.stderr, "Error: page numbers start at 1\n");
+}
+
+#[test]
+fn csv_headers_are_quoted_like_fields() {
+    let query = r#"SELECT id, name AS "a,b" FROM apples LIMIT 2"#;
+    let run = shell(&["-csv", "sample.db", ".headers on", query]);
+    assert_eq!(run.stdout, "id,\"a,b\"\n1,\"Granny Smith\"\n2,Fuji\n");
+
+    let run = shell(&["-csv", "sample.db", query]);
+    assert_eq!(run.stdout, "1,\"Granny Smith\"\n2,Fuji\n");
+
+    // An empty result prints no header either.
+    let run = shell(&["-csv", "-header", "sample.db", "SELECT id FROM apples WHERE id = 0"]);
+    assert_eq!(run.stdout, "");
+}
+
+#[test]
+fn timer_reports_time_and_pages() {
+    let run = shell(&["sample.db", ".timer on", "SELECT id FROM apples WHERE id = 1", ".timer off", "SELECT id FROM apples WHERE id = 2"]);
+    assert!(run.success, "{}", run.stderr);
+    let lines: Vec<&str> = run.stdout.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", run.stdout);
+    assert_eq!(lines[0], "1");
+    assert_eq!(lines[2], "2");
+
+    let fields: Vec<&str> = lines[1].split(' ').collect();
+    assert_eq!(fields[..3], ["Run", "Time:", "real"], "{}", lines[1]);
+    assert!(fields[3].parse::<f64>().is_ok_and(|seconds| seconds >= 0.0), "{}", lines[1]);
+    assert_eq!(fields[4..], ["pages", "1"], "{}", lines[1]);
+}
+
+#[test]
+fn statements_span_lines_until_a_semicolon() {
+    let run = shell_with_stdin(&["sample.db"], b"SELECT name,\ncolor\nFROM apples\nWHERE id = 1;\n.tables\nSELECT 'a;b'; SELECT 2 -- c;\n;\n");
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.stdout, "Granny Smith|Light Green\napples oranges \na;b\n2\n");
+}
+
+#[test]
+fn nullvalue_sets_how_null_prints() {
+    let orders = fixture("orders");
+    let run = shell(&[&orders, "SELECT id, quantity FROM orders WHERE id > 2"]);
+    assert_eq!(run.stdout, "3|\n4|5\n");
+    let run = shell(&[&orders, ".nullvalue NULL", "SELECT id, quantity FROM orders WHERE id > 2"]);
+    assert_eq!(run.stdout, "3|NULL\n4|5\n");
+    let run = shell(&["-nullvalue", "(none)", &orders, "SELECT quantity FROM orders WHERE id = 3"]);
+    assert_eq!(run.stdout, "(none)\n");
+    // JSON has a null of its own.
+    let run = shell(&["-json", &orders, ".nullvalue NULL", "SELECT quantity FROM orders WHERE id = 3"]);
+    assert_eq!(run.stdout, "[{\"quantity\":null}]\n");
+}
+
+#[test]
+fn database_from_stdin_or_a_file_uri() {
+    let image = std::fs::read("sample.db").unwrap();
+    let run = shell_with_stdin(&["-", "SELECT name FROM apples WHERE id = 2"], &image);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.stdout, "Fuji\n");
+
+    let run = shell(&["file:sample.db?mode=ro", ".tables"]);
+    assert_eq!(run.stdout, "apples oranges \n");
+}
+
+#[test]
+fn read_runs_a_script() {
+    let dir = temp_dir("read");
+    let script = dir.join("two.sql");
+    std::fs::write(&script, "SELECT name FROM apples WHERE id = 1;\nSELECT COUNT(*)\n  FROM oranges;\n").unwrap();
+    let script = script.to_str().unwrap();
+    let run = shell(&["sample.db", &format!(".read {}", script)]);
+    assert!(run.success, "{}", run.stderr);
+    assert_eq!(run.stdout, "Granny Smith\n6\n");
+
+    // A failing statement is reported with its line, and the rest of the script still runs.
+    let bad = dir.join("bad.sql");
+    std::fs::write(&bad, "SELECT 1;\nSELECT nope FROM apples;\nSELECT 2;\n").unwrap();
+    let bad = bad.to_str().unwrap();
+    let run = shell_with_stdin(&["sample.db"], format!(".read {}\nSELECT 3;\n", bad).as_bytes());
+    assert_eq!(run.stdout, "1\n2\n3\n");
+    assert_eq!(run.stderr, format!(
+        "Error: {0} line 2: no such column: nope in table apples\n  SELECT nope FROM apples\nError: errors while reading {0}\n", bad));
+
+    // With .bail on, reading stops there.
+    let run = shell(&["sample.db", ".bail on", &format!(".read {}", bad)]);
+    assert!(!run.success);
+    assert_eq!(run.stdout, "1\n");
+    assert!(run.stderr.ends_with(&format!("Error: stopped reading {} at the first error\n", bad)), "{}", run.stderr);
+}
+
+#[test]
+fn separator_and_mode() {
+    let run = shell(&["sample.db", ".separator ;", "SELECT id, name FROM apples WHERE id < 3"]);
+    assert_eq!(run.stdout, "1;Granny Smith\n2;Fuji\n");
+    let run = shell(&["-header", "sample.db", ".mode list", ".separator ' | '", "SELECT id, name FROM apples WHERE id = 1"]);
+    assert_eq!(run.stdout, "id | name\n1 | Granny Smith\n");
+    // As in sqlite3, a separator inside a value is left as it is in list mode.
+    let run = shell(&["-separator", ":", "sample.db", "SELECT id, 'a:b' FROM apples WHERE id = 1"]);
+    assert_eq!(run.stdout, "1:a:b\n");
+
+    let run = shell(&["sample.db", ".mode csv", "SELECT id, name FROM apples WHERE id = 1"]);
+    assert_eq!(run.stdout, "1,\"Granny Smith\"\n");
+    let run = shell(&["sample.db", ".mode typed", "SELECT id, name FROM apples WHERE id = 1"]);
+    assert_eq!(run.stdout, "id=INT:1|name=TEXT:'Granny Smith'\n");
+    let run = shell(&["sample.db", ".mode bogus"]);
+    assert!(!run.success);
+    assert!(run.stderr.contains("mode should be one of: csv json list typed"), "{}", run.stderr);
+}
+
+#[test]
+fn db_and_query_flags() {
+    let positional = shell(&["sample.db", "SELECT name FROM apples WHERE id = 3"]);
+    assert_eq!(positional.stdout, "Honeycrisp\n");
+    for args in [
+        &["--db", "sample.db", "--query", "SELECT name FROM apples WHERE id = 3"][..],
+        &["--query", "SELECT name FROM apples WHERE id = 3", "--db", "sample.db"],
+    ] {
+        let run = shell(args);
+        assert!(run.success, "{}", run.stderr);
+        assert_eq!(run.stdout, positional.stdout, "{:?}", args);
+    }
+
+    let run = shell(&["--csv", "--header", "--db", "sample.db", "--query", "SELECT id, name FROM apples WHERE id < 3"]);
+    assert_eq!(run.stdout, "id,name\n1,\"Granny Smith\"\n2,Fuji\n");
+
+    let run = shell(&["--query", "SELECT 1"]);
+    assert!(!run.success);
+    assert!(run.stderr.contains("Missing <database path>"), "{}", run.stderr);
+}
+
+#[test]
+fn typed_mode_shows_each_storage_class() {
+    let values = fixture("values");
+    let run = shell(&[&values, ".mode typed", "SELECT rowid, v, t FROM mixed"]);
+    assert!(run.success, "{}", run.stderr);
+    // `t` is declared TEXT, so the 3 inserted into it is stored as text.
+    assert_eq!(run.stdout, concat!(
+        "rowid=INT:1|v=NULL|t=TEXT:'text'\n",
+        "rowid=INT:2|v=INT:1|t=BLOB:X'0102'\n",
+        "rowid=INT:3|v=REAL:2.5|t=TEXT:'x'\n",
+        "rowid=INT:4|v=TEXT:'text'|t=NULL\n",
+        "rowid=INT:5|v=BLOB:X'00FF'|t=TEXT:'3'\n",
+    ));
+
+    let run = shell(&[&values, "-typed", "SELECT 'it''s', 1.0, -2 FROM mixed LIMIT 1"]);
+    assert_eq!(run.stdout, "'it''s'=TEXT:'it''s'|1.0=REAL:1.0|-2=INT:-2\n");
+}
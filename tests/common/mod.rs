@@ -0,0 +1,81 @@
+//! Helpers shared by the integration tests. Each test file uses only some of them.
+#![allow(dead_code)]
+
+use codecrafters_sqlite::{Database, SqliteError, Value};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Path of the sample database at the root of the repository.
+pub const SAMPLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db");
+
+/// Path of a database in `tests/fixtures`, built from the `.sql` script of the same name by
+/// `tests/fixtures/build.sh`.
+pub fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}.db", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+/// An empty directory of its own for a test that writes files, named after the test.
+pub fn temp_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sqlite-rust-{}-{}", std::process::id(), test));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("creating a temporary directory");
+    dir
+}
+
+pub fn open(path: &str) -> Database {
+    Database::open(path).unwrap_or_else(|e| panic!("opening {}: {}", path, e))
+}
+
+/// Runs `sql` and formats each row as the shell's list mode does, values joined by `|`.
+pub fn query(db: &mut Database, sql: &str) -> Vec<String> {
+    query_values(db, sql).iter()
+        .map(|row| row.iter().map(Value::to_string).collect::<Vec<_>>().join("|"))
+        .collect()
+}
+
+pub fn query_values(db: &mut Database, sql: &str) -> Vec<Vec<Value>> {
+    db.query(sql)
+        .and_then(|rows| rows.collect())
+        .unwrap_or_else(|e| panic!("{}: {}", sql, e))
+}
+
+/// The error `sql` fails with, whether running it fails or reading one of its rows does.
+pub fn query_err(db: &mut Database, sql: &str) -> SqliteError {
+    match db.query(sql).and_then(|rows| rows.collect::<Result<Vec<_>, _>>()) {
+        Ok(rows) => panic!("{}: expected an error, got {} rows", sql, rows.len()),
+        Err(e) => e,
+    }
+}
+
+/// What a run of the shell binary printed, and whether it exited successfully.
+pub struct ShellRun {
+    pub stdout: String,
+    pub stderr: String,
+    pub success: bool,
+}
+
+/// Runs the shell binary from the root of the repository with `args`, with `stdin` as input.
+pub fn shell_with_stdin(args: &[&str], stdin: &[u8]) -> ShellRun {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_codecrafters-sqlite"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env("RUST_BACKTRACE", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("starting the shell");
+    child.stdin.take().expect("stdin is piped").write_all(stdin).expect("writing the shell's input");
+    let output = child.wait_with_output().expect("running the shell");
+
+    ShellRun {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        success: output.status.success(),
+    }
+}
+
+pub fn shell(args: &[&str]) -> ShellRun {
+    shell_with_stdin(args, b"")
+}
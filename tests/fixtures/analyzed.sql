@@ -0,0 +1,5 @@
+-- A database ANALYZE has run on, which adds sqlite_stat1 (and sqlite_stat4 where compiled in).
+CREATE TABLE items (id INTEGER PRIMARY KEY, kind TEXT, size INTEGER);
+INSERT INTO items (kind, size) VALUES ('a', 1), ('a', 2), ('b', 3), ('c', 4);
+CREATE INDEX items_kind ON items (kind);
+ANALYZE;
@@ -0,0 +1,6 @@
+-- A full auto-vacuum database, whose page 2 is a pointer-map page rather than a b-tree page.
+PRAGMA page_size = 512;
+PRAGMA auto_vacuum = FULL;
+CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT);
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 100)
+INSERT INTO t (id, v) SELECT i, printf('row %03d %.20c', i, 'x') FROM n;
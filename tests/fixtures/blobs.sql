@@ -0,0 +1,4 @@
+-- Rows whose blobs spill onto chains of overflow pages, after small columns that don't.
+PRAGMA page_size = 512;
+CREATE TABLE docs (id INTEGER PRIMARY KEY, name TEXT, data BLOB, size INTEGER);
+INSERT INTO docs VALUES (1, 'small', X'0102', 2), (2, 'large', zeroblob(10000), 10000), (3, 'larger', zeroblob(20000), 20000);
@@ -0,0 +1,8 @@
+-- Small pages so that a few hundred rows make a table b-tree with interior pages.
+PRAGMA page_size = 512;
+CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT);
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 300)
+INSERT INTO t (id, v) SELECT i, printf('row %03d %.20c', i, 'x') FROM n;
+CREATE TABLE s (x INTEGER);
+INSERT INTO s VALUES (5);
+CREATE INDEX s_x ON s (x);
@@ -0,0 +1,29 @@
+#!/bin/sh
+# Rebuilds fixture databases from the SQL scripts of the same name with the sqlite3 shell:
+# those named on the command line (`./build.sh keys text`), or every one without arguments.
+# Rebuilding only what changed keeps the others, the WAL fixture in particular, whose salts
+# and checksums differ on every build, out of the diff.
+# A script runs against `build.db` in a scratch directory. One that leaves a `snapshot.db`
+# behind, copied mid-transaction with `.system`, makes the fixture from that copy and its
+# `-wal` file instead.
+set -e
+cd "$(dirname "$0")"
+fixtures=$(pwd)
+if [ $# -eq 0 ]; then
+    set -- *.sql
+fi
+for script in "$@"; do
+    name=${script%.sql}
+    script=$name.sql
+    [ -e "$script" ] || { echo "no such fixture script: $script" >&2; exit 1; }
+    scratch=$(mktemp -d)
+    (cd "$scratch" && sqlite3 build.db < "$fixtures/$script" > /dev/null)
+    rm -f "$name.db" "$name.db-wal"
+    if [ -e "$scratch/snapshot.db" ]; then
+        cp "$scratch/snapshot.db" "$name.db"
+        cp "$scratch/snapshot.db-wal" "$name.db-wal"
+    else
+        cp "$scratch/build.db" "$name.db"
+    fi
+    rm -r "$scratch"
+done
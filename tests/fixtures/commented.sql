@@ -0,0 +1,11 @@
+-- A CREATE TABLE spread over lines, with comments (one holding a parenthesis), parenthesized
+-- types and constraints, as schemas written by hand tend to be.
+CREATE TABLE ledger (
+    id INTEGER PRIMARY KEY, -- the rowid
+    amount NUMERIC(10,2) NOT NULL CHECK (amount > 0), /* in cents (not dollars) */
+    memo VARCHAR(40) DEFAULT ('n/a'),
+    booked TEXT,
+    UNIQUE (memo, booked)
+);
+INSERT INTO ledger VALUES (1, 12.5, 'lunch', '2024-01-02');
+INSERT INTO ledger (id, amount) VALUES (2, 3);
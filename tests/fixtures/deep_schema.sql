@@ -0,0 +1,10 @@
+-- Small pages and hundreds of tables and indexes, so that sqlite_schema is a b-tree of three
+-- levels: page 1 is an interior page whose children are interior pages too.
+PRAGMA page_size = 512;
+.output objects.sql
+WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 199)
+SELECT printf('CREATE TABLE t%03d (id INTEGER PRIMARY KEY, v TEXT); CREATE INDEX t%03d_v ON t%03d (v);', i, i, i) FROM n;
+.output
+.read objects.sql
+INSERT INTO t000 (v) VALUES ('first');
+INSERT INTO t199 (v) VALUES ('last');
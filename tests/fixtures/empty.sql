@@ -0,0 +1,3 @@
+-- A table with a schema and an index but no rows.
+CREATE TABLE vacant (id INTEGER PRIMARY KEY, name TEXT, n INTEGER);
+CREATE INDEX vacant_name ON vacant (name);
@@ -0,0 +1,5 @@
+-- Parents and their children; one child has no parent recorded.
+CREATE TABLE parents (id INTEGER PRIMARY KEY, name TEXT);
+INSERT INTO parents VALUES (1, 'ann'), (2, 'bob'), (3, 'cy');
+CREATE TABLE children (id INTEGER PRIMARY KEY, parent_id INTEGER, name TEXT);
+INSERT INTO children VALUES (1, 1, 'dee'), (2, 1, 'eve'), (3, 3, 'fay'), (4, NULL, 'gus');
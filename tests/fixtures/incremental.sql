@@ -0,0 +1,8 @@
+-- An incremental auto-vacuum database with several b-trees, so its largest root page is 5.
+PRAGMA page_size = 512;
+PRAGMA auto_vacuum = INCREMENTAL;
+CREATE TABLE a (id INTEGER PRIMARY KEY, v TEXT);
+CREATE TABLE b (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX b_v ON b (v);
+INSERT INTO a VALUES (1, 'one'), (2, 'two');
+INSERT INTO b VALUES (1, 'uno');
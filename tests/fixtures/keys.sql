@@ -0,0 +1,9 @@
+-- `INTEGER PRIMARY KEY` makes `id` the rowid; with DESC it is an ordinary column.
+CREATE TABLE aliased (id INTEGER PRIMARY KEY, v TEXT);
+INSERT INTO aliased VALUES (10, 'a'), (20, 'b');
+CREATE TABLE not_aliased (id INTEGER PRIMARY KEY DESC, v TEXT);
+INSERT INTO not_aliased VALUES (10, 'a'), (20, 'b');
+-- Rowids past 32 bits, down to the 9-byte varints, with an index to find them by.
+CREATE TABLE big_ids (id INTEGER PRIMARY KEY, v TEXT);
+CREATE INDEX big_ids_v ON big_ids (v);
+INSERT INTO big_ids VALUES (-9223372036854775808, 'min'), (-1, 'minus one'), (4294967296, 'two to the 32'), (72057594037927936, 'two to the 56'), (9223372036854775807, 'max');
@@ -0,0 +1,9 @@
+-- Enough tables that sqlite_schema no longer fits on page 1 and becomes an interior page
+-- over several leaves. The CREATE statements are generated into a script and read back.
+.output tables.sql
+WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 99)
+SELECT printf('CREATE TABLE t%03d (id INTEGER PRIMARY KEY, v TEXT);', i) FROM n;
+.output
+.read tables.sql
+INSERT INTO t000 (v) VALUES ('first');
+INSERT INTO t099 (v) VALUES ('last');
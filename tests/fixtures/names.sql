@@ -0,0 +1,3 @@
+-- Tables whose names need quoting.
+CREATE TABLE "my table" (id INTEGER PRIMARY KEY, name TEXT);
+INSERT INTO "my table" (name) VALUES ('a'), ('b');
@@ -0,0 +1,3 @@
+-- Order lines for aggregates and expressions; one quantity is unknown.
+CREATE TABLE orders (id INTEGER PRIMARY KEY, item TEXT, price INTEGER, quantity INTEGER);
+INSERT INTO orders VALUES (1, 'pen', 2, 10), (2, 'book', 15, 2), (3, 'bag', 40, NULL), (4, 'pen', 2, 5);
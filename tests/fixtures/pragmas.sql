@@ -0,0 +1,5 @@
+-- Header fields an application sets to tag its files; each schema change bumps schema_version.
+PRAGMA user_version = 42;
+PRAGMA application_id = 1096044889;
+CREATE TABLE a (x);
+CREATE TABLE b (y);
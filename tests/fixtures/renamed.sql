@@ -0,0 +1,7 @@
+-- A table whose schema row has a `tbl_name` different from its `name`, as another tool might
+-- write it. SQLite itself keeps the two equal for tables.
+CREATE TABLE stock (id INTEGER PRIMARY KEY, item TEXT);
+INSERT INTO stock VALUES (1, 'bolt'), (2, 'nut');
+.dbconfig defensive off
+PRAGMA writable_schema = ON;
+UPDATE sqlite_schema SET tbl_name = 'inventory' WHERE name = 'stock';
@@ -0,0 +1,11 @@
+-- Text holding LIKE and GLOB wildcards.
+CREATE TABLE labels (s TEXT);
+INSERT INTO labels VALUES ('100%'), ('100'), ('1000'), ('a_b'), ('axb'), ('a\b');
+
+-- Numbers stored as text, which sort as text unless cast.
+CREATE TABLE numbered (n TEXT);
+INSERT INTO numbered VALUES ('10'), ('9'), ('100'), ('2'), ('2 apples');
+
+-- Text whose bytes are not valid UTF-8, as a UTF-16 writer or a careless cast leaves behind.
+CREATE TABLE raw (id INTEGER PRIMARY KEY, t TEXT);
+INSERT INTO raw (t) VALUES ('fine'), (CAST(X'41FF42' AS TEXT));
@@ -0,0 +1,13 @@
+-- Declared types covering each column affinity rule, and a column with no type at all.
+CREATE TABLE typed (
+    id INTEGER PRIMARY KEY,
+    count BIGINT,
+    label VARCHAR(20),
+    notes CLOB,
+    data BLOB,
+    untyped,
+    score DOUBLE PRECISION,
+    ratio FLOAT,
+    price DECIMAL(10, 2),
+    "when" DATETIME
+);
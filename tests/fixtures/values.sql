@@ -0,0 +1,18 @@
+-- Values of every storage class, whatever the column's declared type.
+CREATE TABLE mixed (v, t TEXT);
+INSERT INTO mixed VALUES (NULL, 'text'), (1, X'0102'), (2.5, 'x'), ('text', NULL), (X'00FF', 3);
+
+-- Text whose binary (byte-wise) order differs from alphabetical order.
+CREATE TABLE words (w TEXT);
+INSERT INTO words VALUES ('apple'), ('Zebra'), ('éclair'), ('zoo'), ('Apple');
+
+-- A NOCASE column, indexed so that lookups through the index must ignore case too.
+CREATE TABLE fruits (name TEXT COLLATE NOCASE, color TEXT);
+INSERT INTO fruits VALUES ('apple', 'red'), ('Banana', 'yellow'), ('cherry', 'red'), ('APPLE', 'green');
+CREATE INDEX fruits_name ON fruits (name);
+
+-- Integers and reals in one column, and integers too close together to compare as reals.
+CREATE TABLE numbers (n);
+INSERT INTO numbers VALUES (3), (2.5), (10), (-1.5);
+CREATE TABLE big (n INTEGER);
+INSERT INTO big VALUES (9223372036854775806), (9223372036854775807), (9223372036854775805);
@@ -0,0 +1,6 @@
+-- Views over a table, one of them over another view.
+CREATE TABLE orders (id INTEGER PRIMARY KEY, item TEXT, price INTEGER, quantity INTEGER);
+INSERT INTO orders VALUES (1, 'pen', 2, 10), (2, 'book', 15, 2), (3, 'bag', 40, NULL), (4, 'pen', 2, 5);
+CREATE VIEW cheap AS SELECT id, item, price * quantity AS cost FROM orders WHERE price < 10;
+CREATE VIEW pricey_items AS SELECT item FROM orders WHERE price > 10 ORDER BY item;
+CREATE VIEW cheap_total AS SELECT SUM(cost) AS total FROM cheap;
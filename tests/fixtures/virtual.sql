@@ -0,0 +1,3 @@
+-- A virtual table, which has no b-tree of its own (rootpage 0), and its shadow tables.
+CREATE VIRTUAL TABLE notes USING fts5(body);
+INSERT INTO notes (body) VALUES ('first note'), ('second note');
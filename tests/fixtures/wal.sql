@@ -0,0 +1,8 @@
+-- Two committed transactions, creating and filling `t`, that are only in the WAL: the copy
+-- is taken before the connection closes and checkpoints them into the database file.
+PRAGMA page_size = 512;
+PRAGMA journal_mode = WAL;
+PRAGMA wal_autocheckpoint = 0;
+CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT);
+INSERT INTO t (v) VALUES ('one'), ('two'), ('three');
+.system cp build.db snapshot.db && cp build.db-wal snapshot.db-wal
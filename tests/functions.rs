@@ -0,0 +1,67 @@
+mod common;
+
+use codecrafters_sqlite::SqliteError;
+use common::{fixture, open, query, query_err};
+
+#[test]
+fn abs_round_and_scalar_min_max() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT abs(-5), abs(2.5), abs(NULL)"), ["5|2.5|"]);
+    assert_eq!(query(&mut db, "SELECT round(3.14159, 2), round(2.5), round(-2.5), round(1.005, 2)"), ["3.14|3.0|-3.0|1.0"]);
+    assert_eq!(query(&mut db, "SELECT max(1, 7, 3), min(1, 7, 3), max(1, 'a', 2.5), min(3, NULL)"), ["7|1|a|"]);
+    // With one argument they are the aggregates; with more, they compare within each row.
+    assert_eq!(query(&mut db, "SELECT max(price), max(price, quantity) FROM orders WHERE id = 2"), ["15|15"]);
+    assert_eq!(query(&mut db, "SELECT id, min(price, quantity) FROM orders ORDER BY id"), ["1|2", "2|2", "3|", "4|2"]);
+}
+
+#[test]
+fn total_differs_from_sum_over_no_values() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT SUM(quantity), TOTAL(quantity) FROM orders"), ["17|17.0"]);
+    // No rows, or only NULLs: sum is NULL while total is 0.0.
+    assert_eq!(query(&mut db, "SELECT SUM(price), TOTAL(price), COUNT(*) FROM orders WHERE id > 9"), ["|0.0|0"]);
+    assert_eq!(query(&mut db, "SELECT SUM(quantity), total(quantity) FROM orders WHERE id = 3"), ["|0.0"]);
+    assert_eq!(query(&mut db, "SELECT item, SUM(quantity), TOTAL(quantity) FROM orders GROUP BY item"), ["bag||0.0", "book|2|2.0", "pen|15|15.0"]);
+    assert_eq!(query(&mut db, "SELECT total(price * 0.5) FROM orders"), ["29.5"]);
+}
+
+#[test]
+fn distinct_inside_aggregates() {
+    // Two pens share a price of 2.
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT SUM(price), SUM(DISTINCT price), SUM(ALL price) FROM orders"), ["59|57|59"]);
+    assert_eq!(query(&mut db, "SELECT AVG(price), AVG(DISTINCT price), TOTAL(DISTINCT price) FROM orders"), ["14.75|19.0|57.0"]);
+    assert_eq!(query(&mut db, "SELECT COUNT(price), COUNT(DISTINCT price) FROM orders"), ["4|3"]);
+    assert_eq!(query(&mut db, "SELECT item, SUM(DISTINCT price), COUNT(DISTINCT quantity) FROM orders GROUP BY item"), ["bag|40|0", "book|15|1", "pen|2|2"]);
+}
+
+#[test]
+fn date_and_strftime() {
+    let mut db = open(&fixture("orders"));
+    // Unix epoch integers, with the modifier saying so.
+    assert_eq!(query(&mut db, "SELECT date(0, 'unixepoch'), date(1700000000, 'unixepoch'), date(-86400, 'unixepoch')"),
+        ["1970-01-01|2023-11-14|1969-12-31"]);
+    assert_eq!(query(&mut db, "SELECT strftime('%Y-%m-%d %H:%M:%S', 1700000000, 'unixepoch'), strftime('%Y', 951782400, 'unixepoch')"),
+        ["2023-11-14 22:13:20|2000"]);
+    // ISO text, with or without a time.
+    assert_eq!(query(&mut db, "SELECT strftime('%Y', '2024-02-29'), date('2024-02-29 13:45:00'), strftime('%H:%M', '2024-02-29 13:45:07')"),
+        ["2024|2024-02-29|13:45"]);
+    assert_eq!(query(&mut db, "SELECT strftime('%S', '2024-01-01T00:00:09'), strftime('%%d=%d', '2024-03-05')"), ["09|%d=05"]);
+    // What isn't a date is NULL.
+    assert_eq!(query(&mut db, "SELECT date('nope'), date(NULL), date('2024-13-01')"), ["||"]);
+}
+
+#[test]
+fn count_of_a_constant_counts_rows() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT COUNT(*), COUNT(1), COUNT('x'), COUNT(2.5), COUNT(NULL), COUNT(quantity) FROM orders"), ["4|4|4|4|0|3"]);
+    assert_eq!(query(&mut db, "SELECT COUNT(1) FROM orders WHERE price = 2"), ["2"]);
+    assert_eq!(query(&mut db, "SELECT item, COUNT(1) FROM orders GROUP BY item"), ["bag|1", "book|1", "pen|2"]);
+
+    // Like COUNT(*), COUNT(1) only reads leaf cell counts, visiting no rows; COUNT(NULL) scans.
+    let mut db = open(&fixture("btree"));
+    db.set_max_rows_scanned(Some(10));
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM t"), ["300"]);
+    assert_eq!(query(&mut db, "SELECT COUNT(1) FROM t"), ["300"]);
+    assert!(matches!(query_err(&mut db, "SELECT COUNT(NULL) FROM t"), SqliteError::ScanLimitExceeded { limit: 10 }));
+}
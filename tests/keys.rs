@@ -0,0 +1,28 @@
+mod common;
+
+use common::{fixture, open, query};
+
+#[test]
+fn integer_primary_key_desc_is_not_the_rowid() {
+    let mut db = open(&fixture("keys"));
+    assert_eq!(query(&mut db, "SELECT rowid, id, v FROM aliased"), ["10|10|a", "20|20|b"]);
+    assert_eq!(query(&mut db, "SELECT rowid, id, v FROM not_aliased"), ["1|10|a", "2|20|b"]);
+    assert_eq!(query(&mut db, "SELECT v FROM not_aliased WHERE id = 20"), ["b"]);
+    assert_eq!(query(&mut db, "SELECT MAX(id), MAX(rowid) FROM not_aliased"), ["20|2"]);
+    assert_eq!(db.get("not_aliased", 2).unwrap().map(|row| row[0].to_string()), Some("20".to_string()));
+}
+
+#[test]
+fn rowids_use_the_full_64_bits() {
+    let mut db = open(&fixture("keys"));
+    let ids = [i64::MIN, -1, 1 << 32, 1 << 56, i64::MAX];
+    assert_eq!(db.rowids("big_ids").unwrap(), ids);
+    assert_eq!(query(&mut db, "SELECT rowid, v FROM big_ids WHERE id > 4294967295 AND id < 4294967297"), ["4294967296|two to the 32"]);
+    assert_eq!(query(&mut db, "SELECT id FROM big_ids ORDER BY rowid DESC LIMIT 1"), ["9223372036854775807"]);
+
+    // Through the index, whose records end with the rowid.
+    for (v, id) in [("min", i64::MIN), ("two to the 56", 1 << 56), ("max", i64::MAX)] {
+        assert_eq!(query(&mut db, &format!("SELECT id FROM big_ids WHERE v = '{}'", v)), [id.to_string()]);
+    }
+    assert_eq!(db.get("big_ids", 1 << 56).unwrap().map(|row| row[1].to_string()), Some("two to the 56".to_string()));
+}
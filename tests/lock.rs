@@ -0,0 +1,102 @@
+#![cfg(unix)]
+
+mod common;
+
+use codecrafters_sqlite::{Database, SqliteError};
+use common::{SAMPLE, open, query, temp_dir};
+use std::fs::{File, OpenOptions};
+use std::os::fd::AsRawFd;
+
+const PENDING_BYTE: i64 = 0x4000_0000;
+const RESERVED_BYTE: i64 = PENDING_BYTE + 1;
+const SHARED_FIRST: i64 = PENDING_BYTE + 2;
+
+/// A child process holding a write lock on part of a file, as another SQLite connection would.
+/// POSIX record locks belong to a process, so the lock can't be taken by the test itself.
+struct LockHolder {
+    pid: libc::pid_t,
+    release: libc::c_int,
+}
+
+impl LockHolder {
+    fn new(file: &File, start: i64, len: i64) -> Self {
+        let (mut ready, mut release) = ([0; 2], [0; 2]);
+        // SAFETY: the arrays have room for the two descriptors each call returns, and the child
+        // only makes async-signal-safe calls before exiting.
+        unsafe {
+            assert_eq!(libc::pipe(ready.as_mut_ptr()), 0);
+            assert_eq!(libc::pipe(release.as_mut_ptr()), 0);
+            let pid = libc::fork();
+            assert!(pid >= 0, "fork failed");
+            if pid == 0 {
+                libc::close(release[1]);
+                let mut lock: libc::flock = std::mem::zeroed();
+                lock.l_type = libc::F_WRLCK as _;
+                lock.l_whence = libc::SEEK_SET as _;
+                lock.l_start = start as _;
+                lock.l_len = len as _;
+                let status = libc::fcntl(file.as_raw_fd(), libc::F_SETLK, &lock);
+                libc::write(ready[1], [u8::from(status == 0)].as_ptr().cast(), 1);
+                // Hold the lock until the test closes its end of the pipe.
+                let mut byte = 0u8;
+                libc::read(release[0], (&raw mut byte).cast(), 1);
+                libc::_exit(0);
+            }
+            libc::close(ready[1]);
+            libc::close(release[0]);
+            let mut locked = 0u8;
+            assert_eq!(libc::read(ready[0], (&raw mut locked).cast(), 1), 1);
+            libc::close(ready[0]);
+            assert_eq!(locked, 1, "the child could not take the lock");
+            LockHolder { pid, release: release[1] }
+        }
+    }
+}
+
+impl Drop for LockHolder {
+    fn drop(&mut self) {
+        // SAFETY: the descriptor and the child are ours, and each is given up once.
+        unsafe {
+            libc::close(self.release);
+            libc::waitpid(self.pid, std::ptr::null_mut(), 0);
+        }
+    }
+}
+
+/// A writable handle, which `F_SETLK` needs for a write lock.
+fn writable(path: &str) -> File {
+    OpenOptions::new().read(true).write(true).open(path).unwrap()
+}
+
+fn copy_of_sample(test: &str) -> String {
+    let path = temp_dir(test).join("sample.db");
+    std::fs::copy(SAMPLE, &path).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn opens_a_file_another_handle_has_open() {
+    let _other = File::open(SAMPLE).unwrap();
+    let mut db = open(SAMPLE);
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM apples"), ["4"]);
+}
+
+#[test]
+fn reads_while_a_writer_holds_reserved() {
+    let path = copy_of_sample("reserved");
+    let _holder = LockHolder::new(&writable(&path), RESERVED_BYTE, 1);
+    let mut db = open(&path);
+    assert_eq!(query(&mut db, "SELECT name FROM apples WHERE id = 1"), ["Granny Smith"]);
+}
+
+#[test]
+fn refuses_a_file_locked_for_writing() {
+    let path = copy_of_sample("exclusive");
+    for (start, len) in [(PENDING_BYTE, 1), (SHARED_FIRST, 510)] {
+        let holder = LockHolder::new(&writable(&path), start, len);
+        assert!(matches!(Database::open(&path), Err(SqliteError::Locked)));
+        drop(holder);
+    }
+    // Once the writer is done, the file opens again.
+    open(&path);
+}
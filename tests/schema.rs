@@ -0,0 +1,144 @@
+mod common;
+
+use codecrafters_sqlite::{Database, SqlType, SqliteError};
+use common::{SAMPLE, fixture, open, query, query_err, shell};
+
+#[test]
+fn sqlite_schema_is_queryable() {
+    let mut db = open(SAMPLE);
+    assert_eq!(query(&mut db, "SELECT name FROM sqlite_schema WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name"),
+        ["apples", "oranges"]);
+    assert_eq!(query(&mut db, "SELECT tbl_name, rootpage FROM sqlite_master WHERE name = 'apples'"), ["apples|2"]);
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM sqlite_schema"), ["3"]);
+}
+
+#[test]
+fn quoted_table_names() {
+    let mut db = open(&fixture("names"));
+    assert_eq!(query(&mut db, r#"SELECT name FROM "my table""#), ["a", "b"]);
+    assert_eq!(query(&mut db, "SELECT id FROM [my table] WHERE name = 'b'"), ["2"]);
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM `my table`"), ["2"]);
+    assert_eq!(query(&mut db, r#"SELECT MAX(id) FROM "MY TABLE""#), ["2"]);
+    assert_eq!(db.table_names(), ["my table"]);
+}
+
+#[test]
+fn overflowing_schema_sql_is_read_whole() {
+    let mut db = open(&fixture("wide"));
+    let columns = db.columns("wide").unwrap();
+    assert_eq!(columns.len(), 60);
+    assert_eq!(columns[59].name, "c60");
+    assert!(db.schema_objects()[0].sql.ends_with("c60 INTEGER)"));
+    assert_eq!(query(&mut db, "SELECT c01, c30, c60 FROM wide"), ["1|30|60"]);
+}
+
+#[test]
+fn identifiers_ignore_ascii_case() {
+    let mut db = open(SAMPLE);
+    assert_eq!(query(&mut db, "SELECT NAME FROM Apples WHERE ID = 1"), ["Granny Smith"]);
+    assert_eq!(query(&mut db, "SELECT Name FROM MAIN.APPLES WHERE Color = 'Red'"), ["Fuji"]);
+    assert_eq!(query(&mut db, r#"SELECT "nAmE" FROM "APPLES" ORDER BY Id DESC LIMIT 1"#), ["Golden Delicious"]);
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM APPLES"), ["4"]);
+    assert_eq!(db.columns("APPLES").unwrap().len(), 3);
+    assert_eq!(db.get("Apples", 2).unwrap().map(|row| row[1].to_string()), Some("Fuji".to_string()));
+}
+
+#[test]
+fn analyzed_databases_hide_their_statistics() {
+    let mut db = open(&fixture("analyzed"));
+    assert_eq!(db.table_names(), ["items"]);
+    let columns: Vec<String> = db.columns("sqlite_stat1").unwrap().into_iter().map(|c| c.name).collect();
+    assert_eq!(columns, ["tbl", "idx", "stat"]);
+    assert_eq!(query(&mut db, "SELECT tbl, idx, stat FROM sqlite_stat1"), ["items|items_kind|4 2"]);
+    assert_eq!(query(&mut db, "SELECT id FROM items WHERE kind = 'a'"), ["1", "2"]);
+    db.integrity_check().unwrap();
+
+    let run = shell(&[&fixture("analyzed"), ".tables"]);
+    assert_eq!(run.stdout, "items \n");
+}
+
+#[test]
+fn virtual_tables_cannot_be_scanned() {
+    let mut db = open(&fixture("virtual"));
+    let message = "cannot scan 'notes' directly: it has no b-tree (rootpage 0)";
+    for sql in ["SELECT * FROM notes", "SELECT COUNT(*) FROM notes", "SELECT body FROM notes WHERE rowid = 1"] {
+        assert!(matches!(query_err(&mut db, sql), SqliteError::UnsupportedSql(m) if m == message), "{}", sql);
+    }
+    assert!(matches!(db.rowids("notes"), Err(SqliteError::UnsupportedSql(m)) if m == message));
+    assert!(matches!(db.table_cursor("notes").err(), Some(SqliteError::UnsupportedSql(m)) if m == message));
+    // Its shadow tables are ordinary tables.
+    assert_eq!(query(&mut db, "SELECT id, c0 FROM notes_content"), ["1|first note", "2|second note"]);
+    db.integrity_check().unwrap();
+}
+
+#[test]
+fn header_pragmas() {
+    let mut db = open(&fixture("pragmas"));
+    assert_eq!(query(&mut db, "PRAGMA user_version"), ["42"]);
+    assert_eq!(query(&mut db, "pragma Application_ID;"), ["1096044889"]);
+    // Creating each of the two tables bumped it.
+    assert_eq!(query(&mut db, "PRAGMA main.schema_version"), ["2"]);
+    assert_eq!(db.query("PRAGMA user_version").unwrap().column_names(), ["user_version"]);
+
+    let mut db = open(SAMPLE);
+    assert_eq!(query(&mut db, "PRAGMA user_version"), ["0"]);
+    db.attach("tagged", &fixture("pragmas")).unwrap();
+    assert_eq!(query(&mut db, "PRAGMA tagged.user_version"), ["42"]);
+}
+
+#[test]
+fn multiline_create_with_comments_and_parentheses() {
+    let mut db = open(&fixture("commented"));
+    let columns: Vec<(String, SqlType)> = db.columns("ledger").unwrap().into_iter().map(|c| (c.name, c.affinity)).collect();
+    let expected = [("id", SqlType::Integer), ("amount", SqlType::Numeric), ("memo", SqlType::Text), ("booked", SqlType::Text)];
+    assert_eq!(columns, expected.map(|(name, affinity)| (name.to_string(), affinity)));
+    assert_eq!(query(&mut db, "SELECT * FROM ledger"), ["1|12.5|lunch|2024-01-02", "2|3|n/a|"]);
+    assert_eq!(query(&mut db, "SELECT memo, amount FROM ledger WHERE booked IS NULL"), ["n/a|3"]);
+}
+
+#[test]
+fn schema_spread_over_several_pages() {
+    let mut db = open(&fixture("many_tables"));
+    assert_eq!(db.table_names().len(), 100);
+    assert_eq!(db.table_names()[99], "t099");
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM sqlite_schema"), ["100"]);
+    assert_eq!(query(&mut db, "SELECT v FROM t000"), ["first"]);
+    assert_eq!(query(&mut db, "SELECT v FROM t099"), ["last"]);
+    db.integrity_check().unwrap();
+}
+
+#[test]
+fn page_one_must_be_a_table_page() {
+    for (page_type, detail) in [(0x0a, "page 1: unexpected page type LeafIndex in a table b-tree"), (0x02, "page 1: unexpected page type InteriorIndex in a table b-tree"), (0x00, "invalid page type 0x00")] {
+        let mut image = std::fs::read(SAMPLE).unwrap();
+        image[100] = page_type;
+        match Database::open_bytes(image) {
+            Err(SqliteError::Corrupt { page: Some(1), detail: d }) => assert_eq!(d, detail),
+            other => panic!("page type {:#04x}: expected Corrupt, got {:?}", page_type, other.err()),
+        }
+    }
+}
+
+#[test]
+fn schema_btree_of_three_levels() {
+    let mut db = open(&fixture("deep_schema"));
+    assert_eq!(db.table_names().len(), 200);
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM sqlite_schema WHERE type = 'index'"), ["200"]);
+    assert_eq!(query(&mut db, "SELECT sql FROM sqlite_schema WHERE name = 't123_v'"), ["CREATE INDEX t123_v ON t123 (v)"]);
+    assert_eq!(query(&mut db, "SELECT v FROM t000"), ["first"]);
+    // Found through the last table's index.
+    assert_eq!(query(&mut db, "SELECT id FROM t199 WHERE v = 'last'"), ["1"]);
+    db.integrity_check().unwrap();
+}
+
+#[test]
+fn tables_resolve_by_name_then_tbl_name() {
+    // Where the two coincide, as SQLite writes them, either spelling finds the table.
+    let mut db = open(SAMPLE);
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM APPLES"), ["4"]);
+
+    let mut db = open(&fixture("renamed"));
+    assert_eq!(query(&mut db, "SELECT item FROM Stock WHERE id = 2"), ["nut"]);
+    assert_eq!(query(&mut db, "SELECT item FROM INVENTORY WHERE id = 1"), ["bolt"]);
+    assert!(matches!(query_err(&mut db, "SELECT * FROM other"), SqliteError::NoSuchTable(name) if name == "other"));
+}
@@ -0,0 +1,413 @@
+mod common;
+
+use codecrafters_sqlite::{SqliteError, Value};
+use common::{SAMPLE, fixture, open, query, query_err, query_values, shell};
+
+#[test]
+fn literals_next_to_columns() {
+    let mut db = open(SAMPLE);
+    assert_eq!(query(&mut db, "SELECT 1, name FROM apples WHERE id < 3"), ["1|Granny Smith", "1|Fuji"]);
+    assert_eq!(query(&mut db, "SELECT 'const' AS tag, id, 2.5 FROM apples WHERE id < 3"), ["const|1|2.5", "const|2|2.5"]);
+    let rows = db.query("SELECT 'const' AS tag, id FROM apples").unwrap();
+    assert_eq!(rows.column_names(), ["tag", "id"]);
+}
+
+#[test]
+fn aggregates_of_expressions() {
+    // The bag's unknown quantity makes its product NULL, which the aggregates skip.
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT SUM(price * quantity), AVG(price + quantity), COUNT(price * quantity) FROM orders"), ["60|12.0|3"]);
+    assert_eq!(query(&mut db, "SELECT item, SUM(price * quantity) FROM orders GROUP BY item"), ["bag|", "book|30", "pen|30"]);
+}
+
+#[test]
+fn comparison_operator_spellings() {
+    let mut db = open(SAMPLE);
+    assert_eq!(query(&mut db, "SELECT id FROM apples WHERE id == 1"), ["1"]);
+    assert_eq!(query(&mut db, "SELECT id FROM apples WHERE id = 1"), ["1"]);
+    assert_eq!(query(&mut db, "SELECT id FROM apples WHERE id <> 2"), ["1", "3", "4"]);
+    assert_eq!(query(&mut db, "SELECT id FROM apples WHERE id != 2"), ["1", "3", "4"]);
+    assert_eq!(query(&mut db, "SELECT id == 1, id <> 1, id != 1 FROM apples WHERE id < 3"), ["1|0|0", "0|1|1"]);
+}
+
+#[test]
+fn parentheses_group_conditions() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE (item = 'pen' OR item = 'book') AND price > 10"), ["2"]);
+    // AND binds tighter than OR.
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE item = 'pen' OR item = 'book' AND price > 10"), ["1", "2", "4"]);
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE NOT (item = 'pen' OR (price > 10 AND quantity IS NULL))"), ["2"]);
+}
+
+#[test]
+fn order_by_expression() {
+    let mut db = open(&fixture("orders"));
+    // The bag's NULL product sorts first ascending, and so last descending.
+    assert_eq!(query(&mut db, "SELECT id FROM orders ORDER BY price * quantity DESC"), ["2", "1", "4", "3"]);
+    assert_eq!(query(&mut db, "SELECT id FROM orders ORDER BY price * quantity"), ["3", "4", "1", "2"]);
+    assert_eq!(query(&mut db, "SELECT item FROM orders ORDER BY LENGTH(item) DESC, id"), ["book", "pen", "bag", "pen"]);
+}
+
+#[test]
+fn subquery_in_from() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT x FROM (SELECT price * 2 AS x FROM orders) WHERE x > 5"), ["30", "80"]);
+    assert_eq!(query(&mut db, "SELECT item, total FROM (SELECT item, SUM(price * quantity) AS total FROM orders GROUP BY item) AS d \
+        WHERE total IS NOT NULL ORDER BY total DESC, item"), ["book|30", "pen|30"]);
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM (SELECT DISTINCT item FROM orders)"), ["3"]);
+}
+
+#[test]
+fn scalar_subquery_in_where() {
+    // The average price is 14.75.
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE price > (SELECT AVG(price) FROM orders)"), ["2", "3"]);
+    // No row makes the subquery NULL, which matches nothing.
+    assert!(query(&mut db, "SELECT id FROM orders WHERE quantity = (SELECT quantity FROM orders WHERE id = 99)").is_empty());
+
+    assert!(matches!(query_err(&mut db, "SELECT id FROM orders WHERE price = (SELECT price FROM orders WHERE id > 1)"),
+        SqliteError::TypeError(message) if message == "scalar subquery returned more than one row"));
+    assert!(matches!(query_err(&mut db, "SELECT id FROM orders WHERE price = (SELECT price, id FROM orders)"),
+        SqliteError::UnsupportedSql(message) if message == "sub-select returns 2 columns - expected 1"));
+}
+
+#[test]
+fn in_subquery() {
+    let mut db = open(&fixture("family"));
+    assert_eq!(query(&mut db, "SELECT name FROM parents WHERE id IN (SELECT parent_id FROM children)"), ["ann", "cy"]);
+    assert_eq!(query(&mut db, "SELECT name FROM children WHERE parent_id IN (SELECT id FROM parents WHERE name > 'b')"), ["fay"]);
+    assert_eq!(query(&mut db, "SELECT name FROM parents WHERE id NOT IN (SELECT parent_id FROM children WHERE parent_id IS NOT NULL)"), ["bob"]);
+    // With a NULL among the children's parents, NOT IN is never true.
+    assert!(query(&mut db, "SELECT name FROM parents WHERE id NOT IN (SELECT parent_id FROM children)").is_empty());
+}
+
+#[test]
+fn column_lists_span_lines() {
+    let mut db = open(SAMPLE);
+    assert_eq!(query(&mut db, "SELECT name,\ncolor FROM apples WHERE id = 1"), ["Granny Smith|Light Green"]);
+    assert_eq!(query(&mut db, "select id\n  from apples\n where\n  color = 'Red'"), ["2"]);
+}
+
+#[test]
+fn order_by_with_limit_matches_a_full_sort() {
+    let mut db = open(&fixture("btree"));
+    let sorted = query(&mut db, "SELECT id FROM t ORDER BY id % 7, v DESC");
+    assert_eq!(sorted.len(), 300);
+    assert_eq!(query(&mut db, "SELECT id FROM t ORDER BY id % 7, v DESC LIMIT 10"), sorted[..10]);
+    assert_eq!(query(&mut db, "SELECT id FROM t ORDER BY id % 7, v DESC LIMIT 5 OFFSET 3"), sorted[3..8]);
+    // Rows with equal keys keep the order a full sort gives them.
+    let ties = query(&mut db, "SELECT id FROM t ORDER BY id % 7");
+    assert_eq!(query(&mut db, "SELECT id FROM t ORDER BY id % 7 LIMIT 50"), ties[..50]);
+    assert_eq!(query(&mut db, "SELECT id FROM t ORDER BY id % 7 LIMIT 400 OFFSET 290"), ties[290..]);
+}
+
+#[test]
+fn order_by_with_limit_sorts_rows_as_the_scan_reads_them() {
+    let mut db = open(&fixture("btree"));
+    // The sort key of row 5 fails. A full sort has read every page of `t` by the time it gets
+    // there; the LIMIT's heap takes rows from the scan one at a time, and stops in the first leaf.
+    let failing = "abs(CASE WHEN id = 5 THEN -9223372036854775808 ELSE id END)";
+    let sql = format!("SELECT id FROM t ORDER BY {}", failing);
+    assert!(matches!(query_err(&mut db, &sql), SqliteError::TypeError(_)));
+    let all_pages = db.pages_read();
+    assert!(all_pages > 20, "{}", all_pages);
+    assert!(matches!(query_err(&mut db, &format!("{} LIMIT 3", sql)), SqliteError::TypeError(_)));
+    assert_eq!(db.pages_read(), 2);
+
+    assert_eq!(query(&mut db, "SELECT id FROM t ORDER BY v DESC LIMIT 2 OFFSET 1"), ["299", "298"]);
+    assert_eq!(db.pages_read(), all_pages);
+}
+
+#[test]
+fn selecting_through_views() {
+    let mut db = open(&fixture("views"));
+    assert_eq!(query(&mut db, "SELECT * FROM cheap"), ["1|pen|20", "4|pen|10"]);
+    assert_eq!(db.query("SELECT * FROM cheap").unwrap().column_names(), ["id", "item", "cost"]);
+    assert_eq!(query(&mut db, "SELECT id FROM cheap WHERE cost > 15"), ["1"]);
+    assert_eq!(query(&mut db, "SELECT COUNT(*), MAX(cost) FROM cheap"), ["2|20"]);
+    assert_eq!(query(&mut db, "SELECT * FROM pricey_items"), ["bag", "book"]);
+    // A view over another view.
+    assert_eq!(query(&mut db, "SELECT total FROM cheap_total"), ["30"]);
+    assert!(matches!(query_err(&mut db, "SELECT price FROM cheap"),
+        SqliteError::NoSuchColumn { column, table } if column == "price" && table.as_deref() == Some("cheap")));
+}
+
+#[test]
+fn concatenation() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT item || ' x' || quantity FROM orders WHERE id < 3"), ["pen x10", "book x2"]);
+    // Numbers concatenate as their text; a NULL operand makes the result NULL.
+    assert_eq!(query(&mut db, "SELECT 1 || 2, 2.5 || 'b', item || quantity IS NULL FROM orders WHERE id = 3"), ["12|2.5b|1"]);
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE item || price = 'pen2'"), ["1", "4"]);
+    assert!(matches!(query_err(&mut db, "SELECT 'n' | 1"), SqliteError::UnsupportedSql(message) if message == r#"unrecognized token: "|""#));
+}
+
+#[test]
+fn inner_joins_on_a_condition() {
+    let mut db = open(&fixture("family"));
+    let pairs = ["dee|ann", "eve|ann", "fay|cy"];
+    assert_eq!(query(&mut db, "SELECT c.name, p.name FROM children c JOIN parents p ON c.parent_id = p.id"), pairs);
+    assert_eq!(query(&mut db, "SELECT children.name, parents.name FROM children INNER JOIN parents ON parents.id = children.parent_id"), pairs);
+    // Looked up from either side, the rows pair up the same way.
+    assert_eq!(query(&mut db, "SELECT c.name, p.name FROM parents AS p JOIN children AS c ON p.id = c.parent_id ORDER BY c.id"), pairs);
+    assert_eq!(query(&mut db, "SELECT * FROM children c JOIN parents p ON c.parent_id = p.id WHERE c.id = 1"), ["1|1|dee|1|ann"]);
+    assert_eq!(query(&mut db, "SELECT p.*, count(*) FROM parents p JOIN children c ON c.parent_id = p.id GROUP BY p.id"), ["1|ann|2", "3|cy|1"]);
+    // Any other condition tries every pair of rows, as do joins without one.
+    assert_eq!(query(&mut db, "SELECT c.name, p.name FROM children c JOIN parents p ON c.parent_id > p.id"), ["fay|ann", "fay|bob"]);
+    assert_eq!(query(&mut db, "SELECT c.name, p.name FROM children c, parents p WHERE c.parent_id = p.id AND p.name = 'cy'"), ["fay|cy"]);
+    assert_eq!(query(&mut db, "SELECT count(*) FROM children CROSS JOIN parents"), ["12"]);
+
+    let names = db.prepare("SELECT c.name, p.*, c.id AS child FROM children c JOIN parents p ON c.parent_id = p.id").unwrap();
+    assert_eq!(names.column_names(), ["name", "id", "name", "child"]);
+}
+
+#[test]
+fn join_on_an_integer_primary_key_looks_rows_up() {
+    let mut db = open(&fixture("btree"));
+    let row_5 = format!("5|row 005 {}", "x".repeat(20));
+    // Trying every row of `t` reads all of its pages.
+    assert_eq!(query(&mut db, "SELECT s.x, t.v FROM s JOIN t ON t.id + 0 = s.x"), [row_5.as_str()]);
+    let all_pages = db.pages_read();
+    assert!(all_pages > 20, "{}", all_pages);
+    // Looking rowid 5 up reads the one leaf of `s`, then the root of `t` and the leaf holding it.
+    for sql in ["SELECT s.x, t.v FROM s JOIN t ON t.id = s.x", "SELECT s.x, t.v FROM t JOIN s ON s.x = t.id"] {
+        assert_eq!(query(&mut db, sql), [row_5.as_str()], "{}", sql);
+        assert_eq!(db.pages_read(), 3, "{}", sql);
+    }
+}
+
+#[test]
+fn qualified_column_names() {
+    let mut db = open(&fixture("family"));
+    assert_eq!(query(&mut db, "SELECT children.name FROM children WHERE children.id = 2"), ["eve"]);
+    assert_eq!(query(&mut db, "SELECT c.rowid, c.* FROM children AS c LIMIT 1"), ["1|1|1|dee"]);
+    assert!(matches!(query_err(&mut db, "SELECT name FROM children JOIN parents ON parent_id = parents.id"),
+        SqliteError::UnsupportedSql(message) if message == "ambiguous column name: name"));
+    assert!(matches!(query_err(&mut db, "SELECT x.name FROM children c JOIN parents p ON c.parent_id = p.id"),
+        SqliteError::NoSuchColumn { column, .. } if column == "x.name"));
+    // An alias hides the table's own name.
+    assert!(matches!(query_err(&mut db, "SELECT children.name FROM children c"),
+        SqliteError::NoSuchColumn { column, .. } if column == "children.name"));
+}
+
+#[test]
+fn unsupported_joins_are_rejected() {
+    let mut db = open(&fixture("family"));
+    for sql in [
+        "SELECT name FROM children LEFT OUTER JOIN parents ON parent_id = parents.id",
+        "SELECT * FROM children NATURAL JOIN parents",
+        "SELECT * FROM children JOIN parents USING (id)",
+        "SELECT * FROM children c JOIN parents p ON c.parent_id = p.id JOIN children d ON d.id = c.id",
+        "SELECT * FROM children, parents, children",
+        "SELECT * FROM (SELECT * FROM children) JOIN parents",
+    ] {
+        assert!(matches!(query_err(&mut db, sql),
+            SqliteError::UnsupportedSql(message) if message == "only inner joins of two tables are supported"), "{}", sql);
+    }
+}
+
+#[test]
+fn empty_tables() {
+    let mut db = open(&fixture("empty"));
+    for sql in [
+        "SELECT * FROM vacant",
+        "SELECT name, COUNT(*) FROM vacant GROUP BY name",
+        "SELECT DISTINCT name FROM vacant",
+        "SELECT * FROM vacant ORDER BY n LIMIT 3",
+        "SELECT * FROM vacant WHERE name = 'x'",
+        "SELECT * FROM vacant WHERE id = 1",
+        "SELECT n FROM vacant WHERE n IN (SELECT n FROM vacant)",
+    ] {
+        assert!(query(&mut db, sql).is_empty(), "{}", sql);
+    }
+    assert_eq!(query(&mut db, "SELECT COUNT(*), AVG(n), MIN(n), MAX(n), SUM(n), TOTAL(n) FROM vacant"), ["0|||||0.0"]);
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM vacant WHERE name = 'x'"), ["0"]);
+
+    // Without rows the shell prints nothing at all, not even headers.
+    for args in [&["-header"][..], &["-json"], &["-csv", "-header"]] {
+        let run = shell(&[args, &[fixture("empty").as_str(), "SELECT * FROM vacant"]].concat());
+        assert!(run.success, "{}", run.stderr);
+        assert_eq!(run.stdout, "", "{:?}", args);
+    }
+}
+
+#[test]
+fn case_expressions() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT id, CASE WHEN price > 10 THEN 'pricey' WHEN price > 5 THEN 'mid' ELSE 'cheap' END FROM orders"),
+        ["1|cheap", "2|pricey", "3|pricey", "4|cheap"]);
+    // The simple form; without an ELSE, no match is NULL.
+    assert_eq!(query(&mut db, "SELECT id, CASE item WHEN 'pen' THEN 'writing' WHEN 'book' THEN 'reading' END FROM orders"),
+        ["1|writing", "2|reading", "3|", "4|writing"]);
+    assert_eq!(query(&mut db, "SELECT CASE NULL WHEN NULL THEN 'x' ELSE 'y' END, CASE WHEN 0 THEN 1 END"), ["y|"]);
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE CASE WHEN quantity IS NULL THEN 1 ELSE quantity > 4 END"), ["1", "3", "4"]);
+
+    // Only the branch taken is evaluated.
+    let overflow = "abs(-9223372036854775807 - 1)";
+    assert_eq!(query(&mut db, &format!("SELECT CASE WHEN id > 0 THEN 'skipped' ELSE {} END FROM orders WHERE id = 1", overflow)), ["skipped"]);
+    assert_eq!(query(&mut db, &format!("SELECT CASE id WHEN 1 THEN 'one' ELSE {} END FROM orders WHERE id = 1", overflow)), ["one"]);
+    assert!(matches!(query_err(&mut db, &format!("SELECT CASE WHEN id > 1 THEN 'skipped' ELSE {} END FROM orders WHERE id = 1", overflow)),
+        SqliteError::TypeError(message) if message == "integer overflow"));
+}
+
+#[test]
+fn limit_applies_after_grouping_and_distinct() {
+    // The first two rows read are a pen and a book; limiting before grouping would count those.
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT item, COUNT(*) FROM orders GROUP BY item LIMIT 2"), ["bag|1", "book|1"]);
+    assert_eq!(query(&mut db, "SELECT item, SUM(quantity) FROM orders GROUP BY item HAVING COUNT(*) > 1 LIMIT 1"), ["pen|15"]);
+    assert_eq!(query(&mut db, "SELECT item FROM orders GROUP BY item HAVING SUM(price) > 4 ORDER BY item DESC LIMIT 5 OFFSET 1"), ["bag"]);
+    assert_eq!(query(&mut db, "SELECT DISTINCT item FROM orders LIMIT 3"), ["pen", "book", "bag"]);
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM orders LIMIT 1"), ["4"]);
+}
+
+#[test]
+fn leftover_text_is_a_syntax_error() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT item FROM orders o WHERE id = 1"), ["pen"]);
+    for (sql, token) in [
+        ("SELECT item FROM orders WHERE id = 1 WHER", "WHER"),
+        // `WHER` is taken for an alias, as in SQLite.
+        ("SELECT item FROM orders WHER id = 1", "id"),
+        ("SELECT item FROM orders GROUP BY item HAVIN COUNT(*) > 1", "HAVIN"),
+        ("SELECT item FROM orders LIMIT 1 2", "2"),
+    ] {
+        assert!(matches!(query_err(&mut db, sql), SqliteError::UnsupportedSql(m) if m == format!("near \"{}\": syntax error", token)), "{}", sql);
+    }
+}
+
+#[test]
+fn null_literal() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query_values(&mut db, "SELECT NULL, null IS NULL"), [[Value::Null, Value::Integer(1)]]);
+    assert_eq!(shell(&[&fixture("orders"), "SELECT NULL"]).stdout, "\n");
+    // Comparing with NULL is never true; IS [NOT] NULL is the way to test for it.
+    assert!(query(&mut db, "SELECT id FROM orders WHERE quantity = NULL").is_empty());
+    assert!(query(&mut db, "SELECT id FROM orders WHERE quantity != NULL").is_empty());
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE quantity IS NULL"), ["3"]);
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE quantity IS NOT NULL"), ["1", "2", "4"]);
+    assert_eq!(query(&mut db, "SELECT NULL = NULL, NULL IS NULL, 1 IS NOT NULL"), ["|1|1"]);
+}
+
+#[test]
+fn true_and_false() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query_values(&mut db, "SELECT TRUE, false"), [[Value::Integer(1), Value::Integer(0)]]);
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE (quantity > 4) = TRUE"), ["1", "4"]);
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE (price > 10) = false"), ["1", "4"]);
+    assert_eq!(query(&mut db, "SELECT COUNT(*) FROM orders WHERE FALSE"), ["0"]);
+    assert_eq!(query(&mut db, "SELECT true + 1, 1 IS TRUE, NULL IS TRUE"), ["2|1|0"]);
+}
+
+#[test]
+fn result_columns_may_repeat() {
+    let mut db = open(SAMPLE);
+    assert_eq!(query(&mut db, "SELECT name, name, id FROM apples WHERE id < 3"), ["Granny Smith|Granny Smith|1", "Fuji|Fuji|2"]);
+    assert_eq!(db.query("SELECT name, name, id FROM apples").unwrap().column_names(), ["name", "name", "id"]);
+    assert_eq!(query(&mut db, "SELECT id, *, id FROM apples WHERE id = 1"), ["1|1|Granny Smith|Light Green|1"]);
+    assert_eq!(query(&mut db, "SELECT name, color, name FROM apples ORDER BY name LIMIT 1"), ["Fuji|Red|Fuji"]);
+    assert_eq!(query(&mut db, "SELECT DISTINCT color, color FROM apples WHERE id < 3"), ["Light Green|Light Green", "Red|Red"]);
+}
+
+#[test]
+fn comments_are_ignored() {
+    let mut db = open(SAMPLE);
+    assert_eq!(query(&mut db, "SELECT name FROM apples WHERE id = 1 -- trailing comment"), ["Granny Smith"]);
+    assert_eq!(query(&mut db, "SELECT /* which */ name FROM apples /* where */ WHERE id = 2"), ["Fuji"]);
+    assert_eq!(query(&mut db, "SELECT name FROM apples -- ; not the end\nWHERE id = 3;"), ["Honeycrisp"]);
+    // Inside a string they are just text.
+    assert_eq!(query(&mut db, "SELECT 'a--b', 'c/*d*/' FROM apples WHERE id = 1"), ["a--b|c/*d*/"]);
+    assert_eq!(codecrafters_sqlite::strip_comments("SELECT '--' -- gone\n, 1 /* gone */"), "SELECT '--'  , 1  ");
+}
+
+#[test]
+fn comparisons_as_result_columns() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT id, price > 10, quantity >= 5, item = 'pen', price BETWEEN 2 AND 15 FROM orders"),
+        ["1|0|1|1|1", "2|1|0|0|1", "3|1||0|0", "4|0|1|1|1"]);
+    assert_eq!(query_values(&mut db, "SELECT price > 10, quantity < 0 FROM orders WHERE id = 3"), [[Value::Integer(1), Value::Null]]);
+    assert_eq!(query(&mut db, "SELECT SUM(price > 10), COUNT(*) FROM orders"), ["2|4"]);
+}
+
+#[test]
+fn order_by_rowid_reads_the_btree_in_order() {
+    let mut db = open(&fixture("btree"));
+    assert_eq!(query(&mut db, "SELECT id FROM t ORDER BY v DESC LIMIT 1"), ["300"]);
+    let all_pages = db.pages_read();
+
+    // The root and the last leaf, rather than every page.
+    for sql in ["SELECT id FROM t ORDER BY rowid DESC LIMIT 3", "SELECT id FROM t ORDER BY id DESC LIMIT 3"] {
+        assert_eq!(query(&mut db, sql), ["300", "299", "298"]);
+        assert_eq!(db.pages_read(), 2, "{}", sql);
+    }
+    assert_eq!(query(&mut db, "SELECT id FROM t ORDER BY rowid LIMIT 2 OFFSET 5"), ["6", "7"]);
+    assert_eq!(db.pages_read(), 2);
+    assert_eq!(query(&mut db, "SELECT id FROM t WHERE id < 100 ORDER BY rowid DESC LIMIT 2"), ["99", "98"]);
+    assert!(db.pages_read() < all_pages / 4, "{} of {} pages", db.pages_read(), all_pages);
+    assert_eq!(query(&mut db, "SELECT id FROM t ORDER BY rowid DESC").len(), 300);
+}
+
+#[test]
+fn distinct_order_by_must_name_a_result_column() {
+    let mut db = open(SAMPLE);
+    let error = query_err(&mut db, "SELECT DISTINCT color FROM apples ORDER BY name");
+    assert!(matches!(&error, SqliteError::UnsupportedSql(_)), "{:?}", error);
+    assert_eq!(error.to_string(), "1st ORDER BY term does not match any column in the result set");
+    assert_eq!(query_err(&mut db, "SELECT DISTINCT color FROM apples ORDER BY color, id").to_string(), "2nd ORDER BY term does not match any column in the result set");
+
+    // Result columns by name, alias, expression or position are fine, and without DISTINCT
+    // any column may be used.
+    assert_eq!(query(&mut db, "SELECT DISTINCT color AS c FROM apples ORDER BY c DESC LIMIT 2"), ["Yellow", "Red"]);
+    assert_eq!(query(&mut db, "SELECT DISTINCT length(color) FROM apples ORDER BY length(color)"), ["3", "6", "9", "11"]);
+    assert_eq!(query(&mut db, "SELECT DISTINCT color FROM apples ORDER BY 1 LIMIT 1"), ["Blush Red"]);
+    assert_eq!(query(&mut db, "SELECT color FROM apples ORDER BY name LIMIT 1"), ["Red"]);
+}
+
+#[test]
+fn keywords_in_any_case() {
+    let mut db = open(SAMPLE);
+    let sql = "SeLeCt NaMe, CoLoR fRoM ApPlEs wHeRe Id BeTwEeN 2 aNd 3 AnD name NoT lIkE 'h%' OrDeR bY id DeSc LiMiT 5";
+    assert_eq!(query(&mut db, sql), ["Fuji|Red"]);
+    // Column names keep the case they were written in.
+    assert_eq!(db.query(sql).unwrap().column_names(), ["NaMe", "CoLoR"]);
+    assert_eq!(query(&mut db, "select id from apples where null is NULL and name iS nOt NuLl limit 1"), ["1"]);
+    // A reserved word cannot name a column, in any case.
+    assert!(matches!(query_err(&mut db, "SELECT and FROM apples"), SqliteError::UnsupportedSql(message) if message.ends_with("syntax error")));
+}
+
+#[test]
+fn except_and_intersect() {
+    let mut db = open(&fixture("family"));
+    // Parents 1, 2 and 3 against the children's parent ids 1, 1, 3 and NULL.
+    assert_eq!(query(&mut db, "SELECT id FROM parents EXCEPT SELECT parent_id FROM children"), ["2"]);
+    assert_eq!(query(&mut db, "SELECT parent_id FROM children EXCEPT SELECT id FROM parents"), [""]);
+    assert_eq!(query(&mut db, "SELECT parent_id FROM children INTERSECT SELECT id FROM parents"), ["1", "3"]);
+    // Both drop duplicates, NULL included, like UNION.
+    assert_eq!(query(&mut db, "SELECT parent_id FROM children INTERSECT SELECT parent_id FROM children"), ["", "1", "3"]);
+    assert_eq!(query(&mut db, "SELECT parent_id FROM children UNION SELECT id FROM parents"), ["", "1", "2", "3"]);
+    assert!(query(&mut db, "SELECT name FROM parents INTERSECT SELECT name FROM children").is_empty());
+
+    // Operators apply left to right, and ORDER BY to the combined rows.
+    assert_eq!(query(&mut db, "SELECT id FROM parents UNION SELECT id FROM children EXCEPT SELECT parent_id FROM children ORDER BY 1 DESC"), ["4", "2"]);
+    assert_eq!(
+        query_err(&mut db, "SELECT id, name FROM parents EXCEPT SELECT id FROM children").to_string(),
+        "SELECTs to the left and right of EXCEPT do not have the same number of result columns"
+    );
+}
+
+#[test]
+fn unknown_columns_name_their_table() {
+    let mut db = open(SAMPLE);
+    for (sql, column) in [
+        ("SELECT name FROM apples WHERE nonexistent = 1", "nonexistent"),
+        ("SELECT name FROM apples WHERE id = 1 AND (color = 'Red' OR shade > 2)", "shade"),
+        ("SELECT COUNT(*) FROM apples WHERE bogus IS NULL", "bogus"),
+        ("SELECT name FROM apples ORDER BY nope", "nope"),
+    ] {
+        let error = query_err(&mut db, sql);
+        assert!(matches!(&error, SqliteError::NoSuchColumn { column: c, table: Some(t) } if c == column && t == "apples"), "{}: {:?}", sql, error);
+        assert_eq!(error.to_string(), format!("no such column: {} in table apples", column));
+    }
+    // A subquery has no table name to give.
+    assert_eq!(query_err(&mut db, "SELECT x FROM (SELECT id FROM apples)").to_string(), "no such column: x");
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use codecrafters_sqlite::Value;
+use common::{SAMPLE, fixture, open};
+
+#[test]
+fn values_serialize_by_storage_class() {
+    let json = |value: Value| serde_json::to_string(&value).unwrap();
+    assert_eq!(json(Value::Null), "null");
+    assert_eq!(json(Value::Integer(-7)), "-7");
+    assert_eq!(json(Value::Real(2.5)), "2.5");
+    assert_eq!(json(Value::Text("a \"b\"".to_string())), r#""a \"b\"""#);
+    assert_eq!(json(Value::Blob(vec![0, 255])), "[0,255]");
+}
+
+#[test]
+fn rows_serialize_as_maps_in_column_order() {
+    let mut db = open(&fixture("values"));
+    let rows = db.query("SELECT t, v FROM mixed").unwrap().into_rows().unwrap();
+    let expected = r#"[{"t":"text","v":null},{"t":[1,2],"v":1},{"t":"x","v":2.5},{"t":null,"v":"text"},{"t":"3","v":[0,255]}]"#;
+    assert_eq!(serde_json::to_string(&rows).unwrap(), expected);
+    // The collected rows stay put, so serializing them again gives the same result.
+    assert_eq!(serde_json::to_string(&rows).unwrap(), expected);
+
+    // Rows already iterated are not collected.
+    let mut db = open(SAMPLE);
+    let mut rows = db.query("SELECT id, name FROM apples WHERE id < 3").unwrap();
+    rows.next().unwrap().unwrap();
+    assert_eq!(serde_json::to_value(rows.into_rows().unwrap()).unwrap(), serde_json::json!([{"id": 2, "name": "Fuji"}]));
+}
+
+#[test]
+fn a_single_row_serializes_as_a_map() {
+    let mut db = open(SAMPLE);
+    let rows = db.query("SELECT id, name, color FROM apples WHERE id = 2").unwrap().into_rows().unwrap();
+    assert_eq!(serde_json::to_string(&rows[0]).unwrap(), r#"{"id":2,"name":"Fuji","color":"Red"}"#);
+    // Serializing reads nothing, so it gives the same result every time.
+    assert_eq!(serde_json::to_string(&rows[0]).unwrap(), r#"{"id":2,"name":"Fuji","color":"Red"}"#);
+}
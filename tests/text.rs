@@ -0,0 +1,65 @@
+mod common;
+
+use codecrafters_sqlite::{SqliteError, Value};
+use common::{SAMPLE, fixture, open, query, query_err, query_values};
+
+#[test]
+fn like_with_and_without_escape() {
+    let mut db = open(&fixture("text"));
+    assert_eq!(query(&mut db, "SELECT s FROM labels WHERE s LIKE '100%'"), ["100%", "100", "1000"]);
+    assert_eq!(query(&mut db, "SELECT s FROM labels WHERE s LIKE 'a_b'"), ["a_b", "axb", r"a\b"]);
+    assert_eq!(query(&mut db, r"SELECT s FROM labels WHERE s LIKE '100\%' ESCAPE '\'"), ["100%"]);
+    assert_eq!(query(&mut db, r"SELECT s FROM labels WHERE s LIKE 'a\_b' ESCAPE '\'"), ["a_b"]);
+    assert_eq!(query(&mut db, r"SELECT s FROM labels WHERE s LIKE 'a\\b' ESCAPE '\'"), [r"a\b"]);
+    assert_eq!(query(&mut db, "SELECT s FROM labels WHERE s NOT LIKE '100!%' ESCAPE '!' AND s LIKE '1%'"), ["100", "1000"]);
+
+    assert!(matches!(query_err(&mut db, "SELECT s FROM labels WHERE s LIKE 'x' ESCAPE 'ab'"),
+        SqliteError::TypeError(message) if message == "ESCAPE expression must be a single character"));
+}
+
+#[test]
+fn like_and_glob_functions() {
+    // LIKE ignores ASCII case while GLOB doesn't.
+    let mut db = open(SAMPLE);
+    assert_eq!(query(&mut db, "SELECT like('g%', name), glob('G*', name), glob('g*', name) FROM apples"),
+        ["1|1|0", "0|0|0", "0|0|0", "1|1|0"]);
+    assert_eq!(query(&mut db, "SELECT name FROM apples WHERE like('%i%', name) AND NOT glob('*[0-9]*', name)"), ["Granny Smith", "Fuji", "Honeycrisp", "Golden Delicious"]);
+
+    let mut db = open(&fixture("text"));
+    assert_eq!(query(&mut db, "SELECT s FROM labels WHERE like('a!_b', s, '!')"), ["a_b"]);
+}
+
+#[test]
+fn replace_instr_and_hex() {
+    let mut db = open(&fixture("text"));
+    assert_eq!(query(&mut db, "SELECT replace('banana', 'an', 'AN'), replace(NULL, 'a', 'b'), replace('abc', '', 'x')"), ["bANANa||abc"]);
+    assert_eq!(query(&mut db, "SELECT instr('banana', 'nan'), instr('banana', 'x'), instr(NULL, 'a'), instr(X'0102', X'02')"), ["3|0||2"]);
+    assert_eq!(query(&mut db, "SELECT hex(X'00ff10'), hex('Az'), hex(12), hex(1.5), hex(NULL)"), ["00FF10|417A|3132|312E35|"]);
+    assert_eq!(query(&mut db, "SELECT s FROM labels WHERE instr(s, '%') > 0 OR hex(s) = '615F62'"), ["100%", "a_b"]);
+}
+
+#[test]
+fn numeric_text_orders_numerically_through_cast() {
+    let mut db = open(&fixture("text"));
+    assert_eq!(query(&mut db, "SELECT n FROM numbered ORDER BY n"), ["10", "100", "2", "2 apples", "9"]);
+    assert_eq!(query(&mut db, "SELECT n FROM numbered ORDER BY CAST(n AS INTEGER)"), ["2", "2 apples", "9", "10", "100"]);
+    assert_eq!(query(&mut db, "SELECT n FROM numbered ORDER BY CAST(n AS REAL) DESC, n DESC"), ["100", "10", "9", "2 apples", "2"]);
+}
+
+#[test]
+fn strict_text_rejects_invalid_utf8() {
+    let mut db = open(&fixture("text"));
+    assert_eq!(query_values(&mut db, "SELECT t FROM raw WHERE id = 2"), [[Value::Text("A\u{FFFD}B".to_string())]]);
+
+    db.set_strict_text(true);
+    assert_eq!(query(&mut db, "SELECT s FROM labels WHERE s LIKE 'a%'"), ["a_b", "axb", "a\\b"]);
+    match query_err(&mut db, "SELECT t FROM raw") {
+        SqliteError::Corrupt { detail, .. } => assert!(detail.starts_with("text value is not valid UTF-8"), "{}", detail),
+        error => panic!("expected Corrupt, got {:?}", error),
+    }
+    // Only the columns the query reads are decoded, and so checked.
+    assert_eq!(query(&mut db, "SELECT id FROM raw"), ["1", "2"]);
+
+    db.set_strict_text(false);
+    assert_eq!(query(&mut db, "SELECT length(t) FROM raw WHERE id = 2"), ["3"]);
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use codecrafters_sqlite::{SqliteError, Value, format_real};
+use common::{fixture, open, query, query_err, query_values};
+
+#[test]
+fn storage_class_comes_from_the_serial_type() {
+    let mut db = open(&fixture("values"));
+    assert_eq!(query_values(&mut db, "SELECT v, t FROM mixed"), [
+        vec![Value::Null, Value::Text("text".into())],
+        vec![Value::Integer(1), Value::Blob(vec![1, 2])],
+        vec![Value::Real(2.5), Value::Text("x".into())],
+        vec![Value::Text("text".into()), Value::Null],
+        vec![Value::Blob(vec![0, 0xff]), Value::Text("3".into())],
+    ]);
+}
+
+#[test]
+fn text_compares_byte_wise() {
+    // Upper case sorts before lower case, and non-ASCII text after all of it.
+    let mut db = open(&fixture("values"));
+    assert_eq!(query(&mut db, "SELECT w FROM words ORDER BY w"), ["Apple", "Zebra", "apple", "zoo", "éclair"]);
+    assert_eq!(query(&mut db, "SELECT MIN(w), MAX(w) FROM words"), ["Apple|éclair"]);
+    assert_eq!(query(&mut db, "SELECT w FROM words WHERE w > 'Zebra' ORDER BY w DESC"), ["éclair", "zoo", "apple"]);
+}
+
+#[test]
+fn nocase_column_ignores_case() {
+    let mut db = open(&fixture("values"));
+    // Through the index on `name`, and with `+name` through a scan of the table.
+    assert_eq!(query(&mut db, "SELECT color FROM fruits WHERE name = 'Apple'"), ["red", "green"]);
+    assert_eq!(query(&mut db, "SELECT color FROM fruits WHERE +name = 'Apple'"), ["red", "green"]);
+    assert_eq!(query(&mut db, "SELECT name FROM fruits ORDER BY name, color"), ["APPLE", "apple", "Banana", "cherry"]);
+    assert_eq!(query(&mut db, "SELECT COUNT(DISTINCT name) FROM fruits"), ["3"]);
+    // Other columns keep the BINARY collation.
+    assert!(query(&mut db, "SELECT name FROM fruits WHERE color = 'RED'").is_empty());
+}
+
+#[test]
+fn reals_print_like_the_sqlite3_shell() {
+    // Integral reals keep a `.0`, and at most 15 significant digits are printed.
+    for (value, printed) in [
+        (3.0, "3.0"),
+        (100.0, "100.0"),
+        (-0.5, "-0.5"),
+        (2.75, "2.75"),
+        (0.1 + 0.2, "0.3"),
+        (1.0 / 3.0, "0.333333333333333"),
+        (123456789012345678.0, "1.23456789012346e+17"),
+        (1e15, "1.0e+15"),
+        (1e300, "1.0e+300"),
+        (1e-300, "1.0e-300"),
+        (-2.5e-7, "-2.5e-07"),
+    ] {
+        assert_eq!(format_real(value), printed);
+    }
+
+    let mut db = open(&fixture("values"));
+    assert_eq!(query(&mut db, "SELECT 3.0, 1.0 / 3, 1e300, v FROM mixed WHERE v = 2.5"), ["3.0|0.333333333333333|1.0e+300|2.5"]);
+}
+
+#[test]
+fn integers_and_reals_compare_numerically() {
+    let mut db = open(&fixture("values"));
+    assert_eq!(query(&mut db, "SELECT MIN(n), MAX(n), SUM(n), AVG(n) FROM numbers"), ["-1.5|10|14.0|3.5"]);
+    assert_eq!(query(&mut db, "SELECT n FROM numbers ORDER BY n"), ["-1.5", "2.5", "3", "10"]);
+    assert_eq!(query(&mut db, "SELECT n FROM numbers WHERE n > 2.75"), ["3", "10"]);
+    // As reals these would all be equal.
+    assert_eq!(query(&mut db, "SELECT MAX(n), MIN(n) FROM big"), ["9223372036854775807|9223372036854775805"]);
+    assert_eq!(query(&mut db, "SELECT n FROM big WHERE n > 9223372036854775806"), ["9223372036854775807"]);
+}
+
+#[test]
+fn storage_classes_order_null_numbers_text_blob() {
+    let mut db = open(&fixture("values"));
+    assert_eq!(query_values(&mut db, "SELECT v FROM mixed ORDER BY v").concat(), [
+        Value::Null, Value::Integer(1), Value::Real(2.5), Value::Text("text".into()), Value::Blob(vec![0, 0xff]),
+    ]);
+    assert_eq!(query_values(&mut db, "SELECT MIN(v), MAX(v) FROM mixed"), [vec![Value::Integer(1), Value::Blob(vec![0, 0xff])]]);
+
+    let ascending = [Value::Null, Value::Integer(-5), Value::Real(0.5), Value::Integer(1), Value::Text("".into()), Value::Text("a".into()), Value::Blob(vec![])];
+    for pair in ascending.windows(2) {
+        assert!(pair[0] < pair[1], "{:?} < {:?}", pair[0], pair[1]);
+    }
+}
+
+#[test]
+fn order_by_collate() {
+    let mut db = open(&fixture("values"));
+    assert_eq!(query(&mut db, "SELECT w FROM words ORDER BY w"), ["Apple", "Zebra", "apple", "zoo", "éclair"]);
+    // Equal keys keep the order the rows were read in.
+    assert_eq!(query(&mut db, "SELECT w FROM words ORDER BY w COLLATE NOCASE"), ["apple", "Apple", "Zebra", "zoo", "éclair"]);
+    assert_eq!(query(&mut db, "SELECT w FROM words ORDER BY w COLLATE nocase DESC"), ["éclair", "zoo", "Zebra", "apple", "Apple"]);
+    // BINARY overrides the column's declared NOCASE.
+    assert_eq!(query(&mut db, "SELECT name FROM fruits ORDER BY name"), ["apple", "APPLE", "Banana", "cherry"]);
+    assert_eq!(query(&mut db, "SELECT name FROM fruits ORDER BY name COLLATE BINARY"), ["APPLE", "Banana", "apple", "cherry"]);
+    assert!(matches!(query_err(&mut db, "SELECT w FROM words ORDER BY w COLLATE nope"),
+        SqliteError::UnsupportedSql(m) if m == "no such collation sequence: nope"));
+}
+
+#[test]
+fn in_lists_apply_the_column_affinity() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE id IN ('1', 2)"), ["1", "2"]);
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE price IN ('2', 40.0)"), ["1", "3", "4"]);
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE id NOT IN ('1', 2.0)"), ["3", "4"]);
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE quantity IN (NULL, 2)"), ["2"]);
+    // Without a column there is no affinity to apply.
+    assert_eq!(query(&mut db, "SELECT 1 IN ('1'), '1' IN (1)"), ["0|0"]);
+
+    // A TEXT column turns numbers into text, so 1000.0 doesn't match '1000'.
+    let mut db = open(&fixture("text"));
+    assert_eq!(query(&mut db, "SELECT s FROM labels WHERE s IN (100, 1000.0)"), ["100"]);
+}
+
+#[test]
+fn null_operands() {
+    let mut db = open(&fixture("orders"));
+    // NULL on the left, on the right and on both sides.
+    for op in ["+", "-", "*", "/", "%", "||", "=", "!=", "<", ">="] {
+        let sql = format!("SELECT NULL {0} 1, 1 {0} NULL, NULL {0} NULL", op);
+        assert_eq!(query_values(&mut db, &sql), [[Value::Null, Value::Null, Value::Null]], "{}", sql);
+    }
+    assert_eq!(query(&mut db, "SELECT NULL IS NULL, NULL IS 1, 1 IS NOT NULL, (NULL = 1) IS NULL"), ["1|0|1|1"]);
+
+    // Bag's quantity is NULL, so is everything computed from it, and no comparison holds.
+    assert_eq!(query(&mut db, "SELECT id, quantity + 1, item || quantity, quantity = 2 FROM orders WHERE id > 1"), ["2|3|book2|1", "3|||", "4|6|pen5|0"]);
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE NOT (quantity = 10)"), ["2", "4"]);
+}
+
+#[test]
+fn integer_and_real_division() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query_values(&mut db, "SELECT 5 / 2, 5.0 / 2, 5 / 2.0"), [[Value::Integer(2), Value::Real(2.5), Value::Real(2.5)]]);
+    // Integer division truncates toward zero and the remainder takes the dividend's sign.
+    assert_eq!(query(&mut db, "SELECT -7 / 2, 7 % 3, -7 % 3, 7 % -3, 7.5 % 2"), ["-3|1|-1|1|1.0"]);
+    assert_eq!(query(&mut db, "SELECT 5 / 0, 5 % 0, 5.0 / 0"), ["||"]);
+    // Text converts by its numeric prefix.
+    assert_eq!(query(&mut db, "SELECT '7' / 2, '3 apples' + 1, 'x' * 2"), ["3|4|0"]);
+    assert_eq!(query(&mut db, "SELECT price / quantity, price * 1.0 / quantity FROM orders WHERE id = 2"), ["7|7.5"]);
+}
+
+#[test]
+fn debug_prints_sql_literals() {
+    assert_eq!(format!("{:?}", Value::Null), "NULL");
+    assert_eq!(format!("{:?}", Value::Integer(-7)), "-7");
+    assert_eq!(format!("{:?}", Value::Real(2.0)), "2.0");
+    assert_eq!(format!("{:?}", Value::Text("it's".to_string())), "'it''s'");
+    assert_eq!(format!("{:?}", Value::Blob(vec![0x01, 0xab])), "X'01AB'");
+    assert_eq!(format!("{:?}", [Value::Integer(1), Value::Text("a".to_string())]), "[1, 'a']");
+}
+
+#[test]
+fn unary_plus_and_minus() {
+    let mut db = open(&fixture("orders"));
+    assert_eq!(query(&mut db, "SELECT -price, +price, - -price, -(price * 2) FROM orders WHERE id < 3"), ["-2|2|2|-4", "-15|15|15|-30"]);
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE -price < -10"), ["2", "3"]);
+    assert_eq!(query(&mut db, "SELECT id FROM orders WHERE price - 20 > -10"), ["2", "3"]);
+    // Unary minus binds tighter than any binary operator.
+    assert_eq!(query(&mut db, "SELECT 2 - -3, -2 * 3, -3 % 2 FROM orders LIMIT 1"), ["5|-6|-1"]);
+
+    assert_eq!(
+        query_values(&mut db, "SELECT -NULL, -'3', -2.5, +'a', -9223372036854775808 FROM orders LIMIT 1"),
+        [vec![Value::Null, Value::Integer(-3), Value::Real(-2.5), Value::Text("a".to_string()), Value::Integer(i64::MIN)]]
+    );
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use codecrafters_sqlite::Database;
+use common::{fixture, open, query, temp_dir};
+use std::fs;
+
+const WAL_HEADER_SIZE: usize = 32;
+const FRAME_HEADER_SIZE: usize = 24;
+
+fn read_u32(bytes: &[u8], big_endian: bool) -> u32 {
+    let bytes = bytes[..4].try_into().expect("four bytes");
+    if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+}
+
+/// SQLite's WAL checksum over `data`, continuing from `seed`.
+fn wal_checksum(data: &[u8], seed: (u32, u32), big_endian: bool) -> (u32, u32) {
+    let (mut s0, mut s1) = seed;
+    for chunk in data.chunks_exact(8) {
+        s0 = s0.wrapping_add(read_u32(&chunk[..4], big_endian)).wrapping_add(s1);
+        s1 = s1.wrapping_add(read_u32(&chunk[4..], big_endian)).wrapping_add(s0);
+    }
+    (s0, s1)
+}
+
+/// Copies the `wal` fixture into a directory of its own, appending a frame that rewrites the
+/// table's page with `one` changed to `ONE`, as the first frame of a transaction still being
+/// written or, with `commit`, as a committed transaction of its own.
+fn with_frame_appended(test: &str, commit: bool) -> Database {
+    let mut wal = fs::read(fixture("wal") + "-wal").unwrap();
+    let big_endian = wal[3] == 0x83;
+    let page_size = u32::from_be_bytes(wal[8..12].try_into().unwrap()) as usize;
+
+    let last_frame = wal.len() - FRAME_HEADER_SIZE - page_size;
+    assert!(last_frame > WAL_HEADER_SIZE);
+    let mut frame = wal[last_frame..].to_vec();
+    assert_eq!(u32::from_be_bytes(frame[0..4].try_into().unwrap()), 2, "the last frame holds the table's page");
+    let seed = (u32::from_be_bytes(frame[16..20].try_into().unwrap()), u32::from_be_bytes(frame[20..24].try_into().unwrap()));
+
+    let db_size = if commit { frame[4..8].to_vec() } else { vec![0; 4] };
+    frame[4..8].copy_from_slice(&db_size);
+    let page = &mut frame[FRAME_HEADER_SIZE..];
+    let at = page.windows(3).position(|w| w == b"one").expect("the page holds 'one'");
+    page[at..at + 3].copy_from_slice(b"ONE");
+    let checksum = wal_checksum(&frame[0..8], seed, big_endian);
+    let (s0, s1) = wal_checksum(&frame[FRAME_HEADER_SIZE..], checksum, big_endian);
+    frame[16..20].copy_from_slice(&s0.to_be_bytes());
+    frame[20..24].copy_from_slice(&s1.to_be_bytes());
+    wal.extend(frame);
+
+    let dir = temp_dir(test);
+    let db_path = dir.join("wal.db").to_str().unwrap().to_string();
+    fs::copy(fixture("wal"), &db_path).unwrap();
+    fs::write(format!("{}-wal", db_path), wal).unwrap();
+    open(&db_path)
+}
+
+#[test]
+fn committed_frames_are_read() {
+    // The database file itself has no tables yet; `t` and its rows are only in the WAL.
+    let mut db = open(&fixture("wal"));
+    assert_eq!(db.wal_commit_frame(), Some(3));
+    assert_eq!(query(&mut db, "SELECT * FROM t"), ["1|one", "2|two", "3|three"]);
+}
+
+#[test]
+fn trailing_uncommitted_frames_are_ignored() {
+    let mut db = with_frame_appended("trailing_uncommitted_frames_are_ignored", false);
+    assert_eq!(db.wal_commit_frame(), Some(3));
+    assert_eq!(query(&mut db, "SELECT v FROM t WHERE id = 1"), ["one"]);
+}
+
+#[test]
+fn appended_frame_is_read_once_committed() {
+    let mut db = with_frame_appended("appended_frame_is_read_once_committed", true);
+    assert_eq!(db.wal_commit_frame(), Some(4));
+    assert_eq!(query(&mut db, "SELECT v FROM t WHERE id = 1"), ["ONE"]);
+}